@@ -18,10 +18,13 @@ impl ASCII {
 	pub const GT: u8 = b'>';
 	pub const HASH: u8 = b'#';
 	pub const LF: u8 = b'\n';
+	pub const FF: u8 = b'\x0C';
+	pub const LT: u8 = b'<';
 	pub const PAREN_CLOSE: u8 = b')';
 	pub const PAREN_OPEN: u8 = b'(';
 	pub const PERCENTAGE: u8 = b'%';
 	pub const PLUS: u8 = b'+';
+	pub const QUESTION: u8 = b'?';
 	pub const QUOTE: u8 = b'"';
 	pub const SEMICOLON: u8 = b';';
 	pub const SINGLE_QUOTE: u8 = b'\'';
@@ -1,14 +1,15 @@
-pub fn add(left: u64, right: u64) -> u64 {
-	left + right
-}
+//! Typed CSS syntax tree.
+//!
+//! Nodes borrow from the input and know their [`Span`] in it.
+//! Preludes and declaration values are kept as [`ComponentValue`]s,
+//! including whitespace and comments, so nothing of the source is lost
+pub use rule::{AtRule, Block, Comment, Declaration, Item, QualifiedRule, Stylesheet};
+pub use span::Span;
+pub use token::{Token, TokenBuf};
+pub use value::{Bracket, ComponentValue, Function, SimpleBlock};
 
-#[cfg(test)]
-mod tests {
-	use super::*;
-
-	#[test]
-	fn it_works() {
-		let result = add(2, 2);
-		assert_eq!(result, 4);
-	}
-}
+mod rule;
+mod span;
+mod token;
+mod value;
+pub mod visit;
@@ -0,0 +1,96 @@
+use crate::{ComponentValue, Span, Token};
+
+/// Root of the tree, every top level rule and comment in source order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stylesheet<'a> {
+	pub items: Vec<Item<'a>>,
+}
+
+/// Entry of a [`Stylesheet`] or a [`Block`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item<'a> {
+	AtRule(AtRule<'a>),
+	Comment(Comment<'a>),
+	/// Only ever inside a block
+	Declaration(Declaration<'a>),
+	QualifiedRule(QualifiedRule<'a>),
+	/// Input of an item that failed to parse, only there if parser recovers from errors
+	Invalid(Span),
+}
+
+/// `@media screen { ... }` or `@import url(a.css);`
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtRule<'a> {
+	/// Including `@`, e.g. `@media`
+	pub name: &'a [u8],
+	/// Everything between name and block or `;`
+	pub prelude: Vec<ComponentValue<'a>>,
+	/// [`None`] for statement at-rules, e.g. `@import`
+	pub block: Option<Block<'a>>,
+	pub span: Span,
+}
+
+/// Selector list followed by a block, e.g. `a:hover, .b { ... }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct QualifiedRule<'a> {
+	pub prelude: Vec<ComponentValue<'a>>,
+	pub block: Block<'a>,
+	pub span: Span,
+}
+
+/// `{ ... }` of a rule, declarations and nested rules in source order
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block<'a> {
+	pub items: Vec<Item<'a>>,
+	pub span: Span,
+}
+
+/// `color: red !important`, span includes trailing `;` if any
+#[derive(Clone, Debug, PartialEq)]
+pub struct Declaration<'a> {
+	pub name: &'a [u8],
+	/// Everything after `:`
+	pub value: Vec<ComponentValue<'a>>,
+	pub span: Span,
+}
+
+/// `/* text */` outside of preludes and values, those keep comments as [`Token::Comment`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Comment<'a> {
+	/// Without `/*` and `*/`
+	pub text: &'a [u8],
+	pub span: Span,
+}
+
+impl<'a> Item<'a> {
+	#[inline]
+	pub fn span(&self) -> Span {
+		match self {
+			Item::AtRule(AtRule { span, .. })
+			| Item::Comment(Comment { span, .. })
+			| Item::Declaration(Declaration { span, .. })
+			| Item::QualifiedRule(QualifiedRule { span, .. })
+			| Item::Invalid(span) => *span,
+		}
+	}
+}
+
+impl<'a> Declaration<'a> {
+	/// Value ends with `!important`
+	#[inline]
+	pub fn is_important(&self) -> bool {
+		let mut tokens = self.value.iter().rev().filter_map(|value| match value {
+			ComponentValue::Token {
+				token: Token::Whitespace | Token::Comment(_),
+				..
+			} => None,
+			value => Some(value.token()),
+		});
+
+		matches!(
+			(tokens.next(), tokens.next()),
+			(Some(Some(Token::Ident(ident))), Some(Some(Token::Delim(b'!'))))
+				if ident.eq_ignore_ascii_case(b"important")
+		)
+	}
+}
@@ -0,0 +1,36 @@
+/// Byte range of the input a node was parsed from, `end` is exclusive
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Span {
+	pub start: usize,
+	pub end: usize,
+}
+
+impl Span {
+	#[inline(always)]
+	pub const fn new(start: usize, end: usize) -> Self {
+		Self { start, end }
+	}
+
+	#[inline(always)]
+	pub const fn len(&self) -> usize {
+		self.end - self.start
+	}
+
+	#[inline(always)]
+	pub const fn is_empty(&self) -> bool {
+		self.start == self.end
+	}
+
+	/// Bytes of `input` this span covers
+	#[inline]
+	pub fn source<'a>(&self, input: &'a [u8]) -> &'a [u8] {
+		&input[self.start..self.end]
+	}
+}
+
+impl From<Span> for std::ops::Range<usize> {
+	#[inline(always)]
+	fn from(span: Span) -> Self {
+		span.start..span.end
+	}
+}
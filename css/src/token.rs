@@ -0,0 +1,176 @@
+// WARNING: KEEP IT COPYABLE
+#[derive(Clone, Copy, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Token<'a> {
+	/// Comment token - will not include starting `/*` and ending `*/`
+	Comment(&'a [u8]), // Formatter should preserve comments
+
+	Ident(&'a [u8]),
+	Function(&'a [u8]),
+	/// Unquoted url token - will not include surrounding `url(` and `)`
+	URL(&'a [u8]),
+	/// Malformed unquoted url, e.g. `url(a b)`
+	BadURL,
+	AtRule(&'a [u8]),
+	Hash(&'a [u8]),
+	/// String token - will not include surrounding quotes
+	String(&'a [u8]),
+	Number(&'a [u8]),
+	/// Percentage token - will not include trailing `%`
+	Percentage(&'a [u8]),
+	Dimension {
+		value: &'a [u8],
+		unit: &'a [u8],
+	},
+	/// Unicode range token, e.g. `U+0025-00FF` or `u+4??`
+	UnicodeRange(&'a [u8]),
+	/// `<!--`
+	CDO,
+	/// `-->`
+	CDC,
+
+	Delim(u8),
+	/// Whitespace token - any amount of whitespace(`\s*`)
+	Whitespace,
+	Colon,
+	Semicolon,
+	Comma,
+	BracketRoundOpen,
+	BracketRoundClose,
+	BracketSquareOpen,
+	BracketSquareClose,
+	BracketCurlyOpen,
+	BracketCurlyClose,
+}
+
+/// Owned [`Token`], so that it could outlive the input, e.g. in errors
+#[derive(Clone, PartialEq)]
+pub struct TokenBuf {
+	/// Token with its bytes left out
+	kind: Token<'static>,
+	bytes: Box<[u8]>,
+	/// Where `unit` of [`Token::Dimension`] starts, length of `bytes` otherwise
+	split: usize,
+}
+
+impl<'a> Token<'a> {
+	/// Replaces every byte slice of the token with what `f` returns for it, in order
+	#[inline]
+	pub fn map_bytes<'b>(self, mut f: impl FnMut(&'a [u8]) -> &'b [u8]) -> Token<'b> {
+		match self {
+			Token::Comment(bytes) => Token::Comment(f(bytes)),
+			Token::Ident(bytes) => Token::Ident(f(bytes)),
+			Token::Function(bytes) => Token::Function(f(bytes)),
+			Token::AtRule(bytes) => Token::AtRule(f(bytes)),
+			Token::Hash(bytes) => Token::Hash(f(bytes)),
+			Token::String(bytes) => Token::String(f(bytes)),
+			Token::Number(bytes) => Token::Number(f(bytes)),
+			Token::Percentage(bytes) => Token::Percentage(f(bytes)),
+			Token::Dimension { value, unit } => Token::Dimension {
+				value: f(value),
+				unit: f(unit),
+			},
+			Token::UnicodeRange(bytes) => Token::UnicodeRange(f(bytes)),
+			Token::URL(bytes) => Token::URL(f(bytes)),
+			Token::BadURL => Token::BadURL,
+			Token::CDO => Token::CDO,
+			Token::CDC => Token::CDC,
+			Token::Delim(delim) => Token::Delim(delim),
+			Token::Whitespace => Token::Whitespace,
+			Token::Colon => Token::Colon,
+			Token::Semicolon => Token::Semicolon,
+			Token::Comma => Token::Comma,
+			Token::BracketRoundOpen => Token::BracketRoundOpen,
+			Token::BracketRoundClose => Token::BracketRoundClose,
+			Token::BracketSquareOpen => Token::BracketSquareOpen,
+			Token::BracketSquareClose => Token::BracketSquareClose,
+			Token::BracketCurlyOpen => Token::BracketCurlyOpen,
+			Token::BracketCurlyClose => Token::BracketCurlyClose,
+		}
+	}
+}
+
+impl TokenBuf {
+	#[inline]
+	pub fn token(&self) -> Token<'_> {
+		let mut rest = &*self.bytes;
+		let mut split = self.split;
+
+		self.kind.map_bytes(|_| {
+			let (bytes, tail) = rest.split_at(split);
+
+			rest = tail;
+			split = tail.len();
+
+			bytes
+		})
+	}
+}
+
+impl From<Token<'_>> for TokenBuf {
+	#[inline]
+	fn from(token: Token<'_>) -> Self {
+		let mut bytes = Vec::new();
+		let kind = token.map_bytes(|part| {
+			bytes.extend_from_slice(part);
+			&[]
+		});
+
+		let split = match token {
+			Token::Dimension { value, .. } => value.len(),
+			_ => bytes.len(),
+		};
+
+		Self {
+			kind,
+			bytes: bytes.into(),
+			split,
+		}
+	}
+}
+
+impl std::fmt::Debug for Token<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		#[inline]
+		fn str(bytes: &[u8]) -> std::borrow::Cow<'_, str> {
+			String::from_utf8_lossy(bytes)
+		}
+
+		match self {
+			Token::Comment(bytes) => write!(f, "Comment(\"{}\")", str(bytes)),
+			Token::Ident(bytes) => write!(f, "Ident(\"{}\")", str(bytes)),
+			Token::Function(bytes) => write!(f, "Function(\"{}\")", str(bytes)),
+			Token::AtRule(bytes) => write!(f, "AtRule(\"{}\")", str(bytes)),
+			Token::Hash(bytes) => write!(f, "Hash(\"{}\")", str(bytes)),
+			Token::String(bytes) => write!(f, "String(\"{}\")", str(bytes)),
+			Token::Number(bytes) => write!(f, "Number(\"{}\")", str(bytes)),
+			Token::Percentage(bytes) => write!(f, "Percentage(\"{}\")", str(bytes)),
+			Token::Dimension { value, unit } => {
+				write!(f, "Dimension(\"{}\", \"{}\")", str(value), str(unit))
+			}
+			Token::UnicodeRange(bytes) => write!(f, "UnicodeRange(\"{}\")", str(bytes)),
+			Token::URL(bytes) => write!(f, "URL(\"{}\")", str(bytes)),
+			Token::BadURL => f.write_str("BadURL"),
+			Token::CDO => f.write_str("CDO"),
+			Token::CDC => f.write_str("CDC"),
+			Token::Delim(d) => write!(f, "Delim({})", *d as char),
+			Token::Whitespace => f.write_str("Whitespace"),
+			Token::Colon => f.write_str("Colon"),
+			Token::Semicolon => f.write_str("Semicolon"),
+			Token::Comma => f.write_str("Comma"),
+			Token::BracketRoundOpen => f.write_str("BracketRoundOpen"),
+			Token::BracketRoundClose => f.write_str("BracketRoundClose"),
+			Token::BracketSquareOpen => f.write_str("BracketSquareOpen"),
+			Token::BracketSquareClose => f.write_str("BracketSquareClose"),
+			Token::BracketCurlyOpen => f.write_str("BracketCurlyOpen"),
+			Token::BracketCurlyClose => f.write_str("BracketCurlyClose"),
+		}
+	}
+}
+
+impl std::fmt::Debug for TokenBuf {
+	#[inline]
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		self.token().fmt(f)
+	}
+}
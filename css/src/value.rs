@@ -0,0 +1,75 @@
+use crate::{Span, Token};
+
+/// Building block of preludes and declaration values
+#[derive(Clone, Debug, PartialEq)]
+pub enum ComponentValue<'a> {
+	/// Any token, but a function or an opening bracket, those start nested values.
+	/// Whitespace and comments are kept
+	Token {
+		token: Token<'a>,
+		span: Span,
+	},
+	Function(Function<'a>),
+	Block(SimpleBlock<'a>),
+}
+
+/// `calc(...)` or `:is(...)`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function<'a> {
+	/// Without `(`
+	pub name: &'a [u8],
+	pub arguments: Vec<ComponentValue<'a>>,
+	pub span: Span,
+}
+
+/// `(...)`, `[...]` or `{...}` inside of a prelude or a value
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimpleBlock<'a> {
+	pub bracket: Bracket,
+	pub values: Vec<ComponentValue<'a>>,
+	pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bracket {
+	Round,
+	Square,
+	Curly,
+}
+
+impl<'a> ComponentValue<'a> {
+	#[inline]
+	pub fn span(&self) -> Span {
+		match self {
+			ComponentValue::Token { span, .. }
+			| ComponentValue::Function(Function { span, .. })
+			| ComponentValue::Block(SimpleBlock { span, .. }) => *span,
+		}
+	}
+
+	/// [`None`] for functions and blocks
+	#[inline]
+	pub fn token(&self) -> Option<Token<'a>> {
+		match self {
+			ComponentValue::Token { token, .. } => Some(*token),
+			_ => None,
+		}
+	}
+
+	#[inline]
+	pub fn is_whitespace(&self) -> bool {
+		self.token() == Some(Token::Whitespace)
+	}
+}
+
+impl Bracket {
+	/// Opening and closing tokens
+	#[inline]
+	pub fn tokens(self) -> (Token<'static>, Token<'static>) {
+		match self {
+			Bracket::Round => (Token::BracketRoundOpen, Token::BracketRoundClose),
+			Bracket::Square => (Token::BracketSquareOpen, Token::BracketSquareClose),
+			Bracket::Curly => (Token::BracketCurlyOpen, Token::BracketCurlyClose),
+		}
+	}
+}
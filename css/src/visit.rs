@@ -0,0 +1,283 @@
+//! Traversal of the syntax tree, e.g. for codemods.
+//!
+//! Every `visit_*` method of [`Visit`] and [`VisitMut`] walks into children of the node
+//! by default, through free `walk_*` functions of the same name. Override a method to act
+//! on a node, and call its `walk_*` function to keep going deeper:
+//!
+//! ```
+//! use css::visit::{self, VisitMut};
+//! use css::Declaration;
+//!
+//! struct RenameColor;
+//!
+//! impl<'a> VisitMut<'a> for RenameColor {
+//!     fn visit_declaration_mut(&mut self, declaration: &mut Declaration<'a>) {
+//!         if declaration.name == b"colour" {
+//!             declaration.name = b"color";
+//!         }
+//!
+//!         visit::walk_declaration_mut(self, declaration);
+//!     }
+//! }
+//! ```
+//!
+//! Nodes added to the tree have no input to point to, leave their span [`Span::default`]
+//!
+//! [`Span::default`]: crate::Span
+use crate::{AtRule, Block, Comment, ComponentValue, Declaration, Item, QualifiedRule, Stylesheet};
+
+/// Read only traversal, see [module docs](self)
+pub trait Visit<'a> {
+	#[inline]
+	fn visit_stylesheet(&mut self, stylesheet: &Stylesheet<'a>) {
+		walk_stylesheet(self, stylesheet);
+	}
+
+	/// Entries of a stylesheet or a block
+	#[inline]
+	fn visit_items(&mut self, items: &[Item<'a>]) {
+		walk_items(self, items);
+	}
+
+	#[inline]
+	fn visit_item(&mut self, item: &Item<'a>) {
+		walk_item(self, item);
+	}
+
+	#[inline]
+	fn visit_at_rule(&mut self, rule: &AtRule<'a>) {
+		walk_at_rule(self, rule);
+	}
+
+	#[inline]
+	fn visit_qualified_rule(&mut self, rule: &QualifiedRule<'a>) {
+		walk_qualified_rule(self, rule);
+	}
+
+	/// Prelude of a qualified rule, e.g. `a:hover, .b`
+	#[inline]
+	fn visit_selector(&mut self, selector: &[ComponentValue<'a>]) {
+		walk_values(self, selector);
+	}
+
+	#[inline]
+	fn visit_block(&mut self, block: &Block<'a>) {
+		walk_block(self, block);
+	}
+
+	#[inline]
+	fn visit_declaration(&mut self, declaration: &Declaration<'a>) {
+		walk_declaration(self, declaration);
+	}
+
+	#[inline]
+	fn visit_comment(&mut self, _comment: &Comment<'a>) {}
+
+	/// Every value of preludes, selectors and declarations, including nested ones
+	#[inline]
+	fn visit_component_value(&mut self, value: &ComponentValue<'a>) {
+		walk_component_value(self, value);
+	}
+}
+
+/// Traversal that can change, add or remove nodes, see [module docs](self)
+pub trait VisitMut<'a> {
+	#[inline]
+	fn visit_stylesheet_mut(&mut self, stylesheet: &mut Stylesheet<'a>) {
+		walk_stylesheet_mut(self, stylesheet);
+	}
+
+	/// Entries of a stylesheet or a block, override to add or remove some
+	#[inline]
+	fn visit_items_mut(&mut self, items: &mut Vec<Item<'a>>) {
+		walk_items_mut(self, items);
+	}
+
+	#[inline]
+	fn visit_item_mut(&mut self, item: &mut Item<'a>) {
+		walk_item_mut(self, item);
+	}
+
+	#[inline]
+	fn visit_at_rule_mut(&mut self, rule: &mut AtRule<'a>) {
+		walk_at_rule_mut(self, rule);
+	}
+
+	#[inline]
+	fn visit_qualified_rule_mut(&mut self, rule: &mut QualifiedRule<'a>) {
+		walk_qualified_rule_mut(self, rule);
+	}
+
+	/// Prelude of a qualified rule, e.g. `a:hover, .b`
+	#[inline]
+	fn visit_selector_mut(&mut self, selector: &mut Vec<ComponentValue<'a>>) {
+		walk_values_mut(self, selector);
+	}
+
+	#[inline]
+	fn visit_block_mut(&mut self, block: &mut Block<'a>) {
+		walk_block_mut(self, block);
+	}
+
+	#[inline]
+	fn visit_declaration_mut(&mut self, declaration: &mut Declaration<'a>) {
+		walk_declaration_mut(self, declaration);
+	}
+
+	#[inline]
+	fn visit_comment_mut(&mut self, _comment: &mut Comment<'a>) {}
+
+	/// Every value of preludes, selectors and declarations, including nested ones
+	#[inline]
+	fn visit_component_value_mut(&mut self, value: &mut ComponentValue<'a>) {
+		walk_component_value_mut(self, value);
+	}
+}
+
+#[inline]
+pub fn walk_stylesheet<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, stylesheet: &Stylesheet<'a>) {
+	visitor.visit_items(&stylesheet.items);
+}
+
+#[inline]
+pub fn walk_items<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, items: &[Item<'a>]) {
+	for item in items {
+		visitor.visit_item(item);
+	}
+}
+
+#[inline]
+pub fn walk_item<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, item: &Item<'a>) {
+	match item {
+		Item::AtRule(rule) => visitor.visit_at_rule(rule),
+		Item::Comment(comment) => visitor.visit_comment(comment),
+		Item::Declaration(declaration) => visitor.visit_declaration(declaration),
+		Item::QualifiedRule(rule) => visitor.visit_qualified_rule(rule),
+		Item::Invalid(_) => {}
+	}
+}
+
+#[inline]
+pub fn walk_at_rule<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, rule: &AtRule<'a>) {
+	walk_values(visitor, &rule.prelude);
+
+	if let Some(block) = &rule.block {
+		visitor.visit_block(block);
+	}
+}
+
+#[inline]
+pub fn walk_qualified_rule<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, rule: &QualifiedRule<'a>) {
+	visitor.visit_selector(&rule.prelude);
+	visitor.visit_block(&rule.block);
+}
+
+#[inline]
+pub fn walk_block<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, block: &Block<'a>) {
+	visitor.visit_items(&block.items);
+}
+
+#[inline]
+pub fn walk_declaration<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, declaration: &Declaration<'a>) {
+	walk_values(visitor, &declaration.value);
+}
+
+#[inline]
+pub fn walk_component_value<'a, V: Visit<'a> + ?Sized>(
+	visitor: &mut V,
+	value: &ComponentValue<'a>,
+) {
+	match value {
+		ComponentValue::Token { .. } => {}
+		ComponentValue::Function(function) => walk_values(visitor, &function.arguments),
+		ComponentValue::Block(block) => walk_values(visitor, &block.values),
+	}
+}
+
+/// Visits each of `values`
+#[inline]
+pub fn walk_values<'a, V: Visit<'a> + ?Sized>(visitor: &mut V, values: &[ComponentValue<'a>]) {
+	for value in values {
+		visitor.visit_component_value(value);
+	}
+}
+
+#[inline]
+pub fn walk_stylesheet_mut<'a, V: VisitMut<'a> + ?Sized>(
+	visitor: &mut V,
+	stylesheet: &mut Stylesheet<'a>,
+) {
+	visitor.visit_items_mut(&mut stylesheet.items);
+}
+
+#[inline]
+pub fn walk_items_mut<'a, V: VisitMut<'a> + ?Sized>(visitor: &mut V, items: &mut Vec<Item<'a>>) {
+	for item in items {
+		visitor.visit_item_mut(item);
+	}
+}
+
+#[inline]
+pub fn walk_item_mut<'a, V: VisitMut<'a> + ?Sized>(visitor: &mut V, item: &mut Item<'a>) {
+	match item {
+		Item::AtRule(rule) => visitor.visit_at_rule_mut(rule),
+		Item::Comment(comment) => visitor.visit_comment_mut(comment),
+		Item::Declaration(declaration) => visitor.visit_declaration_mut(declaration),
+		Item::QualifiedRule(rule) => visitor.visit_qualified_rule_mut(rule),
+		Item::Invalid(_) => {}
+	}
+}
+
+#[inline]
+pub fn walk_at_rule_mut<'a, V: VisitMut<'a> + ?Sized>(visitor: &mut V, rule: &mut AtRule<'a>) {
+	walk_values_mut(visitor, &mut rule.prelude);
+
+	if let Some(block) = &mut rule.block {
+		visitor.visit_block_mut(block);
+	}
+}
+
+#[inline]
+pub fn walk_qualified_rule_mut<'a, V: VisitMut<'a> + ?Sized>(
+	visitor: &mut V,
+	rule: &mut QualifiedRule<'a>,
+) {
+	visitor.visit_selector_mut(&mut rule.prelude);
+	visitor.visit_block_mut(&mut rule.block);
+}
+
+#[inline]
+pub fn walk_block_mut<'a, V: VisitMut<'a> + ?Sized>(visitor: &mut V, block: &mut Block<'a>) {
+	visitor.visit_items_mut(&mut block.items);
+}
+
+#[inline]
+pub fn walk_declaration_mut<'a, V: VisitMut<'a> + ?Sized>(
+	visitor: &mut V,
+	declaration: &mut Declaration<'a>,
+) {
+	walk_values_mut(visitor, &mut declaration.value);
+}
+
+#[inline]
+pub fn walk_component_value_mut<'a, V: VisitMut<'a> + ?Sized>(
+	visitor: &mut V,
+	value: &mut ComponentValue<'a>,
+) {
+	match value {
+		ComponentValue::Token { .. } => {}
+		ComponentValue::Function(function) => walk_values_mut(visitor, &mut function.arguments),
+		ComponentValue::Block(block) => walk_values_mut(visitor, &mut block.values),
+	}
+}
+
+/// Visits each of `values`
+#[inline]
+pub fn walk_values_mut<'a, V: VisitMut<'a> + ?Sized>(
+	visitor: &mut V,
+	values: &mut [ComponentValue<'a>],
+) {
+	for value in values {
+		visitor.visit_component_value_mut(value);
+	}
+}
@@ -24,11 +24,20 @@ fn main(b: &mut Bencher) {
 
 	assert!(before.len() > 100000);
 
-	format(&before, &mut res).expect("formatted result");
+	format(&before, &mut res, FormatOptions::default()).expect("formatted result");
 
 	assert_eq!(res, after.as_bytes());
 
-	b.iter(|| format(before.as_str(), &mut Void {}))
+	let mut session = Session::default();
+
+	b.iter(|| {
+		format_with_session(
+			&mut session,
+			before.as_str(),
+			&mut Void {},
+			FormatOptions::default(),
+		)
+	})
 }
 
 impl std::io::Write for Void {
@@ -1,20 +1,90 @@
-pub use formatter::Error;
+pub use formatter::{Error, OwnedError, Session};
+pub use position::Position;
 
+#[cfg(feature = "simd-tokenizer")]
+pub use parser::{compare as compare_tokenizers, Divergence};
+
+use ::css::Stylesheet;
+
+use crate::FormatOptions;
 use formatter::Formatter;
-use parser::Parser;
 
-use parser::Cache;
+pub fn format<'a>(
+	session: &'a mut Session,
+	input: &'a [u8],
+	output: &'a mut impl std::io::Write,
+	options: FormatOptions,
+) -> Result<Vec<Error<'a>>, Error<'a>> {
+	resolve(input, format_unresolved(session, input, output, options))
+}
+
+#[inline]
+fn format_unresolved<'a>(
+	session: &'a mut Session,
+	input: &'a [u8],
+	output: &'a mut impl std::io::Write,
+	options: FormatOptions,
+) -> Result<Vec<Error<'a>>, Error<'a>> {
+	let (stylesheet, mut diagnostics) = tree::build(input, options.recover)?;
+
+	// Tokenizer reports end of input right away for blank one
+	if input.iter().all(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r')) {
+		return Err(Error::UnexpectedEOF(Position::at(input.len())));
+	}
+
+	let mut formatter = Formatter::new(input, output, options, session);
+	diagnostics.extend(formatter.format(&stylesheet)?);
 
-pub fn format<'a>(input: &'a [u8], output: &'a mut impl std::io::Write) -> Result<(), Error<'a>> {
-	let parser = Parser::new(input);
+	// Parsing and formatting recover separately, report in input order
+	diagnostics.sort_by_key(|err| err.position().map(|position| position.offset));
 
-	let cache = Cache::new(parser)?;
-	let mut formatter = Formatter::new(cache, output);
+	Ok(diagnostics)
+}
+
+/// Formats `stylesheet` built by [`parse`] out of `input`, possibly changed since
+pub fn format_tree<'a>(
+	session: &'a mut Session,
+	stylesheet: &Stylesheet<'a>,
+	input: &'a [u8],
+	output: &'a mut impl std::io::Write,
+	options: FormatOptions,
+) -> Result<Vec<Error<'a>>, Error<'a>> {
+	let res = tree::validate(input, stylesheet)
+		.and_then(|()| Formatter::new(input, output, options, session).format(stylesheet));
+
+	resolve(input, res)
+}
+
+/// Builds syntax tree of `input`, see [`tree::parse`]
+#[inline]
+pub fn parse(input: &[u8]) -> Result<Stylesheet<'_>, Error<'_>> {
+	tree::parse(input).map_err(|mut err| {
+		Position::resolve(input, err.position_mut());
+		err
+	})
+}
 
-	formatter.format()
+/// Errors only know their offsets while formatting, lines and columns are filled in at once
+#[inline]
+fn resolve<'a>(
+	input: &[u8],
+	res: Result<Vec<Error<'a>>, Error<'a>>,
+) -> Result<Vec<Error<'a>>, Error<'a>> {
+	match res {
+		Ok(mut diagnostics) => {
+			Position::resolve(input, diagnostics.iter_mut().filter_map(Error::position_mut));
+			Ok(diagnostics)
+		}
+		Err(mut err) => {
+			Position::resolve(input, err.position_mut());
+			Err(err)
+		}
+	}
 }
 
 mod formatter;
 mod parser;
+mod position;
+mod tree;
 pub(crate) mod properties;
 mod tokens;
@@ -1,191 +1,588 @@
-use std::hint::unreachable_unchecked;
 use std::io::Write;
 
+use ::css::{AtRule, Block, Bracket, ComponentValue, Declaration, Function, Item, Stylesheet};
+use ::css::{QualifiedRule, SimpleBlock, Span, TokenBuf};
 use consts::ASCII;
 
-use super::parser::{Cache, Error as ParserError};
+use crate::FormatOptions;
+
+use super::parser::{Backend, Error as ParserError};
+use super::position::Position;
 use super::tokens::Token;
+use super::tree::{skip_for, Skip};
 
 use self::context::Context;
-use self::utils::Helper;
+use self::cursor::Cursor;
 
-macro_rules! unexpected_token {
-	($token: expr, $self: expr) => {{
-		#[cfg(debug_assertions)]
-		{
-			eprintln!("--------------------------------------------------");
-			eprintln!("{:?}", $self.context);
-			eprintln!("{:?}", $self.token_cache);
-			eprintln!("--------------------------------------------------");
-		}
+pub use self::context::Session;
+use self::utils::Helper;
 
-		Err(Error::UnexpectedToken {
-			token: $token,
-			line: line!(),
-		})
-	}};
+/// `1`, `50%` or `1px`
+macro_rules! numeric {
+	() => {
+		Token::Number(_) | Token::Percentage(_) | Token::Dimension { .. }
+	};
 }
 
-macro_rules! debug_unexpected_token {
-	($token: expr, $self: expr) => {{
-		#[cfg(debug_assertions)]
-		{
-			return unexpected_token!($token, $self);
-		}
-	}};
+/// Tokens written as is in values and preludes
+macro_rules! value {
+	() => {
+		Token::Hash(_) | Token::Ident(_) | Token::UnicodeRange(_) | Token::URL(_) | numeric!()
+	};
 }
 
-macro_rules! debug_unreachable_token {
-	($token: expr, $self: expr) => {
-		#[allow(unreachable_code)]
-		{
-			debug_unexpected_token!($token, $self);
-
-			unsafe { std::hint::unreachable_unchecked() };
-		}
+/// Matches [`ComponentValue::Token`] by its token
+macro_rules! token {
+	($pat: pat) => {
+		ComponentValue::Token { token: $pat, .. }
 	};
 }
 
-macro_rules! ruleset_start_seq {
+/// Values a selector could start with
+macro_rules! ruleset_start {
 	() => {
-		Token::BracketSquareOpen
-			| Token::Colon
-			| Token::Delim(ASCII::AMPERSAND)
-			| Token::Delim(ASCII::ASTERISK)
-			| Token::Delim(ASCII::FULL_STOP)
-			| Token::Hash(_)
-			| Token::Ident(_)
+		token!(
+			Token::Colon
+				| Token::Delim(ASCII::AMPERSAND)
+				| Token::Delim(ASCII::ASTERISK)
+				| Token::Delim(ASCII::FULL_STOP)
+				| Token::Hash(_)
+				| Token::Ident(_)
+		) | ComponentValue::Block(SimpleBlock {
+			bracket: Bracket::Square,
+			..
+		})
+	};
+}
+
+/// Values, but whitespace, that are spaced from each other, e.g. `a b` or `1px solid`.
+/// Blocks and functions are matched by their brackets, `(...)` or `[...]`
+macro_rules! spaced {
+	($($extra: pat)?) => {
+		token!(Token::AtRule(_) | Token::Colon | Token::String(_) | value!() $(| $extra)?)
+			| ComponentValue::Function(_)
+			| ComponentValue::Block(SimpleBlock {
+				bracket: Bracket::Round | Bracket::Square,
+				..
+			})
 	};
 }
 
+/// Generic over token of [`ErrorOf::UnexpectedToken`], see [`Error`] and [`OwnedError`]
 #[derive(Debug)]
 #[allow(clippy::upper_case_acronyms)]
-pub enum Error<'a> {
-	BadComment,
-	BadString,
+pub enum ErrorOf<T> {
+	BadComment(Position),
+	BadString(Position),
 	IO(std::io::Error),
-	TooManyLevelsOfIndentation,
-	UnexpectedEOF,
-	UnexpectedToken { token: Token<'a>, line: u32 },
-	UnexpectedUTF8,
+	TooManyLevelsOfIndentation(Position),
+	UnexpectedEOF(Position),
+	UnexpectedToken { token: T, position: Position },
+	/// Points to the first byte of invalid UTF-8 sequence, or to the start of a changed node
+	/// holding one, see [`crate::format_tree`]
+	UnexpectedUTF8(Position),
 }
 
 #[derive(Debug)]
 pub struct Formatter<'a, T> {
-	token_cache: Cache<'a>,
-	context: Context<T>,
+	input: &'a [u8],
+	/// Created once, SIMD one copies unaligned input, see [`Self::next_token`]
+	parser: Backend<'a>,
+	context: Context<'a, T>,
+	/// Errors recovered from, see [`crate::FormatOptions::recover`]
+	diagnostics: Vec<Error<'a>>,
+	/// Values being formatted are a selector, rather than a declaration or a prelude
+	selector: bool,
 }
 
+/// Borrows its token from the input
+pub type Error<'a> = ErrorOf<Token<'a>>;
+/// Owns bytes of its token, so that it outlives the input
+pub type OwnedError = ErrorOf<TokenBuf>;
+
 pub type Result<'a, T> = std::result::Result<T, Error<'a>>;
 
+/// Block items are formatted in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scope {
+	/// Outside of any block
+	Top,
+	/// Block of a top level at-rule or of `@keyframes`, see [`Formatter::format_block`]
+	Block,
+	/// Block of a rule, see [`Formatter::format_declaration_block`]
+	Declarations,
+}
+
+/// Comments that opt parts of the input out of formatting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Directive {
+	/// `/* hel-fmt-ignore */`, copies the next item as is
+	Ignore,
+	/// `/* hel-fmt-off */`, copies everything up to `/* hel-fmt-on */` as is
+	Off,
+	/// `/* hel-fmt-on */`
+	On,
+}
+
 impl<'a, T: std::io::Write> Formatter<'a, T> {
+	/// Returns errors that were recovered from, if recovery is enabled
 	#[inline]
-	pub fn format(&mut self) -> Result<'a, ()> {
-		// Write top level comment
-		if let Token::Comment(bytes) = self.token_cache.current() {
-			self.context.write_comment(bytes)?;
-			self.context.flush()?;
-			self.context.flush()?;
+	pub fn format(&mut self, stylesheet: &Stylesheet<'a>) -> Result<'a, Vec<Error<'a>>> {
+		let mut items = stylesheet.items.as_slice();
+
+		// Write top level comment, unless it is a directive applying to what follows
+		if let [Item::Comment(comment), rest @ ..] = items {
+			// Comment alone is not a stylesheet
+			if rest.is_empty() {
+				return Err(Error::UnexpectedEOF(self.position(self.input.len())));
+			}
 
-			self.token_cache.next()?;
+			if directive(comment.text).is_none() {
+				if self.keeps_comment(comment.text) {
+					self.mark(comment.span);
+					self.format_comment(comment.text, comment.span)?;
+					self.context.flush()?;
+				}
+
+				items = rest;
+			}
 		}
 
-		// Top level loop
-		loop {
-			match self.token_cache.current() {
-				Token::Comment(_) => self.format_comment()?,
+		self.format_items(items, Scope::Top)?;
 
-				// Start of a ruleset
-				ruleset_start_seq!() => self.format_ruleset()?,
+		if !self.context.is_empty() {
+			self.context.flush()?;
+		}
 
-				// At-rule `@media ...`, also format it's own block if any
-				Token::AtRule(_) => self.format_atrule()?,
+		self.context.finish(self.input)?;
 
-				token => return unexpected_token!(token, self),
+		Ok(std::mem::take(&mut self.diagnostics))
+	}
+
+	#[inline]
+	fn format_items(&mut self, items: &[Item<'a>], scope: Scope) -> Result<'a, ()> {
+		// Items up to it are copied as is, see `Directive::Off`
+		let mut resume = 0;
+
+		for (i, item) in items.iter().enumerate() {
+			if i < resume {
+				continue;
 			}
 
-			match self.token_cache.next() {
-				Err(ParserError::EOF) => break,
-				Err(err) => return Err(err.into()),
-				_ => continue,
-			};
-		}
+			let last = self.is_last(items, i, scope);
+
+			// Item right after `/* hel-fmt-ignore */`
+			let ignored = i > 0
+				&& matches!(
+					&items[i - 1],
+					Item::Comment(comment) if directive(comment.text) == Some(Directive::Ignore)
+				);
+
+			match item {
+				Item::Comment(comment) if directive(comment.text) == Some(Directive::Off) => {
+					resume = items[i + 1..]
+						.iter()
+						.position(|item| {
+							matches!(
+								item,
+								Item::Comment(comment)
+									if directive(comment.text) == Some(Directive::On)
+							)
+						})
+						.map_or(items.len(), |on| i + 1 + on + 1);
+
+					let end = items[i..resume]
+						.iter()
+						.map(|item| item.span().end)
+						.max()
+						.unwrap_or(comment.span.end);
+
+					let last = self.is_last(items, resume - 1, scope);
+
+					self.write_ignored(Span::new(comment.span.start, end), scope, last)?;
+				}
 
-		if !self.context.is_empty() {
-			self.context.flush()?;
+				Item::Comment(_) if self.is_left_out(item) => {}
+
+				// `color: red; /* brand */` stays on the line of its declaration
+				Item::Comment(comment)
+					if scope == Scope::Declarations
+						&& directive(comment.text).is_none()
+						&& i > 0
+						&& matches!(
+							&items[i - 1],
+							Item::Declaration(prev) if self.is_same_line(prev.span, comment.span)
+						)
+						&& self.context.declaration_resume() =>
+				{
+					self.mark(comment.span);
+					self.context.write_optional_space()?;
+					self.format_inline_comment(comment.text)?;
+					self.context.declaration_end();
+				}
+
+				// Formatted along with the declaration that follows
+				Item::Comment(_)
+					if scope == Scope::Declarations && self.is_attached_comment(items, i) => {}
+
+				Item::Comment(comment) => {
+					self.mark(comment.span);
+					self.format_comment(comment.text, comment.span)?
+				}
+
+				// Declarations are copied along with their comments, see below
+				_ if ignored
+					&& !(scope == Scope::Declarations && matches!(item, Item::Declaration(_))) =>
+				{
+					let skip = match (scope, item) {
+						(Scope::Declarations, Item::Invalid(span)) => {
+							skip_for(self.input[span.start], true)
+						}
+						_ => Skip::Rule,
+					};
+
+					self.write_verbatim(item.span(), skip, last)?;
+				}
+
+				// At-rule `@media ...`, also format it's own block if any
+				Item::AtRule(rule) => {
+					self.format_item(item, Skip::Rule, last, |this| this.format_atrule(rule, last))?
+				}
+
+				Item::QualifiedRule(rule) => self.format_qualified_rule(item, rule, scope, last)?,
+
+				Item::Declaration(declaration) if scope == Scope::Declarations => {
+					let (heading, leading) = self.attached_comments(items, i);
+
+					// Blank line or standalone comments before start a new section
+					if self.context.options().sort_boundaries
+						&& (heading < leading
+							|| heading > 0
+								&& self
+									.line_breaks(items[heading - 1].span(), items[heading].span())
+									.is_some_and(|breaks| breaks > 1))
+					{
+						self.context.declaration_section();
+					}
+
+					if ignored {
+						self.write_verbatim(declaration.span, Skip::Declaration, last)?;
+					} else {
+						self.format_item(item, Skip::Declaration, last, |this| {
+							this.format_declaration(declaration)
+						})?;
+					}
+
+					for (at, item) in items.iter().enumerate().take(i).skip(heading) {
+						let Item::Comment(comment) = item else {
+							continue;
+						};
+
+						if self.is_left_out(item) {
+							continue;
+						}
+
+						self.mark(comment.span);
+						self.format_standalone_comment(comment.text, comment.span)?;
+						self.context.declaration_comment(at < leading);
+					}
+				}
+
+				// Only rules are expected outside of rule blocks
+				Item::Declaration(declaration) => self.format_item(item, Skip::Rule, last, |this| {
+					Err(this.unexpected_at(Token::Ident(declaration.name), declaration.span.start))
+				})?,
+
+				Item::Invalid(span) => {
+					let skip = match scope {
+						Scope::Declarations => skip_for(self.input[span.start], true),
+						_ => Skip::Rule,
+					};
+
+					self.write_verbatim(*span, skip, last)?;
+				}
+			}
 		}
 
 		Ok(())
 	}
 
-	/// Caller must ensure that current token is a CSS at-rule
+	/// Formats an item with `format`. In recovery mode, if it fails,
+	/// copies the item as is and records the error instead
 	#[inline]
-	fn format_atrule(&mut self) -> Result<'a, ()> {
-		let Token::AtRule(at_rule) = self.token_cache.current() else {
-			debug_unreachable_token!(self.token_cache.current(), self);
+	fn format_item(
+		&mut self,
+		item: &Item<'a>,
+		skip: Skip,
+		last: bool,
+		format: impl FnOnce(&mut Self) -> Result<'a, ()>,
+	) -> Result<'a, ()> {
+		if !self.context.options().recover {
+			return format(self);
+		}
+
+		let checkpoint = self.context.checkpoint();
+
+		let res = match format(self) {
+			Err(err @ Error::IO(_)) => Err(err),
+			Err(err) => {
+				self.context.rollback(checkpoint);
+				self.diagnostics.push(err);
+
+				self.write_verbatim(item.span(), skip, last)
+			}
+			ok => ok,
 		};
 
-		self.context.write_all(at_rule)?;
-		self.context.write_space()?;
+		if checkpoint.is_root() {
+			self.context.release()?;
+		}
 
-		self.token_cache.next()?;
+		res
+	}
+
+	/// Followed by `}`, or by the end of input, comments left out aside
+	#[inline]
+	fn is_last(&self, items: &[Item<'a>], i: usize, scope: Scope) -> bool {
+		if scope == Scope::Declarations {
+			return self.is_last_printed(items, i);
+		}
 
-		loop {
-			match self.token_cache.current() {
-				Token::Whitespace => self.process_whitespace()?,
+		match items[i + 1..].iter().find(|item| !self.is_left_out(item)) {
+			None => true,
+			// Stray `}`, or something unparseable, e.g. unterminated string
+			Some(Item::Invalid(span)) => matches!(
+				self.next_token(span.start),
+				Ok(Token::BracketCurlyClose) | Err(_)
+			),
+			Some(_) => false,
+		}
+	}
 
-				Token::Comment(_) => self.format_comment()?,
+	/// Declarations, along with their comments, are printed before rules of a block,
+	/// so only rules, or comments on their own, could follow the item at `i`
+	#[inline]
+	fn is_last_printed(&self, items: &[Item<'a>], i: usize) -> bool {
+		let mut at = i + 1;
+
+		while let Some(item) = items.get(at) {
+			match item {
+				// Region turned off is kept along with declarations, see `Self::write_ignored`
+				Item::Comment(comment) if directive(comment.text) == Some(Directive::Off) => {
+					at += items[at + 1..]
+						.iter()
+						.position(|item| {
+							matches!(
+								item,
+								Item::Comment(comment)
+									if directive(comment.text) == Some(Directive::On)
+							)
+						})
+						.map_or(items.len(), |on| on + 2);
 
-				Token::Ident(bytes) | Token::Hash(bytes) | Token::Number(bytes) => {
-					self.context.write_all(bytes)?
+					continue;
 				}
 
-				// `@import url("something")`
-				Token::Function(_) => self.format_function()?,
+				Item::Comment(comment)
+					if self.is_left_out(item)
+						|| self.is_attached_comment(items, at)
+						|| matches!(
+							&items[at - 1],
+							Item::Declaration(prev) if self.is_same_line(prev.span, comment.span)
+						) => {}
+
+				Item::Declaration(_) => {}
 
-				Token::String(bytes) => self.format_string(bytes)?,
+				Item::Invalid(span) if skip_for(self.input[span.start], true) == Skip::Declaration => {}
 
-				Token::Delim(del) => self.process_delim(del)?,
+				_ => return false,
+			}
+
+			at += 1;
+		}
+
+		true
+	}
+
+	/// Copies a region turned off with [`Directive::Off`] as is, in place:
+	/// declarations of it are neither sorted, nor moved past
+	#[inline]
+	fn write_ignored(&mut self, span: Span, scope: Scope, last: bool) -> Result<'a, ()> {
+		if scope != Scope::Declarations {
+			return self.write_verbatim(span, Skip::Rule, last);
+		}
+
+		// Copied as is, `;` is not added after `/* hel-fmt-on */` the region could end with
+		self.context.declaration_section();
+		self.context
+			.declaration_verbatim(self.input[span.start..span.end].trim_ascii_end());
+		self.context.declaration_section();
+
+		Ok(())
+	}
+
+	/// Copies input of a failed item as is
+	#[inline]
+	fn write_verbatim(&mut self, span: Span, skip: Skip, last: bool) -> Result<'a, ()> {
+		let bytes = self.input[span.start..span.end].trim_ascii_end();
 
-				Token::Semicolon => {
+		match skip {
+			Skip::Declaration => {
+				self.context.declaration_verbatim(bytes);
+
+				// Might not be the last one once sorted, and the next one would be part of it
+				let terminated = matches!(
+					bytes.last(),
+					None | Some(&(ASCII::SEMICOLON | ASCII::CURLY_CLOSE))
+				);
+
+				if !terminated && self.context.declaration_resume() {
 					self.context.write_u8(ASCII::SEMICOLON)?;
+					self.context.declaration_end();
+				}
+			}
+
+			Skip::Rule => {
+				if !self.context.is_empty() {
 					self.context.flush()?;
+				}
 
-					if self.token_cache.peek_next().is_ok() {
-						self.context.flush()?;
-					}
+				self.context.write_verbatim(bytes)?;
 
-					return Ok(());
+				// Add empty line after, if there's more content
+				if !last {
+					self.context.flush()?;
 				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Checks what a rule starts with, the same way a token stream used to be checked
+	#[inline]
+	fn format_qualified_rule(
+		&mut self,
+		item: &Item<'a>,
+		rule: &QualifiedRule<'a>,
+		scope: Scope,
+		last: bool,
+	) -> Result<'a, ()> {
+		let cursor = Cursor::new(
+			&rule.prelude,
+			Some(Token::BracketCurlyOpen),
+			rule.block.span.start,
+		);
+
+		match (scope, cursor.current()) {
+			(Scope::Block, _) => {
+				self.format_item(item, Skip::Rule, last, |this| {
+					this.format_block_rule(cursor, rule, last)
+				})
+			}
+
+			// Nested selectors could start with a combinator: `> div {`
+			(Scope::Top, Some(ruleset_start!()))
+			| (Scope::Declarations, Some(ruleset_start!() | token!(Token::Delim(_)))) => {
+				self.format_item(item, Skip::Rule, last, |this| {
+					this.format_ruleset(cursor, &rule.block, last)
+				})
+			}
+
+			(Scope::Top, _) => {
+				self.format_item(item, Skip::Rule, last, |this| Err(this.unexpected(&cursor)))
+			}
+			(Scope::Declarations, _) => self.format_item(item, Skip::Declaration, last, |this| {
+				Err(this.unexpected(&cursor))
+			}),
+		}
+	}
+
+	/// Statement at-rule has to end with `;`
+	#[inline]
+	fn format_atrule(&mut self, rule: &AtRule<'a>, last: bool) -> Result<'a, ()> {
+		let terminated = self.is_terminated(rule.span);
+
+		let (end, end_offset) = match &rule.block {
+			Some(block) => (Some(Token::BracketCurlyOpen), block.span.start),
+			None if terminated => (Some(Token::Semicolon), rule.span.end.saturating_sub(1)),
+			None => self.after(rule.span.end),
+		};
+
+		self.mark(rule.span);
+		self.context.write_all(rule.name)?;
 
-				Token::Colon => {
+		// `@font-face {`
+		if rule.prelude.iter().all(ComponentValue::is_whitespace) {
+			self.context.write_optional_space()?;
+		} else {
+			self.context.write_space()?;
+		}
+
+		let mut cursor = Cursor::new(&rule.prelude, end, end_offset);
+		self.format_atrule_prelude(&mut cursor)?;
+
+		let Some(block) = &rule.block else {
+			if !terminated {
+				return Err(self.unexpected(&cursor));
+			}
+
+			self.context.write_u8(ASCII::SEMICOLON)?;
+			self.context.flush()?;
+			// Invisible at the end of input, trailing newlines are collapsed
+			self.context.flush()?;
+
+			return Ok(());
+		};
+
+		// FIXME: `Keyframes` is special case, and one of the reasons `format_block` exists
+		// TODO: future-proofing
+		if self.context.indent() == 0 || rule.name == b"@keyframes" {
+			self.format_block(block, last)
+		} else {
+			self.format_declaration_block(block, last, true)
+		}
+	}
+
+	/// Also formats values inside of `(...)` of a prelude
+	#[inline]
+	fn format_atrule_prelude(&mut self, cursor: &mut Cursor<'_, 'a>) -> Result<'a, ()> {
+		while let Some(value) = cursor.current() {
+			self.mark_value(value);
+
+			match value {
+				token!(Token::Whitespace) => self.process_whitespace(cursor)?,
+
+				token!(Token::Comment(text)) => self.format_inline_comment(text)?,
+
+				token!(token @ value!()) => self.format_value(*token)?,
+
+				token!(Token::String(bytes)) => self.format_string(bytes)?,
+
+				token!(Token::Delim(del)) => self.process_delim(*del, cursor)?,
+
+				token!(Token::Colon) => {
 					self.context.write_u8(ASCII::COLON)?;
-					self.context.write_space()?;
+					self.context.write_optional_space()?;
 
-					self.token_cache.next()?;
+					cursor.next();
 
 					continue;
 				}
 
-				Token::BracketRoundOpen => self.context.write_u8(ASCII::PAREN_OPEN)?,
-				Token::BracketRoundClose => self.context.write_u8(ASCII::PAREN_CLOSE)?,
-
-				Token::BracketCurlyOpen => {
-					// FIXME: `Keyframes` is special case, and one of the reasons `format_block` exists
-					// TODO: future-proofing
-					if self.context.indent() == 0 || at_rule == b"@keyframes" {
-						self.format_block()?;
-					} else {
-						self.format_declaration_block()?;
-					}
+				// `@import url("something")`
+				ComponentValue::Function(function) => self.format_function(function)?,
 
-					break;
+				ComponentValue::Block(block @ SimpleBlock { bracket: Bracket::Round, .. }) => {
+					self.context.write_u8(ASCII::PAREN_OPEN)?;
+					self.format_atrule_prelude(&mut Cursor::block(block))?;
+					self.context.write_u8(ASCII::PAREN_CLOSE)?;
 				}
 
-				token => return unexpected_token!(token, self),
+				_ => return Err(self.unexpected(cursor)),
 			}
 
-			self.token_cache.next_with_whitespace()?;
+			cursor.next_with_whitespace();
 		}
 
 		Ok(())
@@ -193,19 +590,23 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 
 	/// Syntax: [`attribute`( `operator` `value` (`char`)?)?]
 	#[inline]
-	fn format_attribute_selector(&mut self) -> Result<'a, ()> {
+	fn format_attribute_selector(&mut self, block: &SimpleBlock<'a>) -> Result<'a, ()> {
+		let mut cursor = Cursor::block(block);
+
 		self.context.write_u8(ASCII::SQUARED_OPEN)?;
 
 		// attribute
 		{
-			let Token::Ident(bytes) = self.token_cache.next()? else {
-				return unexpected_token!(self.token_cache.current(), self);
+			cursor.skip_whitespace();
+
+			let Some(token!(Token::Ident(bytes))) = cursor.current() else {
+				return Err(self.unexpected(&cursor));
 			};
 
 			self.context.write_all(bytes)?;
 
-			if let Token::BracketSquareClose = self.token_cache.peek_next()? {
-				unsafe { self.token_cache.next().unwrap_unchecked() };
+			if cursor.peek_next().is_none() {
+				cursor.next();
 				self.context.write_u8(ASCII::SQUARED_CLOSE)?;
 
 				return Ok(());
@@ -214,22 +615,22 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 
 		// operator could be composite `*=` or single `=`
 		{
-			let Token::Delim(del) = self.token_cache.next()? else {
-				return unexpected_token!(self.token_cache.current(), self);
+			let Some(token!(Token::Delim(del))) = cursor.next() else {
+				return Err(self.unexpected(&cursor));
 			};
 
-			self.context.write_u8(del)?;
+			self.context.write_u8(*del)?;
 
-			if let Token::Delim(del) = self.token_cache.peek_next()? {
-				unsafe { self.token_cache.next().unwrap_unchecked() };
-				self.context.write_u8(del)?;
+			if let Some(token!(Token::Delim(del))) = cursor.peek_next() {
+				cursor.next();
+				self.context.write_u8(*del)?;
 			}
 		}
 
 		// value
 		{
-			let Token::String(bytes) = self.token_cache.next()? else {
-				return unexpected_token!(self.token_cache.current(), self);
+			let Some(token!(Token::String(bytes))) = cursor.next() else {
+				return Err(self.unexpected(&cursor));
 			};
 
 			self.format_string(bytes)?;
@@ -237,8 +638,8 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 
 		// `i` or `I` or `s` or `S`
 		{
-			if let Token::Ident(bytes) = self.token_cache.peek_next()? {
-				unsafe { self.token_cache.next().unwrap_unchecked() };
+			if let Some(token!(Token::Ident(bytes))) = cursor.peek_next() {
+				cursor.next();
 				self.context.write_space()?;
 				self.context.write_all(bytes)?;
 			}
@@ -246,9 +647,9 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 
 		// closing `]`
 		{
-			let Token::BracketSquareClose = self.token_cache.next()? else {
-				return unexpected_token!(self.token_cache.current(), self);
-			};
+			if cursor.next().is_some() {
+				return Err(self.unexpected(&cursor));
+			}
 
 			self.context.write_u8(ASCII::SQUARED_CLOSE)?;
 		}
@@ -257,144 +658,277 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 	}
 
 	#[inline]
-	fn format_block(&mut self) -> Result<'a, ()> {
+	fn format_block(&mut self, block: &Block<'a>, last: bool) -> Result<'a, ()> {
 		if !self.context.is_empty() {
-			self.context.write_space()?;
+			self.context.write_optional_space()?;
 		}
 
+		self.mark(block.span);
 		self.context.write_u8(ASCII::CURLY_OPEN)?;
 		self.context.flush()?;
 
-		self.context.indent_inc()?;
+		self
+			.context
+			.indent_inc()
+			.map_err(|_| Error::TooManyLevelsOfIndentation(self.position(block.span.start)))?;
 
-		self.token_cache.next()?;
+		self.format_items(&block.items, Scope::Block)?;
 
-		loop {
-			match self.token_cache.current() {
-				Token::BracketCurlyClose => break,
+		// Balanced with `indent_inc` above
+		self.context.indent_dec().ok();
 
-				Token::Whitespace => self.process_whitespace()?,
+		self.mark_block_end(block);
+		self.context.write_u8(ASCII::CURLY_CLOSE)?;
+		self.context.flush()?;
 
-				Token::Comment(_) => self.format_comment()?,
+		// Add empty line after block, if there's more content
+		if !last {
+			self.context.flush()?;
+		}
 
-				Token::AtRule(_) => self.format_atrule()?,
+		Ok(())
+	}
 
-				Token::BracketCurlyOpen
-				| Token::BracketSquareOpen
-				| Token::Colon
-				| Token::Delim(ASCII::FULL_STOP)
-				| Token::Delim(ASCII::AMPERSAND)
-				| Token::Hash(_)
-				| Token::Ident(_) => self.format_ruleset()?,
+	/// Rule inside of [`Self::format_block`], e.g. `from` or `0%, 50%` of `@keyframes`
+	#[inline]
+	fn format_block_rule(
+		&mut self,
+		mut cursor: Cursor<'_, 'a>,
+		rule: &QualifiedRule<'a>,
+		last: bool,
+	) -> Result<'a, ()> {
+		while let Some(value) = cursor.current() {
+			self.mark_value(value);
+
+			match value {
+				token!(Token::Whitespace) => self.process_whitespace(&cursor)?,
+
+				token!(Token::Comment(text)) => self.format_inline_comment(text)?,
 
 				// `@keyframes { 0% { color: red; }}`
-				Token::Number(bytes) => self.context.write_all(bytes)?,
+				token!(token @ numeric!()) => self.format_value(*token)?,
 
-				Token::Comma => {
+				token!(Token::Comma) => {
 					self.context.write_u8(ASCII::COMMA)?;
 					self.context.flush()?;
 				}
 
-				token => return unexpected_token!(token, self),
+				token!(
+					Token::Colon
+						| Token::Delim(ASCII::FULL_STOP)
+						| Token::Delim(ASCII::AMPERSAND)
+						| Token::Hash(_)
+						| Token::Ident(_)
+				)
+				| ComponentValue::Block(SimpleBlock {
+					bracket: Bracket::Square,
+					..
+				}) => break,
+
+				_ => return Err(self.unexpected(&cursor)),
 			}
 
-			self.token_cache.next_with_whitespace()?;
+			cursor.next_with_whitespace();
 		}
 
-		unsafe { self.context.indent_dec().unwrap_unchecked() };
+		self.format_ruleset(cursor, &rule.block, last)
+	}
 
-		self.context.write_u8(ASCII::CURLY_CLOSE)?;
-		self.context.flush()?;
+	/// Comment on its own line
+	#[inline]
+	fn format_comment(&mut self, bytes: &[u8], span: Span) -> Result<'a, ()> {
+		self.format_standalone_comment(bytes, span)?;
 
-		// Add empty line after block, if there's more content
-		if !matches!(self.token_cache.peek_next(), Ok(Token::BracketCurlyClose)) {
+		if !self.context.options().minify {
 			self.context.flush()?;
 		}
 
 		Ok(())
 	}
 
+	/// Same as [`Self::format_comment`], but leaves the line to the caller
 	#[inline]
-	fn format_comment(&mut self) -> Result<'a, ()> {
-		let Token::Comment(bytes) = self.token_cache.current() else {
-			debug_unreachable_token!(self.token_cache.current(), self);
-		};
+	fn format_standalone_comment(&mut self, bytes: &[u8], span: Span) -> Result<'a, ()> {
+		if self.keeps_comment(bytes) && !is_verbatim_comment(bytes) && bytes.contains(&ASCII::LF) {
+			return self.format_multiline_comment(bytes, span);
+		}
+
+		self.format_inline_comment(bytes)
+	}
+
+	/// Comment in the middle of a line, e.g. `margin: 0 /* top */ auto;`.
+	/// Where the line ends up is not known yet, so lines after the first one are kept as is
+	#[inline]
+	fn format_inline_comment(&mut self, bytes: &[u8]) -> Result<'a, ()> {
+		if !self.keeps_comment(bytes) {
+			return Ok(());
+		}
+
+		if is_verbatim_comment(bytes) || bytes.contains(&ASCII::LF) {
+			self.context.write_all(b"/*")?;
+			self.context.write_all(bytes)?;
+			self.context.write_all(b"*/")?;
+
+			return Ok(());
+		}
 
 		self.context.write_comment(bytes)?;
-		self.context.flush()?;
 
 		Ok(())
 	}
 
+	/// Lines after the first one keep their indentation relative to the line
+	/// the comment starts on, but at current indentation
 	#[inline]
-	fn format_declaration(&mut self) -> Result<'a, ()> {
-		let Token::Ident(bytes) = self.token_cache.current() else {
-			debug_unreachable_token!(self.token_cache.current(), self);
-		};
+	fn format_multiline_comment(&mut self, bytes: &[u8], span: Span) -> Result<'a, ()> {
+		let options = *self.context.options();
+		let indent = self.context.indent();
+		let base = self.indentation_at(span.start);
 
-		self.context.declaration_start(bytes);
-		self.context.write_all(bytes)?;
+		let mut lines = bytes.split(|&byte| byte == ASCII::LF).peekable();
 
-		let Token::Colon = self.token_cache.next()? else {
-			return unexpected_token!(self.token_cache.current(), self);
-		};
+		let first = lines.next().unwrap_or_default().trim_ascii();
 
-		self.context.write_u8(ASCII::COLON)?;
+		self.context.write_all(b"/*")?;
 
-		self.token_cache.next()?;
+		if !first.is_empty() {
+			self.context.write_space()?;
+			self.context.write_all(first)?;
+		}
 
-		loop {
-			match self.token_cache.current() {
-				// Trailing `;` is optional
-				Token::Semicolon | Token::BracketCurlyClose => {
-					self.context.write_u8(ASCII::SEMICOLON)?;
+		while let Some(line) = lines.next() {
+			let common = line.iter().zip(base).take_while(|(a, b)| a == b).count();
+			let line = &line[common..];
+			let text = line.trim_ascii_end();
 
-					break;
+			self.context.write_newline()?;
+
+			match lines.peek() {
+				Some(_) if text.is_empty() => {}
+				Some(_) => {
+					self.context.write_indent(indent, &options)?;
+					self.context.write_all(text)?;
 				}
 
-				// FIXME: Inline comments
-				Token::Comment(_) => return unexpected_token!(self.token_cache.current(), self),
+				// `*/` on its own line, e.g. ` */` of ` * ...` lines
+				None if text.is_empty() => {
+					let blank = line.iter().take_while(|&&byte| matches!(byte, b' ' | b'\t'));
+
+					self.context.write_indent(indent, &options)?;
+
+					for &byte in blank {
+						self.context.write_u8(byte)?;
+					}
+
+					self.context.write_all(b"*/")?;
+				}
+				None => {
+					self.context.write_indent(indent, &options)?;
+					self.context.write_all(text)?;
+					self.context.write_all(b" */")?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Comments, but license `/*! ... */` ones and directives, are left out when minifying
+	/// or stripping them. Input a directive applies to is copied as is either way
+	#[inline]
+	fn keeps_comment(&self, bytes: &[u8]) -> bool {
+		let options = self.context.options();
+
+		!(options.minify || options.strip_comments)
+			|| bytes.first() == Some(&ASCII::EXCLAMATION)
+			|| directive(bytes).is_some()
+	}
+
+	#[inline]
+	fn is_left_out(&self, item: &Item<'a>) -> bool {
+		matches!(item, Item::Comment(comment) if !self.keeps_comment(comment.text))
+	}
+
+	/// Whitespace the line `offset` is on starts with
+	#[inline]
+	fn indentation_at(&self, offset: usize) -> &'a [u8] {
+		let start = self.input[..offset]
+			.iter()
+			.rposition(|&byte| byte == ASCII::LF)
+			.map_or(0, |i| i + 1);
+
+		let line = &self.input[start..offset];
+		let len = line
+			.iter()
+			.position(|&byte| !matches!(byte, b' ' | b'\t'))
+			.unwrap_or(line.len());
+
+		&line[..len]
+	}
+
+	#[inline]
+	fn format_declaration(&mut self, declaration: &Declaration<'a>) -> Result<'a, ()> {
+		let Declaration { name, value, span } = declaration;
+
+		self.context.declaration_start(name);
+		self.mark(*span);
+		self.context.write_all(name)?;
+
+		self.context.write_u8(ASCII::COLON)?;
+
+		// Trailing `;` is optional
+		let mut cursor = match self.is_terminated(*span) {
+			true => Cursor::new(value, Some(Token::Semicolon), span.end.saturating_sub(1)),
+			false => Cursor::new(value, Some(Token::BracketCurlyClose), span.end),
+		};
+
+		// Whitespace of the input is dropped, values are separated right here
+		while let Some(value) = cursor.current() {
+			self.mark_value(value);
 
+			match value {
 				// `content: ":)";`
-				Token::String(bytes) => {
-					self.context.write_space()?;
+				token!(Token::String(bytes)) => {
+					self.write_value_space()?;
 					self.format_string(bytes)?;
 				}
 
 				// `color: var(--some-var);` or `background: conic-gradient(...)`
-				Token::Function(_) => {
-					self.context.write_space()?;
-					self.format_function()?;
+				ComponentValue::Function(function) => {
+					self.write_value_space()?;
+					self.format_function(function)?;
 				}
 
-				// `color: #cccccc;`
-				Token::Hash(bytes) | Token::Ident(bytes) | Token::Number(bytes) => {
-					self.context.write_space()?;
-					self.context.write_all(bytes)?
+				// `color: #cccccc;` or `background: url(image.png);`
+				token!(token @ value!()) => {
+					self.write_value_space()?;
+					self.format_value(*token)?;
 				}
 
-				Token::Delim(del @ ASCII::HASH) => {
-					self.context.write_space()?;
-					self.process_delim(del)?;
+				token!(Token::Delim(del @ ASCII::HASH)) => {
+					self.write_value_space()?;
+					self.process_delim(*del, &mut cursor)?;
 				}
-				Token::Delim(del) => self.process_delim(del)?,
+				token!(Token::Delim(del)) => self.process_delim(*del, &mut cursor)?,
 
 				// `background: var(--some-var), blue;`
-				Token::Comma => {
-					self.context.write_u8(ASCII::COMMA)?;
-
-					self.token_cache.next()?;
+				token!(Token::Comma) => self.context.write_u8(ASCII::COMMA)?,
 
-					continue;
+				// `margin: 0 /* top */ auto;`
+				token!(Token::Comment(text)) if self.keeps_comment(text) => {
+					self.write_value_space()?;
+					self.format_inline_comment(text)?;
 				}
+				token!(Token::Comment(_)) => {}
 
-				token => return unexpected_token!(token, self),
+				_ => return Err(self.unexpected(&cursor)),
 			}
 
-			// self.token_cache.next_with_whitespace()?;
-			self.token_cache.next()?;
+			cursor.next();
 		}
 
+		self.context.write_u8(ASCII::SEMICOLON)?;
+
 		self.context.declaration_end();
 
 		Ok(())
@@ -404,67 +938,51 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 	// We will enforce order of:
 	// 1. Declarations e.g - `background: red;` - separated by newline
 	// 2. Nested selectors or at-rules (if any) like - `&:hover { ... }` - separated by empty line
+	//
+	// Short blocks of declarations alone could stay on the line they start on, if `inline`
 	#[inline]
-	fn format_declaration_block(&mut self) -> Result<'a, ()> {
+	fn format_declaration_block(
+		&mut self,
+		block: &Block<'a>,
+		last: bool,
+		inline: bool,
+	) -> Result<'a, ()> {
+		let options = self.context.options();
+		let inline = inline
+			&& !options.minify
+			&& (1..=options.single_line_rules).contains(&block.items.len())
+			&& block.items.iter().all(|item| matches!(item, Item::Declaration(_)));
+
 		// Turn `something{` into `something {`, but not `{` to ` {`
 		{
 			if !self.context.is_empty() {
-				self.context.write_space()?;
+				self.context.write_optional_space()?;
 			}
 
+			self.mark(block.span);
 			self.context.write_u8(ASCII::CURLY_OPEN)?;
-			self.context.flush()?;
 
-			self.token_cache.next()?;
-		}
-
-		self.context.layer_push()?;
-
-		loop {
-			match self.token_cache.current() {
-				Token::BracketCurlyClose => break,
-
-				Token::Comment(_) => self.format_comment()?,
-
-				// Declaration: `background: blue;` or `color: green;`
-				// Not nested selector: `div&` or `div &`
-				// TODO: cleanup
-				Token::Ident(_)
-					if self.context.is_empty()
-						&& !matches!(
-							self.token_cache.peek_next()?,
-							Token::Delim(ASCII::AMPERSAND)
-						) =>
-				{
-					self.format_declaration()?;
-
-					// You can skip trailing `;` in declaration, if next token is `}` ignoring whitespace in between
-					if self.token_cache.current() == Token::BracketCurlyClose {
-						break;
-					}
-				}
-
-				// Nested CSS ruleset
-				ruleset_start_seq!() => self.format_ruleset()?,
-
-				// Nested CSS at-rule
-				Token::AtRule(_) => self.format_atrule()?,
-
-				Token::Delim(del) => self.process_delim(del)?,
-
-				token => return unexpected_token!(token, self),
+			if !inline {
+				self.context.flush()?;
 			}
+		}
 
-			self.token_cache.next()?;
+		match inline {
+			true => self.context.layer_push_inline(),
+			false => self.context.layer_push(),
 		}
+		.map_err(|_| Error::TooManyLevelsOfIndentation(self.position(block.span.start)))?;
+
+		self.format_items(&block.items, Scope::Declarations)?;
 
 		self.context.layer_pop()?;
 
+		self.mark_block_end(block);
 		self.context.write_u8(ASCII::CURLY_CLOSE)?;
 		self.context.flush()?;
 
 		// Add empty line after block, if there's more content
-		if !matches!(self.token_cache.peek_next(), Ok(Token::BracketCurlyClose)) {
+		if !last {
 			self.context.flush()?;
 		}
 
@@ -473,175 +991,443 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 
 	/// Format CSS function `:is()` or `translate()`
 	#[inline]
-	fn format_function(&mut self) -> Result<'a, ()> {
-		let Token::Function(bytes) = self.token_cache.current() else {
-			debug_unreachable_token!(self.token_cache.current(), self);
-		};
+	fn format_function(&mut self, function: &Function<'a>) -> Result<'a, ()> {
+		self.context.write_all(function.name)?;
+		self.context.write_u8(ASCII::PAREN_OPEN)?;
 
-		self.context.write_all(bytes)?;
+		let mut cursor = Cursor::arguments(function);
+		cursor.skip_whitespace();
 
-		self.token_cache.next()?;
+		self.format_arguments(&mut cursor)?;
 
-		let mut level = 0;
-		loop {
-			match self.token_cache.current() {
-				Token::BracketRoundClose => {
-					self.context.write_u8(ASCII::PAREN_CLOSE)?;
+		self.context.write_u8(ASCII::PAREN_CLOSE)?;
 
-					if level == 0 {
-						break;
-					}
-
-					level -= 1;
-				}
-
-				Token::Whitespace => self.process_whitespace()?,
+		Ok(())
+	}
 
-				Token::BracketRoundOpen => {
-					self.context.write_u8(ASCII::PAREN_OPEN)?;
+	/// Arguments of a function, or values inside of `(...)` nested in one
+	#[inline]
+	fn format_arguments(&mut self, cursor: &mut Cursor<'_, 'a>) -> Result<'a, ()> {
+		while let Some(value) = cursor.current() {
+			self.mark_value(value);
 
-					level += 1;
-				}
+			match value {
+				token!(Token::Whitespace) => self.process_whitespace(cursor)?,
 
-				Token::Comma => {
+				token!(Token::Comma) => {
 					self.context.write_u8(ASCII::COMMA)?;
-					self.context.write_space()?;
+					self.context.write_optional_space()?;
 				}
 
-				Token::String(bytes) => self.format_string(bytes)?,
+				token!(Token::String(bytes)) => self.format_string(bytes)?,
 
-				Token::Comment(_) => self.format_comment()?,
+				token!(Token::Comment(text)) => self.format_inline_comment(text)?,
 
-				// Nested functions: `max(calc(...), min(...))`
-				Token::Function(_) => self.format_function()?,
-
-				Token::Ident(bytes) | Token::Hash(bytes) | Token::Number(bytes) => {
-					self.context.write_all(bytes)?
-				}
+				token!(token @ value!()) => self.format_value(*token)?,
 
-				Token::Delim(del) => self.process_delim(del)?,
+				token!(Token::Delim(del)) => self.process_delim(*del, cursor)?,
 
 				// Nested selectors: `:has(:is(...))`
-				Token::Colon => self.format_pseudo()?,
+				token!(Token::Colon) => self.format_pseudo(cursor)?,
+
+				// Nested functions: `max(calc(...), min(...))`
+				ComponentValue::Function(function) => self.format_function(function)?,
+
+				ComponentValue::Block(block @ SimpleBlock { bracket: Bracket::Round, .. }) => {
+					self.context.write_u8(ASCII::PAREN_OPEN)?;
+					self.format_arguments(&mut Cursor::block(block))?;
+					self.context.write_u8(ASCII::PAREN_CLOSE)?;
+				}
 
 				// Selectors :is([class="some-class"])
-				Token::BracketSquareOpen => self.format_attribute_selector()?,
+				ComponentValue::Block(block @ SimpleBlock { bracket: Bracket::Square, .. }) => {
+					self.format_attribute_selector(block)?
+				}
 
-				token => return unexpected_token!(token, self),
+				_ => return Err(self.unexpected(cursor)),
 			}
 
-			self.token_cache.next_with_whitespace()?;
+			cursor.next_with_whitespace();
 		}
 
 		Ok(())
 	}
 
+	/// Current value of `cursor` has to be `:`
 	#[inline]
-	fn format_pseudo(&mut self) -> Result<'a, ()> {
+	fn format_pseudo(&mut self, cursor: &mut Cursor<'_, 'a>) -> Result<'a, ()> {
 		self.context.write_u8(ASCII::COLON)?;
 
-		match self.token_cache.next()? {
+		match cursor.next() {
 			// `::before`
-			Token::Colon => {
+			Some(token!(Token::Colon)) => {
 				self.context.write_u8(ASCII::COLON)?;
 
-				match self.token_cache.next()? {
+				match cursor.next() {
 					// `::before`
-					Token::Ident(bytes) => self.context.write_all(bytes)?,
+					Some(token!(Token::Ident(bytes))) => self.context.write_all(bytes)?,
 
 					// `::part(...)`
-					Token::Function(_) => self.format_function()?,
+					Some(ComponentValue::Function(function)) => self.format_function(function)?,
 
-					token => return unexpected_token!(token, self),
+					_ => return Err(self.unexpected(cursor)),
 				}
 			}
 
 			// Pseudo-class: `:active` or `:hover`
-			Token::Ident(bytes) => self.context.write_all(bytes)?,
+			Some(token!(Token::Ident(bytes))) => self.context.write_all(bytes)?,
 
 			// Preudo-class: `:is(...)` or `:has(...)`
-			Token::Function(_) => self.format_function()?,
+			Some(ComponentValue::Function(function)) => self.format_function(function)?,
 
-			token => return unexpected_token!(token, self),
+			_ => return Err(self.unexpected(cursor)),
 		};
 
 		Ok(())
 	}
 
+	/// Formats selectors left in `cursor`, followed by `block`
 	#[inline]
-	fn format_ruleset(&mut self) -> Result<'a, ()> {
-		loop {
-			match self.token_cache.current() {
-				// Format block and return
-				Token::BracketCurlyOpen => return self.format_declaration_block(),
+	fn format_ruleset(
+		&mut self,
+		mut cursor: Cursor<'_, 'a>,
+		block: &Block<'a>,
+		last: bool,
+	) -> Result<'a, ()> {
+		let line_num = self.context.line_num();
+
+		// Combinators are spaced differently, see `Self::process_delim`
+		self.selector = true;
+		let res = self.format_selector(&mut cursor);
+		self.selector = false;
+		res?;
+
+		// Selectors that take several lines keep the block expanded
+		let inline = self.context.line_num() == line_num;
+
+		self.format_declaration_block(block, last, inline)
+	}
 
-				Token::Whitespace => self.process_whitespace()?,
+	#[inline]
+	fn format_selector(&mut self, cursor: &mut Cursor<'_, 'a>) -> Result<'a, ()> {
+		while let Some(value) = cursor.current() {
+			self.mark_value(value);
+
+			match value {
+				token!(Token::Whitespace) => self.process_whitespace(cursor)?,
 
-				Token::Comment(_) => self.format_comment()?,
+				token!(Token::Comment(text)) => self.format_inline_comment(text)?,
 
 				// `p` or `div` or `#some-id`
-				Token::Ident(bytes) | Token::Hash(bytes) => self.context.write_all(bytes)?,
+				token!(Token::Ident(bytes) | Token::Hash(bytes)) => self.context.write_all(bytes)?,
 
 				// `:has()` or `::before`
-				Token::Colon => self.format_pseudo()?,
+				token!(Token::Colon) => self.format_pseudo(cursor)?,
 
 				// Selector: `*` or `*::before`
-				Token::Delim(del @ ASCII::ASTERISK) => self.context.write_u8(del)?,
-				Token::Delim(del) => self.process_delim(del)?,
+				token!(Token::Delim(del @ ASCII::ASTERISK)) => self.context.write_u8(*del)?,
+				token!(Token::Delim(del)) => self.process_delim(*del, cursor)?,
 
 				// Comma means EOL for us
-				Token::Comma => {
+				token!(Token::Comma) => {
 					self.context.write_u8(ASCII::COMMA)?;
 					self.context.flush()?;
 				}
 
 				// Selector: [href*="something"]
-				Token::BracketSquareOpen => self.format_attribute_selector()?,
+				ComponentValue::Block(block @ SimpleBlock { bracket: Bracket::Square, .. }) => {
+					self.format_attribute_selector(block)?
+				}
 
-				token => return unexpected_token!(token, self),
+				_ => return Err(self.unexpected(cursor)),
 			}
 
-			self.token_cache.next_with_whitespace()?;
+			cursor.next_with_whitespace();
 		}
+
+		Ok(())
 	}
 
+	/// Writes string in double quotes, unless it has unescaped ones, e.g. `'say "hi"'`
 	#[inline]
 	fn format_string(&mut self, bytes: &[u8]) -> Result<'a, ()> {
-		self.context.write_u8(ASCII::QUOTE)?;
+		let mut escaped = false;
+		let has_quote = bytes.iter().any(|&byte| {
+			let is_quote = byte == ASCII::QUOTE && !escaped;
+			escaped = byte == ASCII::BACKSLASH && !escaped;
+
+			is_quote
+		});
+
+		let quote = if has_quote { ASCII::SINGLE_QUOTE } else { ASCII::QUOTE };
+
+		self.context.write_u8(quote)?;
 		self.context.write_all(bytes)?;
-		self.context.write_u8(ASCII::QUOTE)?;
+		self.context.write_u8(quote)?;
+
+		Ok(())
+	}
+
+	/// Caller must ensure that `token` matches [`value!`]
+	#[inline]
+	fn format_value(&mut self, token: Token<'a>) -> Result<'a, ()> {
+		match token {
+			Token::Hash(bytes)
+			| Token::Ident(bytes)
+			| Token::Number(bytes)
+			| Token::UnicodeRange(bytes) => self.context.write_all(bytes)?,
+
+			Token::Percentage(value) => {
+				self.context.write_all(value)?;
+				self.context.write_u8(ASCII::PERCENTAGE)?;
+			}
+
+			Token::Dimension { value, unit } => {
+				self.context.write_all(value)?;
+				self.context.write_all(unit)?;
+			}
+
+			Token::URL(bytes) => {
+				self.context.write_all(b"url(")?;
+				self.context.write_all(bytes)?;
+				self.context.write_u8(ASCII::PAREN_CLOSE)?;
+			}
+
+			token => unreachable!("{token:?} is not a value"),
+		}
 
 		Ok(())
 	}
 
 	#[inline]
-	pub fn new(cache: Cache<'a>, output: T) -> Self {
+	pub fn new(
+		input: &'a [u8],
+		output: T,
+		options: FormatOptions,
+		session: &'a mut Session,
+	) -> Self {
 		Self {
-			token_cache: cache,
-			context: Context::new(output),
+			input,
+			parser: Backend::new(input),
+			context: Context::new(output, options, session),
+			diagnostics: Vec::new(),
+			selector: false,
+		}
+	}
+
+	/// Item ends with `;`, as do ones added to the tree, having no span
+	#[inline(always)]
+	fn is_terminated(&self, span: Span) -> bool {
+		span.is_empty() || self.input[..span.end].last() == Some(&ASCII::SEMICOLON)
+	}
+
+	/// What an item not terminated by `;` is followed by, `}` or the end of input, and where
+	#[inline]
+	fn after(&self, offset: usize) -> (Option<Token<'a>>, usize) {
+		match self.input[offset..]
+			.iter()
+			.position(|byte| !byte.is_ascii_whitespace())
+		{
+			Some(i) => (Some(Token::BracketCurlyClose), offset + i),
+			None => (None, self.input.len()),
 		}
 	}
 
+	/// First token after `offset`, skipping whitespace
 	#[inline]
-	fn process_delim(&mut self, delim: u8) -> Result<'a, ()> {
+	fn next_token(&self, offset: usize) -> std::result::Result<Token<'a>, ParserError> {
+		let mut parser = self.parser.clone();
+		parser.seek(offset);
+
+		match parser.next()? {
+			Token::Whitespace => parser.next(),
+			token => Ok(token),
+		}
+	}
+
+	#[inline(always)]
+	fn write_space(&mut self, optional: bool) -> Result<'a, ()> {
+		match optional {
+			true => self.context.write_optional_space()?,
+			false => self.context.write_space()?,
+		}
+
+		Ok(())
+	}
+
+	/// Comments right above the declaration at `i` of `items` go along with it,
+	/// as do standalone ones before those, if [`FormatOptions::sort_boundaries`].
+	/// Returns where standalone and leading comments start
+	#[inline]
+	fn attached_comments(&self, items: &[Item<'a>], i: usize) -> (usize, usize) {
+		let mut start = i;
+
+		// Directives of a region turned off stay where they are
+		while start > 0
+			&& matches!(
+				&items[start - 1],
+				Item::Comment(comment)
+					if !matches!(directive(comment.text), Some(Directive::Off | Directive::On))
+			)
+		{
+			start -= 1;
+		}
+
+		// Trailing comment of the declaration before, see `Self::format_items`
+		if let (Some(Item::Declaration(prev)), Some(Item::Comment(comment))) =
+			(start.checked_sub(1).map(|prev| &items[prev]), items.get(start))
+		{
+			if start < i
+				&& directive(comment.text).is_none()
+				&& self.is_same_line(prev.span, comment.span)
+			{
+				start += 1;
+			}
+		}
+
+		let mut leading = i;
+
+		while leading > start
+			&& self
+				.line_breaks(items[leading - 1].span(), items[leading].span())
+				.is_some_and(|breaks| breaks <= 1)
+		{
+			leading -= 1;
+		}
+
+		match self.context.options().sort_boundaries {
+			true => (start, leading),
+			false => (leading, leading),
+		}
+	}
+
+	/// Comment at `i` of `items` goes along with a declaration, see [`Self::attached_comments`]
+	#[inline]
+	fn is_attached_comment(&self, items: &[Item<'a>], i: usize) -> bool {
+		let Some(next) = items[i..]
+			.iter()
+			.position(|item| !matches!(item, Item::Comment(_)))
+		else {
+			return false;
+		};
+
+		matches!(items[i + next], Item::Declaration(_))
+			&& self.attached_comments(items, i + next).0 <= i
+	}
+
+	/// No line break in between, e.g. of a declaration and a comment following it
+	#[inline]
+	fn is_same_line(&self, before: Span, after: Span) -> bool {
+		self.line_breaks(before, after) == Some(0)
+	}
+
+	/// Line breaks in between items of the input, [`None`] if any was added to the tree
+	#[inline]
+	fn line_breaks(&self, before: Span, after: Span) -> Option<usize> {
+		if before.is_empty() || after.is_empty() || before.end > after.start {
+			return None;
+		}
+
+		let between = &self.input[before.end..after.start];
+
+		Some(between.iter().filter(|&&byte| byte == ASCII::LF).count())
+	}
+
+	/// Space before a value of a declaration, needed only if it follows another value
+	#[inline]
+	fn write_value_space(&mut self) -> Result<'a, ()> {
+		let after_value = !matches!(
+			self.context.last(),
+			Some(b' ' | b'"' | b'\'' | b'(' | b')' | b'*' | b',' | b'/' | b':')
+		);
+
+		self.write_space(!after_value)
+	}
+
+	/// Maps what is written next to the start of `span`, nodes added to the tree have none
+	#[inline(always)]
+	fn mark(&mut self, span: Span) {
+		if !span.is_empty() {
+			self.context.mark(span.start);
+		}
+	}
+
+	/// Whitespace is never written as is, so it's not worth mapping
+	#[inline(always)]
+	fn mark_value(&mut self, value: &ComponentValue<'a>) {
+		if !matches!(value, token!(Token::Whitespace)) {
+			self.mark(value.span());
+		}
+	}
+
+	/// Takes over the mark of a value that is left out
+	#[inline]
+	fn mark_current(&mut self, cursor: &Cursor<'_, 'a>) {
+		if let Some(value) = cursor.current() {
+			self.mark_value(value);
+		}
+	}
+
+	/// Closing `}` could be missing at the end of input
+	#[inline]
+	fn mark_block_end(&mut self, block: &Block<'a>) {
+		let end = block.span.end;
+
+		if self.input[..end].last() == Some(&ASCII::CURLY_CLOSE) {
+			self.context.mark(end - 1);
+		}
+	}
+
+	#[inline]
+	fn position(&self, offset: usize) -> Position {
+		Position::at(offset)
+	}
+
+	/// Current value of `cursor` is not the one expected
+	#[inline]
+	fn unexpected(&self, cursor: &Cursor<'_, 'a>) -> Error<'a> {
+		match cursor.token() {
+			Some(token) => self.unexpected_at(token, cursor.offset()),
+			None => Error::UnexpectedEOF(self.position(cursor.offset())),
+		}
+	}
+
+	#[inline]
+	fn unexpected_at(&self, token: Token<'a>, offset: usize) -> Error<'a> {
+		Error::UnexpectedToken {
+			token,
+			position: self.position(offset),
+		}
+	}
+
+	#[inline]
+	fn process_delim(&mut self, delim: u8, cursor: &mut Cursor<'_, 'a>) -> Result<'a, ()> {
 		match delim {
 			ASCII::ASTERISK | ASCII::DASH | ASCII::SLASH | ASCII::GT | ASCII::PLUS | ASCII::TILDE => {
+				// `a * b` is not `a*b`, nor `1px + 2px` is `1px+2px`
+				let optional = match delim {
+					ASCII::GT | ASCII::TILDE => true,
+					ASCII::PLUS => self.selector,
+					ASCII::ASTERISK | ASCII::SLASH => !self.selector,
+					_ => false,
+				};
+
 				if !self.context.is_empty() && !matches!(self.context.last(), Some(b' ')) {
-					self.context.write_space()?;
+					self.write_space(optional)?;
 				}
 
 				self.context.write_u8(delim)?;
 				// FIXME: better space handling?
-				self.context.write_space()?;
+				self.write_space(optional)?;
 			}
 
 			// `!important`
 			ASCII::EXCLAMATION => {
-				self.context.write_space()?;
+				self.context.write_optional_space()?;
 				self.context.write_u8(ASCII::EXCLAMATION)?;
 
-				let Token::Ident(bytes) = self.token_cache.next()? else {
-					return unexpected_token!(self.token_cache.current(), self);
+				let Some(token!(Token::Ident(bytes))) = cursor.next() else {
+					return Err(self.unexpected(cursor));
 				};
 
 				self.context.write_all(bytes)?;
@@ -649,76 +1435,66 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 
 			// Nested selector: `& .parent {` or `.parent & {` or `& + div {`
 			ASCII::AMPERSAND => {
-				match self.token_cache.next()? {
+				let spaced = cursor.prev().is_some_and(ComponentValue::is_whitespace);
+
+				cursor.next();
+
+				match cursor.current() {
 					// Space is mandatory for: `& div` or `& custom-element`
-					Token::Ident(bytes) => {
+					Some(token!(Token::Ident(bytes))) => {
 						self.context.write_u8(delim)?;
 						self.context.write_space()?;
 						self.context.write_all(bytes)?;
 					}
 
-					Token::Delim(ASCII::FULL_STOP) | Token::Colon | Token::Hash(_) => {
-						if unsafe {
-							self
-								.token_cache
-								.peek_prev_with_whitespace()
-								.unwrap_unchecked()
-						} != Token::Whitespace
-						{
+					Some(
+						value @ token!(Token::Delim(ASCII::FULL_STOP) | Token::Colon | Token::Hash(_)),
+					) => {
+						if !cursor.prev().is_some_and(ComponentValue::is_whitespace) {
 							self.context.write_u8(delim)?;
+						} else {
+							self.mark_current(cursor);
 						}
 
-						match self.token_cache.current() {
-							Token::Delim(_) => self.process_delim(ASCII::FULL_STOP)?,
-							Token::Colon => self.format_pseudo()?,
-							Token::Hash(bytes) => self.context.write_all(bytes)?,
-							_ => unsafe { unreachable_unchecked() },
+						match value {
+							token!(Token::Delim(_)) => self.process_delim(ASCII::FULL_STOP, cursor)?,
+							token!(Token::Colon) => self.format_pseudo(cursor)?,
+							token!(Token::Hash(bytes)) => self.context.write_all(bytes)?,
+							_ => return Err(self.unexpected(cursor)),
 						}
 					}
 
-					Token::Delim(ASCII::AMPERSAND) => {
+					Some(token!(Token::Delim(ASCII::AMPERSAND))) => {
 						self.context.write_u8(delim)?;
 
-						if unsafe {
-							self
-								.token_cache
-								.peek_prev_with_whitespace()
-								.unwrap_unchecked()
-						} == Token::Whitespace
-						{
+						if cursor.prev().is_some_and(ComponentValue::is_whitespace) {
 							self.context.write_space()?;
 						}
 
-						self.process_delim(delim)?;
+						self.process_delim(delim, cursor)?;
 					}
 
 					// Remove `&` for `& [+>~] whatever`
-					Token::Delim(del) => self.process_delim(del)?,
-
-					// `& {` or `&& {` or `.parent & {`
-					Token::BracketCurlyOpen => {
-						self.token_cache.prev();
-
-						if !self.context.is_empty()
-							&& unsafe {
-								self
-									.token_cache
-									.peek_prev_with_whitespace()
-									.unwrap_unchecked()
-							} == Token::Whitespace
-						{
+					Some(token!(Token::Delim(del))) => {
+						self.mark_current(cursor);
+						self.process_delim(*del, cursor)?
+					}
+
+					// `& {` or `&& {` or `.parent & {`, the last value of a prelude
+					None if cursor.end() == Some(Token::BracketCurlyOpen) => {
+						if !self.context.is_empty() && spaced {
 							self.context.write_space()?;
 						}
 
 						self.context.write_u8(delim)?;
 					}
 
-					Token::BracketRoundClose => {
-						self.token_cache.prev();
+					// `:is(.parent &)`, the last argument
+					None if cursor.end() == Some(Token::BracketRoundClose) => {
 						self.context.write_u8(delim)?;
 					}
 
-					_ => return unexpected_token!(self.token_cache.current(), self),
+					_ => return Err(self.unexpected(cursor)),
 				}
 			}
 
@@ -726,8 +1502,8 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 			ASCII::FULL_STOP => {
 				self.context.write_u8(delim)?;
 
-				let Token::Ident(bytes) = self.token_cache.next()? else {
-					return unexpected_token!(self.token_cache.current(), self);
+				let Some(token!(Token::Ident(bytes))) = cursor.next() else {
+					return Err(self.unexpected(cursor));
 				};
 
 				self.context.write_all(bytes)?;
@@ -736,52 +1512,61 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 			ASCII::HASH => {
 				self.context.write_u8(delim)?;
 
-				let Token::Number(bytes) = self.token_cache.next()? else {
-					return unexpected_token!(self.token_cache.current(), self);
+				let Some(token!(Token::Number(bytes))) = cursor.next() else {
+					return Err(self.unexpected(cursor));
 				};
 
 				self.context.write_all(bytes)?;
 			}
 
-			_ => return unexpected_token!(self.token_cache.current(), self),
+			_ => return Err(self.unexpected(cursor)),
 		}
 
 		Ok(())
 	}
 
 	#[inline]
-	fn process_whitespace(&mut self) -> Result<'a, ()> {
-		let (Some(prev), Ok(next)) = (
-			self.token_cache.peek_prev_with_whitespace(),
-			self.token_cache.peek_next(),
-		) else {
+	fn process_whitespace(&mut self, cursor: &Cursor<'_, 'a>) -> Result<'a, ()> {
+		let (Some(mut prev), mut next) = (cursor.prev(), cursor.peek_next()) else {
 			return Ok(());
 		};
 
-		if matches!(
-			prev,
-			Token::AtRule(_)
-				| Token::BracketSquareClose
-				| Token::Colon
-				| Token::Function(_)
-				| Token::Hash(_)
-				| Token::Ident(_)
-				| Token::Number(_)
-				| Token::String(_)
-				| Token::BracketRoundClose
-		) && matches!(
-			next,
-			Token::AtRule(_)
-				| Token::BracketSquareOpen
-				| Token::Colon
-				| Token::Delim(ASCII::FULL_STOP)
-				| Token::Function(_)
-				| Token::Hash(_)
-				| Token::Ident(_)
-				| Token::Number(_)
-				| Token::String(_)
-				| Token::BracketRoundOpen
-		) {
+		let kept = |value: &ComponentValue<'a>| {
+			matches!(value, token!(Token::Comment(text)) if self.keeps_comment(text))
+		};
+		let is_comment = |value: &ComponentValue<'a>| matches!(value, token!(Token::Comment(_)));
+
+		// `a /* x */ b` is not `a/* x */b`, spaces around a comment are kept once
+		if kept(prev) || next.is_some_and(kept) {
+			if !self.context.is_empty() && !matches!(self.context.last(), Some(b' ')) {
+				let optional = matches!(self.context.last(), Some(b'(' | b','));
+
+				self.write_space(optional)?;
+			}
+
+			return Ok(());
+		}
+
+		// Comment left out is not there at all, e.g. `a /* x */ {` is `a {`
+		if is_comment(prev) || next.is_some_and(is_comment) {
+			if matches!(self.context.last(), Some(b' ')) {
+				return Ok(());
+			}
+
+			let skip = |value: &ComponentValue<'a>| value.is_whitespace() || is_comment(value);
+
+			let (Some(significant_prev), significant_next) =
+				(cursor.prev_skipping(skip), cursor.next_skipping(skip))
+			else {
+				return Ok(());
+			};
+
+			(prev, next) = (significant_prev, significant_next);
+		}
+
+		if matches!(prev, spaced!())
+			&& matches!(next, Some(spaced!(Token::Delim(ASCII::FULL_STOP))))
+		{
 			self.context.write_space()?;
 		}
 
@@ -789,14 +1574,30 @@ impl<'a, T: std::io::Write> Formatter<'a, T> {
 	}
 }
 
+/// Comment text is trimmed, so that `/*hel-fmt-off*/` works as well
+#[inline]
+fn directive(bytes: &[u8]) -> Option<Directive> {
+	match bytes.trim_ascii() {
+		b"hel-fmt-ignore" => Some(Directive::Ignore),
+		b"hel-fmt-off" => Some(Directive::Off),
+		b"hel-fmt-on" => Some(Directive::On),
+		_ => None,
+	}
+}
+
+/// License `/*! ... */` and documentation `/** ... */` comments are kept as is
+#[inline(always)]
+fn is_verbatim_comment(bytes: &[u8]) -> bool {
+	matches!(bytes.first(), Some(&(ASCII::EXCLAMATION | ASCII::ASTERISK)))
+}
+
 impl<'a> From<ParserError> for Error<'a> {
 	#[inline]
 	fn from(value: ParserError) -> Self {
 		match value {
-			ParserError::BadComment => Error::BadComment,
-			ParserError::BadString => Error::BadString,
-			ParserError::EOF => Error::UnexpectedEOF,
-			ParserError::NonASCII => Error::UnexpectedUTF8,
+			ParserError::BadComment(position) => Error::BadComment(position),
+			ParserError::BadString(position) => Error::BadString(position),
+			ParserError::EOF(position) => Error::UnexpectedEOF(position),
 		}
 	}
 }
@@ -808,30 +1609,60 @@ impl<'a> From<std::io::Error> for Error<'a> {
 	}
 }
 
-impl<'a> From<context::IntegerOverflow> for Error<'a> {
+impl<'a> Error<'a> {
+	/// Copies bytes of the token out of the input
 	#[inline]
-	fn from(_: context::IntegerOverflow) -> Self {
-		Error::TooManyLevelsOfIndentation
+	pub fn into_owned(self) -> OwnedError {
+		match self {
+			Error::BadComment(position) => ErrorOf::BadComment(position),
+			Error::BadString(position) => ErrorOf::BadString(position),
+			Error::IO(err) => ErrorOf::IO(err),
+			Error::TooManyLevelsOfIndentation(position) => {
+				ErrorOf::TooManyLevelsOfIndentation(position)
+			}
+			Error::UnexpectedEOF(position) => ErrorOf::UnexpectedEOF(position),
+			Error::UnexpectedUTF8(position) => ErrorOf::UnexpectedUTF8(position),
+			Error::UnexpectedToken { token, position } => ErrorOf::UnexpectedToken {
+				token: token.into(),
+				position,
+			},
+		}
 	}
 }
 
-impl<'a> Error<'a> {
-	pub fn leak(self) -> Error<'static> {
+impl<T> ErrorOf<T> {
+	/// Where in the input error occurred, `None` for IO errors
+	#[inline]
+	pub fn position(&self) -> Option<Position> {
 		match self {
-			Error::BadComment => Error::BadComment,
-			Error::BadString => Error::BadString,
-			Error::IO(err) => Error::IO(err),
-			Error::TooManyLevelsOfIndentation => Error::TooManyLevelsOfIndentation,
-			Error::UnexpectedEOF => Error::UnexpectedEOF,
-			Error::UnexpectedUTF8 => Error::UnexpectedUTF8,
-			Error::UnexpectedToken { token, line } => Error::UnexpectedToken {
-				token: token.leak(),
-				line,
-			},
+			Self::BadComment(position)
+			| Self::BadString(position)
+			| Self::TooManyLevelsOfIndentation(position)
+			| Self::UnexpectedEOF(position)
+			| Self::UnexpectedUTF8(position)
+			| Self::UnexpectedToken { position, .. } => Some(*position),
+			Self::IO(_) => None,
+		}
+	}
+
+	#[inline]
+	pub fn position_mut(&mut self) -> Option<&mut Position> {
+		match self {
+			Self::BadComment(position)
+			| Self::BadString(position)
+			| Self::TooManyLevelsOfIndentation(position)
+			| Self::UnexpectedEOF(position)
+			| Self::UnexpectedUTF8(position)
+			| Self::UnexpectedToken { position, .. } => Some(position),
+			Self::IO(_) => None,
 		}
 	}
 }
 
 mod context;
+mod cursor;
 mod line;
 mod utils;
+
+#[cfg(test)]
+mod tests;
@@ -1,56 +1,209 @@
-use std::io::Write;
-
 use crate::css::properties::{Descriptor, Trie};
+use crate::source_map::SourceMap;
+use crate::utils::{display_width, Helper as _};
+use crate::FormatOptions;
 
-use super::{line::Line, utils::Helper};
+use super::line::{Line, Sink, Splitter};
+use super::utils::Helper;
 
 use consts::ASCII;
-use layer_manager::LayerManager;
+use layer_manager::{Layer, LayerManager};
+use output::Output;
 
 #[derive(Debug)]
 pub struct IntegerOverflow;
 
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Declaration {
-	pub descriptor: Descriptor,
+	/// Declarations are sorted within a section, see [`FormatOptions::sort_boundaries`]
+	pub section: u32,
+	pub descriptor: Descriptor<'static>,
 	pub line: Line,
+	/// Copied from the input as is, must not be split
+	pub verbatim: bool,
+	/// Comments right above, they go wherever the declaration does
+	pub comments: Vec<Line>,
+	/// Standalone comments the section of the declaration starts with
+	pub heading: Vec<Line>,
 }
 
-pub struct Context<T> {
-	output: T,
+/// Snapshot of [`Context`] taken before formatting an item, see [`Context::rollback`]
+#[derive(Clone, Copy, Debug)]
+pub struct Checkpoint {
+	indent: u8,
+	layer: usize,
+	declarations: usize,
+	line: usize,
+	written: usize,
+}
+
+/// Memory reused between formatting runs, one per concurrently running formatter
+#[derive(Default)]
+pub struct Session {
 	layers: LayerManager,
+	splitter: Splitter,
+	props: Trie,
+	source_map: SourceMap,
+}
+
+// Sessions are handed out to worker threads
+const _: () = {
+	const fn assert_send<T: Send>() {}
+	assert_send::<Session>();
+};
+
+pub struct Context<'s, T> {
+	output: Output<'s, T>,
+	options: FormatOptions,
+	layers: &'s mut LayerManager,
+	splitter: &'s mut Splitter,
 
 	indent: u8,
 	line_num: u32,
 
 	current_line: Line,
+	/// Current line is swapped with a declaration
+	in_declaration: bool,
 
-	props: Trie,
+	props: &'s Trie,
 }
 
-impl<T> Context<T>
+impl<'s, T> Context<'s, T>
 where
 	T: std::io::Write,
 {
+	/// Takes a snapshot to [`Self::rollback`] to, if formatting of an item fails.
+	///
+	/// Top level snapshot holds output until [`Self::release`]
+	#[inline]
+	pub fn checkpoint(&mut self) -> Checkpoint {
+		let (declarations, written) = match self.layers.current() {
+			Some(layer) => (layer.declarations().len(), layer.main().len()),
+			None => {
+				self.output.hold();
+				(0, self.output.held_len())
+			}
+		};
+
+		Checkpoint {
+			indent: self.indent,
+			layer: self.layers.depth(),
+			declarations,
+			line: self.current_line.len(),
+			written,
+		}
+	}
+
 	#[inline]
 	pub fn declaration_end(&mut self) {
-		let current_layer = unsafe { self.layers.current().unwrap_unchecked() };
-		let declarations = current_layer.declarations_mut();
+		// Outside of a block declaration stays in current line
+		let Some(current_layer) = self.layers.current() else {
+			return;
+		};
 
-		let line = declarations.pop();
+		let Some(line) = current_layer.declarations_mut().pop() else {
+			return;
+		};
 
 		std::mem::swap(&mut self.current_line, line);
+		self.in_declaration = false;
 	}
 
 	#[inline]
 	pub fn declaration_start(&mut self, with: &[u8]) {
-		let current_layer = unsafe { self.layers.current().unwrap_unchecked() };
+		let desc = self.get_descriptor(with);
+
+		let Some(current_layer) = self.layers.current() else {
+			return;
+		};
+
 		let declarations = current_layer.declarations_mut();
 
-		let desc = self.get_descriptor(with);
-		let line = declarations.push(desc);
+		let line = declarations.push(&desc);
 
 		std::mem::swap(&mut self.current_line, line);
+		self.in_declaration = true;
+	}
+
+	/// Continues the line of the last declaration, until [`Self::declaration_end`].
+	/// Returns `false` if there is none
+	#[inline]
+	pub fn declaration_resume(&mut self) -> bool {
+		let Some(line) = self
+			.layers
+			.current()
+			.and_then(|layer| layer.declarations_mut().pop())
+		else {
+			return false;
+		};
+
+		std::mem::swap(&mut self.current_line, line);
+		self.in_declaration = true;
+
+		true
+	}
+
+	/// Moves current line, e.g. a comment, above the last declaration, so they are sorted
+	/// together. With `heading` it's above the section the declaration starts instead
+	#[inline]
+	pub fn declaration_comment(&mut self, heading: bool) {
+		let line = std::mem::take(&mut self.current_line);
+
+		let Some(declaration) = self
+			.layers
+			.current()
+			.and_then(|layer| layer.declarations_mut().last_mut())
+		else {
+			self.current_line = line;
+			return;
+		};
+
+		match heading {
+			true => declaration.heading.push(line),
+			false => declaration.comments.push(line),
+		}
+	}
+
+	/// Declarations pushed from now on are sorted apart from ones before
+	#[inline]
+	pub fn declaration_section(&mut self) {
+		if let Some(layer) = self.layers.current() {
+			layer.declarations_mut().next_section();
+		}
+	}
+
+	/// Adds a declaration copied from the input as is, it still takes part in sorting
+	#[inline]
+	pub fn declaration_verbatim(&mut self, bytes: &[u8]) {
+		let name_len = bytes
+			.iter()
+			.position(|byte| !byte.is_ident_char())
+			.unwrap_or(bytes.len());
+
+		self.declaration_start(&bytes[..name_len]);
+		self.current_line.extend_from_slice(bytes);
+		self.declaration_end();
+
+		if let Some(declaration) = self
+			.layers
+			.current()
+			.and_then(|layer| layer.declarations_mut().last_mut())
+		{
+			declaration.verbatim = true;
+		}
+	}
+
+	/// Must be called once everything is flushed, source map is resolved against `input`
+	#[inline]
+	pub fn finish(&mut self, input: &[u8]) -> std::io::Result<()> {
+		self.output.resolve_source_map(input);
+
+		// Minified output has no newline to hold back
+		if self.options.minify {
+			self.output.write_newline()?;
+		}
+
+		self.output.finish(self.options.final_newline)
 	}
 
 	/// Flushes self into [`T`], or current layer if any
@@ -61,12 +214,16 @@ where
 				&mut self.current_line,
 				&mut self.line_num,
 				self.indent,
+				&self.options,
+				self.splitter,
 				layer.main_mut(),
 			),
 			None => Self::flush_into(
 				&mut self.current_line,
 				&mut self.line_num,
 				self.indent,
+				&self.options,
+				self.splitter,
 				&mut self.output,
 			),
 		}
@@ -77,22 +234,58 @@ where
 		current_line: &mut Line,
 		line_num: &mut u32,
 		indent: u8,
-		output: &mut impl std::io::Write,
+		options: &FormatOptions,
+		splitter: &mut Splitter,
+		output: &mut impl Sink,
 	) -> std::io::Result<()> {
+		// Everything goes on a single line
+		if options.minify {
+			current_line.write_into(output)?;
+			current_line.clear();
+
+			return Ok(());
+		}
+
 		if current_line.is_empty() {
 			*line_num += 1;
 
 			output.write_newline()?;
 		} else {
-			*line_num += current_line.flush_self_with_indent(indent, output)?;
+			*line_num += current_line.flush_self_with_indent(indent, options, splitter, output)?;
 		}
 
 		Ok(())
 	}
 
+	/// Next byte written maps to `source` offset of the input, if source map is enabled
 	#[inline]
-	fn get_descriptor(&self, bytes: &[u8]) -> Descriptor {
-		let name = unsafe { std::str::from_utf8_unchecked(bytes) };
+	pub fn mark(&mut self, source: usize) {
+		if self.options.source_map {
+			self.current_line.mark(source);
+		}
+	}
+
+	/// Space that is only there for readability, left out when minifying
+	#[inline]
+	pub fn write_optional_space(&mut self) -> std::io::Result<()> {
+		if self.options.minify {
+			return Ok(());
+		}
+
+		self.current_line.write_space()
+	}
+
+	#[inline]
+	pub fn options(&self) -> &FormatOptions {
+		&self.options
+	}
+
+	#[inline]
+	fn get_descriptor<'b>(&self, bytes: &'b [u8]) -> Descriptor<'b> {
+		let Ok(name) = std::str::from_utf8(bytes) else {
+			// Entry points validate UTF-8, anything slipping past sorts as unknown
+			return Descriptor::unknown("");
+		};
 
 		if bytes.len() > 1 && bytes[0] == ASCII::DASH {
 			return if bytes[1] == ASCII::DASH {
@@ -105,7 +298,7 @@ where
 		}
 
 		if let Some(desc) = self.props.get(bytes) {
-			return *desc;
+			return desc.clone();
 		}
 
 		Descriptor::unknown(name)
@@ -116,6 +309,12 @@ where
 		self.indent
 	}
 
+	/// Amount of lines written so far
+	#[inline(always)]
+	pub fn line_num(&self) -> u32 {
+		self.line_num
+	}
+
 	#[inline]
 	pub fn indent_dec(&mut self) -> Result<u8, IntegerOverflow> {
 		self.indent = self.indent.checked_sub(1).ok_or(IntegerOverflow)?;
@@ -135,39 +334,92 @@ where
 			"Logical error - popped a layer before any were pushed"
 		);
 
-		debug_assert!(
-			self.current_line.is_empty(),
-			"Current buffer should be empty at this point!"
-		);
+		// Leftovers of malformed input, e.g. stray delim in `a { > }`
+		if !self.current_line.is_empty() {
+			self.flush()?;
+		}
+
+		let Some(mut layer) = self.layers.pop() else {
+			return Ok(());
+		};
+
+		if !layer.head().is_empty() {
+			if self.fits_on_head(&layer) {
+				return self.layer_pop_inline(layer);
+			}
 
-		let layer = self.layers.pop();
+			// Block starts on its own line after all, at indentation of the parent
+			self.indent_dec().ok();
+			std::mem::swap(&mut self.current_line, layer.head_mut());
+			self.flush()?;
+			self.indent_inc().ok();
+		}
 
+		let nested_rules = !layer.main().is_empty();
 		let declarations: &mut [Declaration] = layer.declarations_mut();
 
+		declarations.sort();
+
+		// Nothing follows the last declaration but `}`
+		if self.options.minify && !nested_rules {
+			if let Some(Declaration {
+				line,
+				verbatim: false,
+				..
+			}) = declarations.last_mut()
+			{
+				if line.last() == Some(&ASCII::SEMICOLON) {
+					line.truncate(line.len() - 1);
+				}
+			}
+		}
+
 		if !declarations.is_empty() {
-			declarations.sort();
+			let mut prev: Option<&Declaration> = None;
+
+			for (i, declaration) in declarations.iter().enumerate() {
+				match prev {
+					None => self.write_heading(&declarations[i..])?,
+					Some(prev) if prev.section != declaration.section => {
+						self.flush()?;
+						self.write_heading(&declarations[i..])?;
+					}
+					Some(prev) if prev.descriptor.group() != declaration.descriptor.group() => {
+						self.flush()?;
+					}
+					Some(_) => {}
+				}
 
-			let mut group = unsafe { declarations.first().unwrap_unchecked() }
-				.descriptor
-				.group();
+				prev = Some(declaration);
 
-			for Declaration { descriptor, line } in declarations.iter() {
-				if descriptor.group() != group {
-					group = descriptor.group();
+				let Declaration {
+					line,
+					verbatim,
+					comments,
+					..
+				} = declaration;
+
+				for comment in comments {
+					comment.write_into(&mut self.current_line)?;
 					self.flush()?;
 				}
 
-				self.write_all(line)?;
-				self.flush()?;
+				if *verbatim {
+					self.write_verbatim(line)?;
+				} else {
+					line.write_into(&mut self.current_line)?;
+					self.flush()?;
+				}
 			}
 
-			if !layer.main().is_empty() {
+			if nested_rules {
 				self.flush()?;
 			}
 		}
 
 		// WARNING: DO NOT REORDER
-		unsafe { self.indent_dec().unwrap_unchecked() };
+		// Balanced with `indent_inc` in `layer_push`
+		self.indent_dec().ok();
 
 		{
 			// This is already formatted
@@ -175,14 +427,40 @@ where
 
 			// ... so we just flush it down the stack
 			match self.layers.current() {
-				Some(layer) => layer.main_mut().write_all(main),
-				None => self.output.write_all(main),
+				Some(layer) => main.write_into(layer.main_mut()),
+				None => main.write_into(&mut self.output),
 			}?;
 		}
 
+		self.layers.recycle(layer);
+
 		Ok(())
 	}
 
+	/// Writes standalone comments `section` starts with, followed by an empty line
+	#[inline]
+	fn write_heading(&mut self, section: &[Declaration]) -> std::io::Result<()> {
+		let Some(first) = section.first() else {
+			return Ok(());
+		};
+
+		let Some(heading) = section
+			.iter()
+			.take_while(|declaration| declaration.section == first.section)
+			.map(|declaration| &declaration.heading)
+			.find(|heading| !heading.is_empty())
+		else {
+			return Ok(());
+		};
+
+		for comment in heading {
+			comment.write_into(&mut self.current_line)?;
+			self.flush()?;
+		}
+
+		self.flush()
+	}
+
 	#[inline]
 	pub fn layer_push(&mut self) -> Result<(), IntegerOverflow> {
 		self.indent_inc()?;
@@ -190,24 +468,150 @@ where
 		Ok(())
 	}
 
+	/// Same as [`Self::layer_push`], but holds on to current line, e.g. `a {`,
+	/// to put declarations of the layer on it once popped, if they fit
+	#[inline]
+	pub fn layer_push_inline(&mut self) -> Result<(), IntegerOverflow> {
+		self.layer_push()?;
+
+		if let Some(layer) = self.layers.current() {
+			std::mem::swap(&mut self.current_line, layer.head_mut());
+		}
+
+		Ok(())
+	}
+
+	/// Line of `layer` would be `a { color: red; }`, with `}` written by the caller
+	#[inline]
+	fn fits_on_head(&self, layer: &Layer) -> bool {
+		let declarations = layer.declarations();
+
+		if !layer.main().is_empty() || declarations.iter().any(|declaration| declaration.verbatim) {
+			return false;
+		}
+
+		let width = display_width(layer.head())
+			+ declarations
+				.iter()
+				.map(|declaration| 1 + display_width(&declaration.line))
+				.sum::<usize>()
+			+ " }".len();
+
+		let parent_indent = self.indent.saturating_sub(1);
+
+		width
+			<= self
+				.options
+				.print_width
+				.saturating_sub(self.options.indent_columns(parent_indent))
+	}
+
+	/// Puts sorted declarations of `layer` on its head, which becomes current line again
 	#[inline]
-	pub fn new(output: T) -> Self {
+	fn layer_pop_inline(&mut self, mut layer: Layer) -> std::io::Result<()> {
+		// Balanced with `indent_inc` in `layer_push`
+		self.indent_dec().ok();
+
+		std::mem::swap(&mut self.current_line, layer.head_mut());
+
+		let declarations: &mut [Declaration] = layer.declarations_mut();
+		declarations.sort();
+
+		for Declaration { line, .. } in declarations.iter() {
+			self.current_line.write_space()?;
+			line.write_into(&mut self.current_line)?;
+		}
+
+		self.current_line.write_space()?;
+
+		self.layers.recycle(layer);
+
+		Ok(())
+	}
+
+	/// Writes out everything held since top level [`Self::checkpoint`]
+	#[inline]
+	pub fn release(&mut self) -> std::io::Result<()> {
+		self.output.release()
+	}
+
+	/// Discards everything formatted since `checkpoint`
+	#[inline]
+	pub fn rollback(&mut self, checkpoint: Checkpoint) {
+		if self.in_declaration {
+			self.declaration_end();
+		}
+
+		self.layers.truncate(checkpoint.layer);
+
+		self.indent = checkpoint.indent;
+		self.current_line.truncate(checkpoint.line);
+
+		match self.layers.current() {
+			Some(layer) => {
+				layer.declarations_mut().truncate(checkpoint.declarations);
+				layer.main_mut().truncate(checkpoint.written);
+			}
+			None => self.output.truncate(checkpoint.written),
+		}
+	}
+
+	/// Writes `bytes` on their own line as is, without splitting
+	#[inline]
+	pub fn write_verbatim(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+		debug_assert!(self.current_line.is_empty());
+
+		self.line_num += 1 + bytes.iter().filter(|&&byte| byte == ASCII::LF).count() as u32;
+
+		// Ends its line even when minifying, it might not end where a token does
+		let indent = if self.options.minify { 0 } else { self.indent };
+
+		match self.layers.current() {
+			Some(layer) => layer
+				.main_mut()
+				.finish_line_with_indent(bytes, indent, &self.options),
+			None => self
+				.output
+				.finish_line_with_indent(bytes, indent, &self.options),
+		}
+	}
+
+	#[inline]
+	pub fn new(output: T, options: FormatOptions, session: &'s mut Session) -> Self {
+		let Session {
+			layers,
+			splitter,
+			props,
+			source_map,
+		} = session;
+
+		// Previous run might have failed half way through
+		layers.reset();
+		source_map.clear();
+
 		Self {
-			output,
+			output: Output::new(
+				output,
+				options.end_of_line,
+				options.source_map.then_some(source_map),
+			),
+			options,
 
-			layers: LayerManager::default(),
+			layers,
+			splitter,
 
 			indent: 0,
 			line_num: 0,
 
 			current_line: Line::new(),
+			in_declaration: false,
 
-			props: Trie::new(),
+			props,
 		}
 	}
 }
 
-impl<T> std::ops::Deref for Context<T> {
+impl<T> std::ops::Deref for Context<'_, T> {
 	type Target = Line;
 
 	#[inline(always)]
@@ -216,17 +620,17 @@ impl<T> std::ops::Deref for Context<T> {
 	}
 }
 
-impl<T> std::ops::DerefMut for Context<T> {
+impl<T> std::ops::DerefMut for Context<'_, T> {
 	#[inline(always)]
 	fn deref_mut(&mut self) -> &mut Self::Target {
 		&mut self.current_line
 	}
 }
 
-impl<T> std::fmt::Debug for Context<T> {
+impl<T> std::fmt::Debug for Context<'_, T> {
 	#[inline]
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		let buf = unsafe { std::str::from_utf8_unchecked(&self.current_line) };
+		let buf = String::from_utf8_lossy(&self.current_line);
 
 		writeln!(f, "Line: {}", self.line_num)?;
 		writeln!(f, "Indentation: {}", self.indent)?;
@@ -234,11 +638,31 @@ impl<T> std::fmt::Debug for Context<T> {
 	}
 }
 
+impl Session {
+	/// Source map of the last run, empty unless [`FormatOptions::source_map`] is set
+	#[inline(always)]
+	pub fn source_map(&self) -> &SourceMap {
+		&self.source_map
+	}
+}
+
+impl Checkpoint {
+	/// Taken at top level, outside of any block
+	#[inline(always)]
+	pub fn is_root(&self) -> bool {
+		self.layer == 0 && self.indent == 0
+	}
+}
+
 impl Declaration {
 	#[inline]
 	fn clear(&mut self) {
-		self.line.clear()
+		self.line.clear();
+		self.verbatim = false;
+		self.comments.clear();
+		self.heading.clear();
 	}
 }
 
 mod layer_manager;
+mod output;
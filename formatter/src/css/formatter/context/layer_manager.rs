@@ -2,8 +2,10 @@ use crate::css::{formatter::line::Line, properties::Descriptor};
 
 use super::Declaration;
 
-#[derive(Clone, Copy, Default)]
+/// Stack of layers, popped layers are kept around to reuse their allocations
+#[derive(Default)]
 pub struct LayerManager {
+	arena: Vec<Layer>,
 	current: usize,
 }
 
@@ -11,6 +13,8 @@ pub struct LayerManager {
 pub struct DeclarationManager {
 	inner: Vec<Declaration>,
 	cursor: usize,
+	/// Section of declarations pushed next
+	section: u32,
 }
 
 #[derive(Default)]
@@ -19,46 +23,63 @@ pub struct Layer {
 	declarations: DeclarationManager,
 	/// Nested block or at-rules
 	main: Line,
-}
-
-#[thread_local]
-static mut ARENA: Vec<Layer> = Vec::new();
-
-macro_rules! get {
-	() => {
-		unsafe { &mut *std::ptr::addr_of_mut!(ARENA) }
-	};
-
-	($idx: expr) => {
-		get!()[$idx]
-	};
+	/// Line the block starts on, e.g. `a {`, see [`super::Context::layer_push_inline`]
+	head: Line,
 }
 
 impl LayerManager {
+	/// Amount of pushed layers
+	#[inline(always)]
+	pub fn depth(&self) -> usize {
+		self.current
+	}
+
 	#[inline]
 	pub fn push(&mut self) {
 		// We waste zeroth layer
 		self.current += 1;
 
-		get!().get_or_init(self.current).clear();
+		self.arena.get_or_init(self.current).clear();
 	}
 
+	/// Takes current layer out, should be given back with [`Self::recycle`].
+	/// Returns [`None`] if nothing was pushed
 	#[inline]
-	pub fn pop(&mut self) -> &'static mut Layer {
-		let layer = &mut get![self.current];
+	pub fn pop(&mut self) -> Option<Layer> {
+		let layer = std::mem::take(self.current()?);
 
 		self.current -= 1;
 
-		layer
+		Some(layer)
+	}
+
+	/// Gives back allocations of a layer taken by [`Self::pop`]
+	#[inline]
+	pub fn recycle(&mut self, layer: Layer) {
+		if let Some(slot) = self.arena.get_mut(self.current + 1) {
+			*slot = layer;
+		}
+	}
+
+	/// Drops layers above `depth`
+	#[inline]
+	pub fn truncate(&mut self, depth: usize) {
+		self.current = self.current.min(depth);
+	}
+
+	/// Drops every layer, keeping allocations
+	#[inline(always)]
+	pub fn reset(&mut self) {
+		self.current = 0;
 	}
 
 	#[inline]
-	pub fn current(self) -> Option<&'static mut Layer> {
+	pub fn current(&mut self) -> Option<&mut Layer> {
 		if self.current == 0 {
 			return None;
 		}
 
-		Some(&mut get![self.current])
+		Some(&mut self.arena[self.current])
 	}
 }
 
@@ -67,6 +88,7 @@ impl Layer {
 	fn clear(&mut self) {
 		self.main.clear();
 		self.declarations.clear();
+		self.head.clear();
 	}
 
 	#[inline(always)]
@@ -87,29 +109,52 @@ impl Layer {
 	pub fn main_mut(&mut self) -> &mut Line {
 		&mut self.main
 	}
+
+	#[inline(always)]
+	pub fn head(&self) -> &Line {
+		&self.head
+	}
+
+	#[inline(always)]
+	pub fn head_mut(&mut self) -> &mut Line {
+		&mut self.head
+	}
 }
 
 impl DeclarationManager {
 	#[inline]
 	fn clear(&mut self) {
 		self.cursor = 0;
+		self.section = 0;
 	}
 
-	pub fn push(&mut self, descriptor: Descriptor) -> &mut Line {
+	pub fn push(&mut self, descriptor: &Descriptor) -> &mut Line {
 		let declaration = self.inner.get_or_init(self.cursor);
 		declaration.clear();
 
-		declaration.descriptor = descriptor;
+		declaration.section = self.section;
+		declaration.descriptor.assign(descriptor);
 
 		self.cursor += 1;
 
 		&mut declaration.line
 	}
 
-	pub fn pop(&mut self) -> &mut Line {
-		debug_assert!(self.cursor > 0, "Popped before push");
+	/// Returns line of the last pushed declaration, [`None`] if nothing was pushed
+	pub fn pop(&mut self) -> Option<&mut Line> {
+		let last = self.cursor.checked_sub(1)?;
+
+		Some(&mut self.inner[last].line)
+	}
 
-		&mut self.inner[self.cursor - 1].line
+	#[inline]
+	pub fn truncate(&mut self, len: usize) {
+		self.cursor = self.cursor.min(len);
+	}
+
+	#[inline(always)]
+	pub fn next_section(&mut self) {
+		self.section += 1;
 	}
 }
 
@@ -0,0 +1,198 @@
+use consts::ASCII;
+
+use crate::css::formatter::line::{Line, Sink};
+use crate::source_map::SourceMap;
+use crate::utils::utf16_len;
+use crate::EndOfLine;
+
+/// Final destination of formatted bytes
+///
+/// Translates `\n` into configured line ending and holds back trailing newlines,
+/// so output could end with exactly one (or none) of them
+pub struct Output<'s, T> {
+	inner: T,
+	end_of_line: EndOfLine,
+	/// Newlines that were written last, but not yet passed to `inner`
+	pending_newlines: usize,
+
+	/// Everything written while holding, could still be truncated
+	held: Line,
+	holding: bool,
+
+	/// Filled in only if [`crate::FormatOptions::source_map`] is set
+	source_map: Option<&'s mut SourceMap>,
+	/// Zero-based line and UTF-16 column of the next byte passed to `inner`
+	line: u32,
+	column: u32,
+}
+
+impl<'s, T: std::io::Write> Output<'s, T> {
+	/// Length of held bytes
+	#[inline(always)]
+	pub fn held_len(&self) -> usize {
+		self.held.len()
+	}
+
+	/// Keep everything written from now on, until [`Self::release`]
+	#[inline(always)]
+	pub fn hold(&mut self) {
+		self.holding = true;
+	}
+
+	#[inline]
+	pub fn release(&mut self) -> std::io::Result<()> {
+		self.holding = false;
+
+		let held = std::mem::take(&mut self.held);
+		held.write_into(self)?;
+
+		// Keep allocation
+		self.held = held;
+		self.held.clear();
+
+		Ok(())
+	}
+
+	#[inline(always)]
+	pub fn truncate(&mut self, len: usize) {
+		self.held.truncate(len);
+	}
+
+	/// Writes held back newline if `final_newline` is set
+	#[inline]
+	pub fn finish(&mut self, final_newline: bool) -> std::io::Result<()> {
+		if final_newline && self.pending_newlines > 0 {
+			self.inner.write_all(self.end_of_line.as_bytes())?;
+		}
+
+		self.pending_newlines = 0;
+
+		Ok(())
+	}
+
+	#[inline]
+	pub fn new(inner: T, end_of_line: EndOfLine, source_map: Option<&'s mut SourceMap>) -> Self {
+		Self {
+			inner,
+			end_of_line,
+			pending_newlines: 0,
+
+			held: Line::default(),
+			holding: false,
+
+			source_map,
+			line: 0,
+			column: 0,
+		}
+	}
+
+	/// Turns input offsets of the source map into lines and columns
+	#[inline]
+	pub fn resolve_source_map(&mut self, input: &[u8]) {
+		if let Some(source_map) = &mut self.source_map {
+			source_map.resolve(input);
+		}
+	}
+
+	/// Moves position past `content`, that does not end with a newline
+	#[inline]
+	fn advance(&mut self, content: &[u8]) {
+		if self.source_map.is_none() {
+			return;
+		}
+
+		if self.pending_newlines > 0 {
+			self.line += self.pending_newlines as u32;
+			self.column = 0;
+		}
+
+		match content.iter().rposition(|&byte| byte == ASCII::LF) {
+			Some(i) => {
+				self.line += content.iter().filter(|&&byte| byte == ASCII::LF).count() as u32;
+				self.column = utf16_len(&content[i + 1..]) as u32;
+			}
+			None => self.column += utf16_len(content) as u32,
+		}
+	}
+
+	#[inline]
+	fn write_translated(&mut self, buf: &[u8]) -> std::io::Result<()> {
+		if self.end_of_line == EndOfLine::Lf {
+			return self.inner.write_all(buf);
+		}
+
+		let mut lines = buf.split(|&byte| byte == ASCII::LF).peekable();
+
+		while let Some(line) = lines.next() {
+			if lines.peek().is_none() {
+				return self.inner.write_all(line);
+			}
+
+			// Source might already use `\r\n` (e.g. inside comments)
+			self
+				.inner
+				.write_all(line.strip_suffix(&[ASCII::CR]).unwrap_or(line))?;
+			self.inner.write_all(self.end_of_line.as_bytes())?;
+		}
+
+		Ok(())
+	}
+}
+
+impl<T: std::io::Write> Sink for Output<'_, T> {
+	#[inline]
+	fn mark(&mut self, source: usize) {
+		if self.holding {
+			self.held.mark(source);
+			return;
+		}
+
+		let Some(source_map) = &mut self.source_map else {
+			return;
+		};
+
+		// Held back newlines come before the next byte
+		match self.pending_newlines {
+			0 => source_map.push(self.line, self.column, source),
+			pending => source_map.push(self.line + pending as u32, 0, source),
+		}
+	}
+}
+
+impl<T: std::io::Write> std::io::Write for Output<'_, T> {
+	#[inline]
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.write_all(buf)?;
+		Ok(buf.len())
+	}
+
+	#[inline]
+	fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+		if self.holding {
+			self.held.extend_from_slice(buf);
+			return Ok(());
+		}
+
+		let content_len = buf.len() - buf.iter().rev().take_while(|&&b| b == ASCII::LF).count();
+
+		if content_len == 0 {
+			self.pending_newlines += buf.len();
+			return Ok(());
+		}
+
+		for _ in 0..self.pending_newlines {
+			self.inner.write_all(self.end_of_line.as_bytes())?;
+		}
+
+		self.advance(&buf[..content_len]);
+		self.write_translated(&buf[..content_len])?;
+		self.pending_newlines = buf.len() - content_len;
+
+		Ok(())
+	}
+
+	#[inline(always)]
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.inner.flush()
+	}
+}
@@ -0,0 +1,153 @@
+use ::css::{ComponentValue, Function, SimpleBlock};
+
+use crate::css::tokens::Token;
+
+/// Walks component values of one nesting level, e.g. arguments of a function,
+/// one by one, the same way tokens are pulled from a parser
+#[derive(Clone, Debug)]
+pub struct Cursor<'t, 'a> {
+	values: &'t [ComponentValue<'a>],
+	/// Of current value, equals to length once every value is consumed
+	index: usize,
+	/// Token right after the last value, e.g. `)` of a function, [`None`] at the end of input
+	end: Option<Token<'a>>,
+	/// Where `end` starts
+	end_offset: usize,
+}
+
+impl<'t, 'a> Cursor<'t, 'a> {
+	#[inline]
+	pub fn new(
+		values: &'t [ComponentValue<'a>],
+		end: Option<Token<'a>>,
+		end_offset: usize,
+	) -> Self {
+		Self {
+			values,
+			index: 0,
+			end,
+			end_offset,
+		}
+	}
+
+	/// Over arguments, ends with `)`
+	#[inline]
+	pub fn arguments(function: &'t Function<'a>) -> Self {
+		Self::new(
+			&function.arguments,
+			Some(Token::BracketRoundClose),
+			function.span.end - 1,
+		)
+	}
+
+	/// Over values inside of brackets, ends with closing one
+	#[inline]
+	pub fn block(block: &'t SimpleBlock<'a>) -> Self {
+		Self::new(
+			&block.values,
+			Some(block.bracket.tokens().1),
+			block.span.end - 1,
+		)
+	}
+
+	/// [`None`] once every value is consumed
+	#[inline(always)]
+	pub fn current(&self) -> Option<&'t ComponentValue<'a>> {
+		self.values.get(self.index)
+	}
+
+	/// Token current value starts with, `end` once every value is consumed, for errors
+	#[inline]
+	pub fn token(&self) -> Option<Token<'a>> {
+		self.current().map(first_token).or(self.end)
+	}
+
+	/// Token right after the last value, e.g. `{` of a prelude or `)` of arguments
+	#[inline(always)]
+	pub fn end(&self) -> Option<Token<'a>> {
+		self.end
+	}
+
+	/// Where current value, or `end`, starts
+	#[inline]
+	pub fn offset(&self) -> usize {
+		self.current()
+			.map_or(self.end_offset, |value| value.span().start)
+	}
+
+	/// Moves to the next value, skipping whitespace
+	#[inline]
+	pub fn next(&mut self) -> Option<&'t ComponentValue<'a>> {
+		match self.next_with_whitespace() {
+			Some(value) if value.is_whitespace() => self.next_with_whitespace(),
+			value => value,
+		}
+	}
+
+	#[inline]
+	pub fn next_with_whitespace(&mut self) -> Option<&'t ComponentValue<'a>> {
+		self.index = (self.index + 1).min(self.values.len());
+		self.current()
+	}
+
+	/// Next value, skipping whitespace, [`None`] for the last one
+	#[inline]
+	pub fn peek_next(&self) -> Option<&'t ComponentValue<'a>> {
+		let mut rest = self.values.iter().skip(self.index + 1);
+
+		match rest.next() {
+			Some(value) if value.is_whitespace() => rest.next(),
+			value => value,
+		}
+	}
+
+	/// Previous value, whitespace included, [`None`] for the first one
+	#[inline]
+	pub fn prev(&self) -> Option<&'t ComponentValue<'a>> {
+		let prev = self.index.checked_sub(1)?;
+
+		self.values.get(prev)
+	}
+
+	/// Previous value, passing over ones `skip` returns `true` for
+	#[inline]
+	pub fn prev_skipping(
+		&self,
+		skip: impl Fn(&ComponentValue<'a>) -> bool,
+	) -> Option<&'t ComponentValue<'a>> {
+		self.values[..self.index.min(self.values.len())]
+			.iter()
+			.rev()
+			.find(|value| !skip(value))
+	}
+
+	/// Next value, passing over ones `skip` returns `true` for
+	#[inline]
+	pub fn next_skipping(
+		&self,
+		skip: impl Fn(&ComponentValue<'a>) -> bool,
+	) -> Option<&'t ComponentValue<'a>> {
+		self.values
+			.iter()
+			.skip(self.index + 1)
+			.find(|value| !skip(value))
+	}
+
+	/// Moves past current value, if it is whitespace
+	#[inline]
+	pub fn skip_whitespace(&mut self) {
+		if self.current().is_some_and(ComponentValue::is_whitespace) {
+			self.next_with_whitespace();
+		}
+	}
+}
+
+/// Function starts with [`Token::Function`], without `(` in this case, and block with its bracket
+#[inline]
+fn first_token<'a>(value: &ComponentValue<'a>) -> Token<'a> {
+	match value {
+		ComponentValue::Token { token, .. } => *token,
+		ComponentValue::Function(function) => Token::Function(function.name),
+		ComponentValue::Block(block) => block.bracket.tokens().0,
+	}
+}
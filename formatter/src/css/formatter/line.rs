@@ -1,37 +1,67 @@
-use crate::css::formatter::line::splitter::{Split, Splitter};
+use crate::css::formatter::line::splitter::Split;
+use crate::utils::display_width;
+use crate::FormatOptions;
 
 use super::Helper;
 
-#[derive(Clone, Eq, Ord, PartialEq, PartialOrd, Debug, Default)]
+/// Bytes to be written, ordered by them alone
+#[derive(Clone, Debug, Default)]
 pub struct Line {
 	buf: Vec<u8>,
+	/// Sorted by position, see [`Sink::mark`]
+	marks: Vec<Mark>,
 }
 
-/// Hardcoded max length of single line
-/// If `Line > MAX_LENGTH`, split into multiple lines
-pub const MAX_LENGTH: usize = 80;
+/// Byte of a [`Line`] written from `source` offset of the input
+#[derive(Clone, Copy, Debug)]
+struct Mark {
+	at: usize,
+	source: usize,
+}
+
+/// Destination of formatted bytes, that keeps track of where they came from
+pub trait Sink: std::io::Write {
+	/// Next byte written maps to `source` offset of the input
+	fn mark(&mut self, source: usize);
+}
 
 impl Line {
+	/// If `indent + Line > print_width`, split into multiple lines
 	#[inline]
 	pub fn flush_self_with_indent(
 		&mut self,
 		indent: u8,
-		output: &mut impl std::io::Write,
+		options: &FormatOptions,
+		splitter: &mut Splitter,
+		output: &mut impl Sink,
 	) -> std::io::Result<u32> {
 		debug_assert!(!self.is_empty());
 
-		let wrote = if self.len() > MAX_LENGTH {
+		let max_length = options
+			.print_width
+			.saturating_sub(options.indent_columns(indent));
+
+		let mut marks = self.marks.iter().copied().peekable();
+
+		let wrote = if display_width(self) > max_length {
 			let mut count = 0;
 
-			for Split { offset, bytes } in Splitter::split(self) {
-				output.finish_line_with_indent(bytes, indent + offset)?;
+			for Split { offset, bytes } in splitter.split(&self.buf, max_length) {
+				// Splits are slices of the line
+				let start = bytes.as_ptr() as usize - self.buf.as_ptr() as usize;
+
+				output.write_indent(indent + offset, options)?;
+				write_marked(output, bytes, start, &mut marks)?;
+				output.write_newline()?;
 
 				count += 1;
 			}
 
 			count
 		} else {
-			output.finish_line_with_indent(self, indent)?;
+			output.write_indent(indent, options)?;
+			write_marked(output, &self.buf, 0, &mut marks)?;
+			output.write_newline()?;
 
 			1
 		};
@@ -41,14 +71,98 @@ impl Line {
 		Ok(wrote)
 	}
 
+	#[inline]
+	pub fn clear(&mut self) {
+		self.buf.clear();
+		self.marks.clear();
+	}
+
+	#[inline]
+	pub fn truncate(&mut self, len: usize) {
+		self.buf.truncate(len);
+
+		let kept = self.marks.partition_point(|mark| mark.at < len);
+		self.marks.truncate(kept);
+	}
+
+	/// Writes whole line into `output` along with its marks
+	#[inline]
+	pub fn write_into(&self, output: &mut impl Sink) -> std::io::Result<()> {
+		write_marked(output, &self.buf, 0, &mut self.marks.iter().copied().peekable())
+	}
+
 	#[inline]
 	pub fn new() -> Self {
 		Self {
-			buf: Vec::with_capacity(MAX_LENGTH),
+			buf: Vec::with_capacity(FormatOptions::DEFAULT.print_width),
+			marks: Vec::new(),
 		}
 	}
 }
 
+/// Writes `bytes` starting at `start` of a line, with marks that fall before their end
+#[inline]
+fn write_marked(
+	output: &mut impl Sink,
+	bytes: &[u8],
+	start: usize,
+	marks: &mut std::iter::Peekable<impl Iterator<Item = Mark>>,
+) -> std::io::Result<()> {
+	let mut written = 0;
+
+	// Marks of bytes dropped between splits go to the start of the next one
+	while let Some(Mark { at, source }) = marks.next_if(|mark| mark.at < start + bytes.len()) {
+		let at = at.saturating_sub(start).max(written);
+		// Marks made right before a space belong to what follows it
+		let at = at + bytes[at..].iter().take_while(|&&byte| byte == b' ').count();
+
+		output.write_all(&bytes[written..at])?;
+		output.mark(source);
+
+		written = at;
+	}
+
+	output.write_all(&bytes[written..])
+}
+
+impl Sink for Line {
+	#[inline]
+	fn mark(&mut self, source: usize) {
+		let at = self.buf.len();
+
+		// Nothing was written for the previous mark
+		if let Some(last) = self.marks.last_mut().filter(|last| last.at == at) {
+			last.source = source;
+			return;
+		}
+
+		self.marks.push(Mark { at, source });
+	}
+}
+
+impl PartialEq for Line {
+	#[inline]
+	fn eq(&self, other: &Self) -> bool {
+		self.buf == other.buf
+	}
+}
+
+impl Eq for Line {}
+
+impl PartialOrd for Line {
+	#[inline]
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Line {
+	#[inline]
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		self.buf.cmp(&other.buf)
+	}
+}
+
 impl std::ops::Deref for Line {
 	type Target = Vec<u8>;
 
@@ -78,3 +192,5 @@ impl std::io::Write for Line {
 }
 
 mod splitter;
+
+pub use splitter::Splitter;
@@ -1,144 +1,158 @@
 use split_tree::{Kind, SplitNode, SplitTree};
 
-use crate::css::formatter::line::MAX_LENGTH;
-
 use consts::ASCII;
 
+use crate::utils::display_width;
+
 #[derive(Debug, Clone)]
 pub(crate) struct Split<T> {
 	pub offset: u8,
 	pub bytes: T,
 }
 
-pub struct Splitter;
+/// Owns allocations reused by every split
+#[derive(Default)]
+pub struct Splitter {
+	tree: SplitTree,
+}
 
 impl Splitter {
-	/// Split any ASCII byte array into smaller chunks.
+	/// Split any UTF-8 byte array into smaller chunks, measured in display width.
+	/// Splits only ever happen on ASCII bytes
 	///
 	/// Kind of expensive
 	#[inline]
-	pub fn split(buf: &[u8]) -> impl Iterator<Item = Split<&[u8]>> {
-		if buf.starts_with(&[ASCII::SLASH, ASCII::ASTERISK]) {
-			panic!("Comments are not allowed here");
-		}
-
+	pub fn split<'b>(
+		&'b mut self,
+		buf: &'b [u8],
+		max_length: usize,
+	) -> impl Iterator<Item = Split<&'b [u8]>> + 'b {
 		#[cfg(debug_assertions)]
 		{
-			eprintln!("Split on:\n`{}`", unsafe {
-				std::str::from_utf8_unchecked(buf)
-			});
+			eprintln!("Split on:\n`{}`", String::from_utf8_lossy(buf));
 		}
 
-		let splits = SplitTree::from_byte_array(buf);
+		let splits = &mut self.tree;
 
-		if splits.is_empty() {
-			// TODO: Implement early return type ... enum?
-			panic!("No splits found");
+		// Comments are never split
+		if buf.starts_with(&[ASCII::SLASH, ASCII::ASTERISK]) {
+			splits.whole(buf);
+		} else {
+			splits.build(buf);
+			Self::flatten(buf, splits, max_length);
 		}
 
-		Self::flatten(buf, splits);
-
-		splits.into_iter().map_windows(move |[a, b]| {
+		splits.iter().map_windows(move |[a, b]| {
 			let from = (a.at as isize + a.offset_from as isize) as usize;
 			let to = (b.at as isize + b.offset_to as isize) as usize;
 
 			let bytes = &buf[from..to];
 
-			if bytes.len() > MAX_LENGTH {
-				eprintln!("Could not break {} len string", bytes.len())
+			if display_width(bytes) > max_length {
+				eprintln!("Could not break {} len string", display_width(bytes))
 			}
 
 			Split {
 				offset: a.offset_indent,
-				bytes: &buf[from..to],
+				bytes,
 			}
 		})
 	}
 
 	#[inline]
-	fn flatten(buf: &[u8], splits: SplitTree) {
-		Self::flatten_groups(buf, splits);
-		Self::remove_soft_splits(buf, splits);
+	fn flatten(buf: &[u8], splits: &mut SplitTree, max_length: usize) {
+		Self::flatten_groups(buf, splits, max_length);
+		Self::remove_soft_splits(buf, splits, max_length);
 		Self::remove_conditional(buf, splits);
 	}
 
 	#[inline]
-	fn flatten_groups(buf: &[u8], splits: SplitTree) {
-		for node in splits
-			.iter_mut()
-			.filter(|s| matches!(s.kind, Kind::ClosedGroupStart))
-		{
-			let group_len = unsafe { node.calc_group_len() };
+	fn flatten_groups(buf: &[u8], splits: &mut SplitTree, max_length: usize) {
+		// Indexing, since collapsing a group removes nodes ahead of us
+		for id in 0..splits.len() as u32 {
+			let node = splits[id as usize];
 
-			if group_len > MAX_LENGTH {
+			if !matches!(node.kind, Kind::ClosedGroupStart) {
 				continue;
 			}
 
-			let next = splits[node.metadata as usize + 1];
-			let prev_at = node.prev().map(|s| s.at as usize).unwrap_or(0);
-			let mut next_at = next.at as usize;
+			let group_len = splits.calc_group_width(id, buf);
 
-			if next.is_plug() || matches!(next.kind, Kind::Conditional) {
-				next_at = buf.len();
+			if group_len > max_length {
+				continue;
 			}
 
-			if next_at - prev_at > MAX_LENGTH {
+			let prev_at = splits.prev(id).map(|s| s.at as usize).unwrap_or(0);
+
+			// Unclosed group runs until the end
+			let next_at = match splits.get(node.metadata as usize + 1) {
+				Some(next) if !next.is_plug() && !matches!(next.kind, Kind::Conditional) => {
+					next.at as usize
+				}
+				_ => buf.len(),
+			};
+
+			if buf.get(prev_at..next_at).map_or(0, display_width) > max_length {
 				continue;
 			}
 
-			unsafe { node.collapse_closed_group() };
+			splits.collapse_closed_group(id);
 		}
 	}
 
 	#[inline]
-	fn remove_soft_splits(buf: &[u8], splits: SplitTree) {
-		macro_rules! iter_filter {
-			($kind: pat) => {
-				splits
-					.iter_mut()
-					.filter(|s| !s.is_plug() && matches!(s.kind, $kind))
-			};
-		}
-
+	fn remove_soft_splits(buf: &[u8], splits: &mut SplitTree, max_length: usize) {
 		#[inline]
-		fn inner<'a>(buf: &[u8], soft_splits: impl Iterator<Item = &'a mut SplitNode>) {
-			for split in soft_splits {
-				let prev = split.prev().map(|s| s.at as usize).unwrap_or(0);
-				let next = split.next().map(|s| s.at as usize).unwrap_or(buf.len());
+		fn inner(
+			buf: &[u8],
+			max_length: usize,
+			splits: &mut SplitTree,
+			is_soft: impl Fn(&SplitNode) -> bool,
+		) {
+			for id in 0..splits.len() as u32 {
+				let split = &splits[id as usize];
+
+				if split.is_plug() || !is_soft(split) {
+					continue;
+				}
 
-				if next - prev < MAX_LENGTH {
-					split.remove();
+				let prev = splits.prev(id).map(|s| s.at as usize).unwrap_or(0);
+				let next = splits.next(id).map(|s| s.at as usize).unwrap_or(buf.len());
+
+				if display_width(&buf[prev..next]) < max_length {
+					splits[id as usize].remove();
 				}
 			}
 		}
 
 		// FIXME: Do a proper algorithm
 		if !buf.starts_with(b"grid-template-areas") {
-			inner(buf, iter_filter!(Kind::Whitespace));
+			inner(buf, max_length, splits, |s| matches!(s.kind, Kind::Whitespace));
 		}
-		inner(buf, iter_filter!(Kind::Operator));
+		inner(buf, max_length, splits, |s| matches!(s.kind, Kind::Operator));
 	}
 
 	#[inline]
-	fn remove_conditional(_: &[u8], splits: SplitTree) {
-		let Some(penultimate) = splits.last().and_then(|s| s.prev()) else {
+	fn remove_conditional(_: &[u8], splits: &mut SplitTree) {
+		let Some(last) = splits.last() else {
 			return;
 		};
 
-		if !matches!(penultimate.kind, Kind::Conditional) {
-			return;
-		}
-
-		let Some(prev) = penultimate.prev() else {
-			penultimate.remove();
+		let Some(&penultimate) = splits.prev(last.id) else {
 			return;
 		};
 
-		if prev.offset_indent != penultimate.offset_indent {
+		if !matches!(penultimate.kind, Kind::Conditional) {
 			return;
 		}
 
-		penultimate.remove();
+		if let Some(prev) = splits.prev(penultimate.id) {
+			if prev.offset_indent != penultimate.offset_indent {
+				return;
+			}
+		}
+
+		splits[penultimate.id as usize].remove();
 	}
 }
 
@@ -1,7 +1,9 @@
-use std::{assert_matches::debug_assert_matches, fmt::Write, hint::unreachable_unchecked};
+use std::fmt::Write;
 
 use consts::ASCII;
 
+use crate::utils::display_width;
+
 #[derive(Clone, Copy, Debug)]
 #[non_exhaustive]
 pub enum Kind {
@@ -39,21 +41,16 @@ pub struct SplitNode {
 	pub offset_to: i8,
 }
 
-#[derive(Clone, Copy)]
-pub struct SplitTree;
-
-#[thread_local]
-static mut ARENA: Vec<SplitNode> = Vec::new();
-
-#[inline]
-fn unsafe_get_arena() -> &'static mut Vec<SplitNode> {
-	unsafe { &mut *std::ptr::addr_of_mut!(ARENA) }
+/// Nodes are addressed by their [`SplitNode::id`], allocation is reused between builds
+#[derive(Default)]
+pub struct SplitTree {
+	nodes: Vec<SplitNode>,
 }
 
 impl SplitTree {
 	#[inline]
-	pub fn from_byte_array(buf: &[u8]) -> Self {
-		let splits = unsafe_get_arena();
+	pub fn build(&mut self, buf: &[u8]) {
+		let splits = &mut self.nodes;
 		let mut split;
 		let mut window;
 		let mut offset = 0;
@@ -72,6 +69,7 @@ impl SplitTree {
 
 		 	($($key: ident: $value: expr$(,)?)*) => {
 		 		SplitNode {
+		 			id: splits.len() as u32,
 		 			at: at as u32,
 
 		 			offset_indent: offset,
@@ -94,18 +92,25 @@ impl SplitTree {
 			};
 
 			match window {
-				// Matches `content: "..."` or `[href^="#"]`
+				// Matches `content: "..."` or `[href^='#']`
 				//                   ^                 ^
-				[ASCII::DOUBLE_QUOTE, ..] => {
+				[quote @ (ASCII::DOUBLE_QUOTE | ASCII::SINGLE_QUOTE), ..] => {
+					let mut escaped = false;
+
 					loop {
 						match window {
-							// Skip next escaped quote
-							[ASCII::BACKSLASH, ASCII::DOUBLE_QUOTE] => {}
-							[.., ASCII::DOUBLE_QUOTE] => break, // at as u32 - split.at + 1,
-							_ => {}
+							[_, closing] if closing == quote && !escaped => break,
+							// Skip next escaped char, `\\` included
+							[_, ASCII::BACKSLASH] => escaped = !escaped,
+							_ => escaped = false,
 						};
 
-						window = unsafe { windows.next().unwrap_unchecked().1 };
+						// Unterminated string, nothing to split
+						let Some((_, next)) = windows.next() else {
+							break;
+						};
+
+						window = next;
 					}
 
 					windows.next();
@@ -113,10 +118,52 @@ impl SplitTree {
 					continue;
 				}
 
+				// Matches `0 /* top */ auto`, comment is never split
+				//            ^
+				[ASCII::SLASH, ASCII::ASTERISK] => {
+					let Some(len) = buf[at + 2..].windows(2).position(|w| w == b"*/") else {
+						break;
+					};
+
+					// Step over the rest of the comment, `*/` included
+					windows.nth(len + 2);
+
+					continue;
+				}
+
+				// Matches `color: red; /* brand */`, trailing comment stays with its declaration
+				//                    ^
+				[ASCII::SEMICOLON, ASCII::SPACE] => {
+					windows.next();
+
+					continue;
+				}
+
+				// Matches `.\31 0col` or `.sm\:flex`, escaped code point is never a split
+				//           ^^^^          ^^
+				[ASCII::BACKSLASH, escaped] => {
+					let mut len = 1;
+
+					if escaped.is_ascii_hexdigit() {
+						len += buf[at + 2..].iter().take(5).take_while(|b| b.is_ascii_hexdigit()).count();
+
+						// Single whitespace after hex digits belongs to the escape
+						len += matches!(
+							buf.get(at + 1 + len),
+							Some(&(ASCII::SPACE | ASCII::TAB | ASCII::LF | ASCII::CR))
+						) as usize;
+					}
+
+					// Step over escaped bytes
+					windows.nth(len - 1);
+
+					continue;
+				}
+
 				// Matches `rgb(...)` or `[class="..."]`
 				//             ^          ^
 				[ASCII::PAREN_OPEN | ASCII::SQUARED_OPEN, ..] => {
-					offset += 1;
+					offset = offset.saturating_add(1);
 
 					split = node!(Kind::ClosedGroupStart, 1, 1);
 				}
@@ -124,7 +171,7 @@ impl SplitTree {
 				// Matches `rgb(...)` or `[class="..."]`
 				//                 ^                  ^
 				[ASCII::PAREN_CLOSE | ASCII::SQUARED_CLOSE, ..] => {
-					offset -= 1;
+					offset = offset.saturating_sub(1);
 
 					split = node!(Kind::ClosedGroupEnd);
 				}
@@ -132,7 +179,7 @@ impl SplitTree {
 				// Matches `background: ...`, but not `div:hover` or `:has(...`
 				//                    ^                   x           x
 				[ASCII::COLON, ASCII::SPACE] => {
-					offset += 1;
+					offset = offset.saturating_add(1);
 					open_group = true;
 
 					split = node!(Kind::OpenGroupStart, 2, 1);
@@ -154,14 +201,15 @@ impl SplitTree {
 				//                 ^
 				[ASCII::SPACE, ASCII::GT | ASCII::PLUS | ASCII::TILDE] => {
 					// Skip space
-					at = unsafe { windows.next().unwrap_unchecked().0 };
-					offset += 1;
+					windows.next();
+					at += 1;
+					offset = offset.saturating_add(1);
 
 					split = node!(Kind::Operator, 0, -1);
 
 					// Skip delim
 					windows.next();
-					offset -= 1;
+					offset = offset.saturating_sub(1);
 				}
 
 				[ASCII::SPACE, ASCII::CURLY_OPEN] => {
@@ -171,11 +219,11 @@ impl SplitTree {
 				// Matches `.class a`, but not `.class > a` (above rule) or `red, blue`
 				//                ^                   x x                        x
 				[ASCII::SPACE, ..] => {
-					offset += !open_group as u8;
+					offset = offset.saturating_add(!open_group as u8);
 
 					split = node!(Kind::Whitespace, 1, 0);
 
-					offset -= !open_group as u8;
+					offset = offset.saturating_sub(!open_group as u8);
 				}
 
 				_ => continue,
@@ -185,9 +233,12 @@ impl SplitTree {
 		}
 
 		// Check last char
-		at = buf.len() - 1;
-		if matches!(buf[at], ASCII::PAREN_CLOSE | ASCII::SQUARED_CLOSE) {
-			splits.push(node!(Kind::ClosedGroupEnd));
+		if let Some(&last) = buf.last() {
+			at = buf.len() - 1;
+
+			if matches!(last, ASCII::PAREN_CLOSE | ASCII::SQUARED_CLOSE) {
+				splits.push(node!(Kind::ClosedGroupEnd));
+			}
 		}
 
 		at = buf.len();
@@ -200,7 +251,7 @@ impl SplitTree {
 			out.push_str("Found splits for:\n");
 
 			out.push('`');
-			out.push_str(unsafe { std::str::from_utf8_unchecked(buf) });
+			out.push_str(&String::from_utf8_lossy(buf));
 			out.push('`');
 			out.push('\n');
 
@@ -208,7 +259,7 @@ impl SplitTree {
 
 			let mut prev = 0;
 			// NOTE: Skip 1st and last elements
-			for node in splits.iter().skip(1).take(unsafe_get_arena().len() - 2) {
+			for node in splits.iter().skip(1).take(splits.len() - 2) {
 				let at = node.at;
 
 				for _ in prev..at {
@@ -223,11 +274,27 @@ impl SplitTree {
 			eprintln!("{out}\n");
 		}
 
-		Self.update()
+		self.update();
 	}
 
+	/// Tree without any splits, `buf` stays as is
 	#[inline]
-	fn update(self) -> Self {
+	pub fn whole(&mut self, buf: &[u8]) {
+		self.nodes.clear();
+		self.nodes.push(SplitNode {
+			kind: Kind::Begin,
+			..Default::default()
+		});
+		self.nodes.push(SplitNode {
+			id: 1,
+			at: buf.len() as u32,
+			kind: Kind::End,
+			..Default::default()
+		});
+	}
+
+	#[inline]
+	fn update(&mut self) {
 		#[inline]
 		fn inner<'a>(last: usize, iter: &mut impl Iterator<Item = &'a mut SplitNode>) -> u32 {
 			while let Some(node) = iter.next() {
@@ -242,111 +309,87 @@ impl SplitTree {
 			last as u32
 		}
 
-		inner(self.len() - 1, &mut self.iter_mut());
+		let last = self.nodes.len() - 1;
+		let mut iter = self.iter_mut().peekable();
 
-		self
-	}
-
-	#[inline]
-	pub fn into_iter(self) -> impl Iterator<Item = SplitNode> {
-		unsafe_get_arena()
-			.iter()
-			.copied()
-			.filter(|s| !matches!(s.kind, Kind::None))
+		// Stray closing brackets end `inner` early
+		while iter.peek().is_some() {
+			inner(last, &mut iter);
+		}
 	}
 
 	#[inline]
 	pub fn iter(&self) -> impl DoubleEndedIterator<Item = &SplitNode> {
-		unsafe_get_arena()
-			.iter()
-			.filter(|s| !matches!(s.kind, Kind::None))
+		self.nodes.iter().filter(|s| !matches!(s.kind, Kind::None))
 	}
 
 	#[inline]
-	pub fn iter_mut(&self) -> impl DoubleEndedIterator<Item = &mut SplitNode> {
-		unsafe_get_arena()
+	pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut SplitNode> {
+		self
+			.nodes
 			.iter_mut()
 			.filter(|s| !matches!(s.kind, Kind::None))
 	}
-}
-
-impl SplitNode {
-	#[inline]
-	pub fn remove(&mut self) {
-		self.kind = Kind::None;
-	}
 
+	/// Removes a closed group starting at `id`, with everything inside it
 	#[inline]
-	pub unsafe fn collapse_closed_group(&mut self) {
-		debug_assert_matches!(self.kind, Kind::ClosedGroupStart);
-
-		let Kind::ClosedGroupStart = self.kind else {
-			unsafe { unreachable_unchecked() }
+	pub fn collapse_closed_group(&mut self, id: u32) {
+		let SplitNode {
+			kind: Kind::ClosedGroupStart,
+			metadata,
+			..
+		} = self[id as usize]
+		else {
+			return;
 		};
 
-		let vec = unsafe_get_arena();
-
-		for i in self.id..=self.metadata {
-			vec[i as usize].kind = Kind::None;
+		for node in &mut self[id as usize..=metadata as usize] {
+			node.kind = Kind::None;
 		}
 	}
 
 	#[inline]
-	pub fn next(&self) -> Option<&mut SplitNode> {
-		unsafe_get_arena()
-			.iter_mut()
-			.skip(self.id as usize + 1)
+	pub fn next(&self, id: u32) -> Option<&SplitNode> {
+		self
+			.nodes
+			.iter()
+			.skip(id as usize + 1)
 			.find(|s| !s.is_plug())
 	}
 
 	#[inline]
-	pub fn next_solid(&self) -> Option<&mut SplitNode> {
-		unsafe_get_arena()
-			.iter_mut()
-			.skip(self.id as usize + 1)
-			.find(|s| s.is_solid())
-	}
-
-	#[inline]
-	pub fn prev(&self) -> Option<&mut SplitNode> {
-		unsafe_get_arena()
-			.iter_mut()
-			.take(self.id as usize)
+	pub fn prev(&self, id: u32) -> Option<&SplitNode> {
+		self
+			.nodes
+			.iter()
+			.take(id as usize)
 			.rev()
 			.find(|s| !s.is_plug())
 	}
 
+	/// Display width of content of a closed group starting at `id`, excluding brackets
 	#[inline]
-	pub fn prev_solid(&self) -> Option<&mut SplitNode> {
-		unsafe_get_arena()
-			.iter_mut()
-			.take(self.id as usize)
-			.rev()
-			.find(|s| s.is_solid())
-	}
-
-	#[inline]
-	pub unsafe fn calc_group_len(&self) -> usize {
-		debug_assert_matches!(self.kind, Kind::ClosedGroupStart);
-
-		let Kind::ClosedGroupStart = self.kind else {
-			unsafe { unreachable_unchecked() }
+	pub fn calc_group_width(&self, id: u32, buf: &[u8]) -> usize {
+		let SplitNode {
+			kind: Kind::ClosedGroupStart,
+			at,
+			metadata,
+			..
+		} = self[id as usize]
+		else {
+			return 0;
 		};
 
-		(unsafe_get_arena()[self.metadata as usize].at - self.at - 1) as usize
-	}
+		let end = self[metadata as usize].at as usize;
 
-	#[inline]
-	pub fn is_solid(&self) -> bool {
-		matches!(
-			self.kind,
-			Kind::ClosedGroupStart | Kind::OpenGroupStart | Kind::Point
-		)
+		buf.get(at as usize + 1..end).map_or(0, display_width)
 	}
+}
 
+impl SplitNode {
 	#[inline]
-	pub fn is_soft(&self) -> bool {
-		matches!(self.kind, Kind::Operator | Kind::Whitespace)
+	pub fn remove(&mut self) {
+		self.kind = Kind::None;
 	}
 
 	#[inline]
@@ -375,7 +418,7 @@ impl std::fmt::Debug for SplitTree {
 			f.write_char('\n')?;
 
 			let offset = match node.kind {
-				Kind::OpenGroupStart | Kind::ClosedGroupStart => node.offset_indent - 1,
+				Kind::OpenGroupStart | Kind::ClosedGroupStart => node.offset_indent.saturating_sub(1),
 				_ => node.offset_indent,
 			};
 
@@ -433,14 +476,14 @@ impl std::ops::Deref for SplitTree {
 
 	#[inline(always)]
 	fn deref(&self) -> &Self::Target {
-		unsafe_get_arena()
+		&self.nodes
 	}
 }
 
 impl std::ops::DerefMut for SplitTree {
 	#[inline(always)]
 	fn deref_mut(&mut self) -> &mut Self::Target {
-		unsafe_get_arena()
+		&mut self.nodes
 	}
 }
 
@@ -448,7 +491,7 @@ impl Default for SplitNode {
 	#[inline]
 	fn default() -> Self {
 		Self {
-			id: unsafe_get_arena().len() as u32,
+			id: 0,
 
 			at: 0,
 			kind: Kind::None,
@@ -2,12 +2,17 @@ use hel_colored::Colored;
 
 use super::{Split, Splitter};
 
+const MAX_LENGTH: usize = crate::FormatOptions::DEFAULT.print_width;
+
 #[test]
 fn small() {
 	fn helper(subject: &str) {
 		use std::str::from_utf8_unchecked as to_str;
 
-		let Split { offset, bytes } = Splitter::split(subject.as_bytes())
+		let mut splitter = Splitter::default();
+
+		let Split { offset, bytes } = splitter
+			.split(subject.as_bytes(), MAX_LENGTH)
 			.next()
 			.unwrap_or_else(|| panic!("{}", "empty result".red().to_string()));
 
@@ -245,9 +250,62 @@ fn string_split() {
 			(1, "Ubuntu,"),
 			(1, "sans-serif;")
 		),
+
+		helper!(
+			r#"content: "an escaped backslash \\", "followed by another string that is long enough to split";"#,
+			(0, "content:"),
+			(1, r#""an escaped backslash \\","#),
+			(1, r#""followed by another string that is long enough to split";"#),
+		),
+
+		helper!(
+			r".very-long-selector-name .\31 0col .another-long-selector-name .sm\:flex\(parens\) {",
+			(0, r".very-long-selector-name .\31 0col .another-long-selector-name"),
+			(1, r".sm\:flex\(parens\)"),
+			(0, "{"),
+		),
+
+		helper!(
+			r#"quotes: '"' '"', "a double quoted string that is long enough to split", 'a, b';"#,
+			(0, "quotes:"),
+			(1, r#"'"' '"',"#),
+			(1, r#""a double quoted string that is long enough to split","#),
+			(1, "'a, b';"),
+		),
+
+		helper!(
+			"transition: opacity 0.3s /* fade, then slide */ ease-in, transform 0.3s ease-out; /* motion, x */",
+			(0, "transition:"),
+			(1, "opacity 0.3s /* fade, then slide */ ease-in,"),
+			(1, "transform 0.3s ease-out; /* motion, x */"),
+		),
 	];
 
+	// Reused, as it would be by the formatter
+	let mut splitter = Splitter::default();
+
 	for test in cases {
-		test.equal_or_fail(Splitter::split(test.buf()))
+		test.equal_or_fail(splitter.split(test.buf(), MAX_LENGTH))
+	}
+}
+
+#[test]
+fn malformed() {
+	let cases = [
+		r#"content: "unterminated string that is long enough to be split into multiple lines, hopefully"#,
+		"stray ) closing bracket ) that is long enough to be split into multiple lines ( hopefully (",
+		"ends with an operator, that is long enough to be split into multiple lines, hopefully >",
+		"/* comments are never split, even if they are long enough to be split into multiple lines */",
+	];
+
+	let mut splitter = Splitter::default();
+
+	for case in cases {
+		let joined: Vec<u8> = splitter
+			.split(case.as_bytes(), MAX_LENGTH)
+			.flat_map(|Split { bytes, .. }| bytes.iter().copied())
+			.collect();
+
+		assert!(!joined.is_empty(), "{}", "empty result".red().to_string());
 	}
 }
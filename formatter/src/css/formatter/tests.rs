@@ -0,0 +1,509 @@
+use std::path::Path;
+
+use crate::{format_with_session, EndOfLine, FormatOptions, IndentStyle, Session};
+
+const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/css");
+
+const MINIFY: FormatOptions = FormatOptions {
+	minify: true,
+	..FormatOptions::DEFAULT
+};
+
+fn format(input: &str, options: FormatOptions) -> String {
+	let mut output = Vec::new();
+
+	if let Err(err) = format_with_session(&mut Session::default(), input, &mut output, options) {
+		panic!("{input:?} failed to format: {err:?}");
+	}
+
+	String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn options() {
+	const NESTED: &str = "@media x{a{font-family:Helvetica, Arial}}";
+
+	let spaces = FormatOptions {
+		indent_style: IndentStyle::Spaces,
+		indent_width: 4,
+		..FormatOptions::DEFAULT
+	};
+	let narrow = FormatOptions {
+		print_width: 30,
+		..FormatOptions::DEFAULT
+	};
+
+	let cases = [
+		(
+			FormatOptions::DEFAULT,
+			"@media x {\n\ta {\n\t\tfont-family: Helvetica, Arial;\n\t}\n}\n",
+		),
+		(
+			spaces,
+			"@media x {\n    a {\n        font-family: Helvetica, Arial;\n    }\n}\n",
+		),
+		// Tabs are left out of the width, unless `tab_width` is set
+		(
+			narrow,
+			"@media x {\n\ta {\n\t\tfont-family: Helvetica, Arial;\n\t}\n}\n",
+		),
+		(
+			FormatOptions {
+				tab_width: 4,
+				..narrow
+			},
+			"@media x {\n\ta {\n\t\tfont-family:\n\t\t\tHelvetica,\n\t\t\tArial;\n\t}\n}\n",
+		),
+		(
+			FormatOptions {
+				indent_width: 2,
+				print_width: 30,
+				..spaces
+			},
+			"@media x {\n  a {\n    font-family:\n      Helvetica,\n      Arial;\n  }\n}\n",
+		),
+		(
+			FormatOptions {
+				end_of_line: EndOfLine::CrLf,
+				..FormatOptions::DEFAULT
+			},
+			"@media x {\r\n\ta {\r\n\t\tfont-family: Helvetica, Arial;\r\n\t}\r\n}\r\n",
+		),
+		(
+			FormatOptions {
+				final_newline: false,
+				..FormatOptions::DEFAULT
+			},
+			"@media x {\n\ta {\n\t\tfont-family: Helvetica, Arial;\n\t}\n}",
+		),
+		(
+			FormatOptions {
+				end_of_line: EndOfLine::CrLf,
+				final_newline: false,
+				..FormatOptions::DEFAULT
+			},
+			"@media x {\r\n\ta {\r\n\t\tfont-family: Helvetica, Arial;\r\n\t}\r\n}",
+		),
+	];
+
+	for (options, expected) in cases {
+		assert_eq!(format(NESTED, options), expected, "{options:?}");
+	}
+}
+
+#[test]
+fn minify() {
+	let cases = [
+		("a {\n\tcolor: red;\n}\n", "a{color:red}\n"),
+		(
+			"a{margin:0 auto;color:red !important}",
+			"a{margin:0 auto;color:red!important}\n",
+		),
+		// Only a value could follow another one
+		(
+			"a{background:url(a.png) no-repeat, var(--x, 1px) 0 0;font:12px / 1.5 a}",
+			"a{background:url(a.png)no-repeat,var(--x,1px)0 0;font:12px/1.5 a}\n",
+		),
+		// Spaces of `+` and `-` are required in `calc()`, but not in selectors
+		("a{width:calc(1px + 2px * 3 - 1%)}", "a{width:calc(1px + 2px*3 - 1%)}\n"),
+		("a > b + c ~ d,\n.e .f:hover {x:y}", "a>b+c~d,.e .f:hover{x:y}\n"),
+		// `;` is kept before nested rules
+		("a{color:red;&:hover{color:blue}}", "a{color:red;&:hover{color:blue}}\n"),
+		(
+			"@media (min-width: 1px) and print {\n\ta { b: c }\n}\n\n@import url(x);",
+			"@media (min-width:1px) and print{a{b:c}}@import url(x);\n",
+		),
+		("a{@font-face{src:x}}", "a{@font-face{src:x}}\n"),
+		// License comments are kept as is
+		("/*! MIT */\n/* note */\na{/* b */color:red}", "/*! MIT */a{color:red}\n"),
+	];
+
+	for (input, expected) in cases {
+		assert_eq!(format(input, MINIFY), expected, "{input:?}");
+	}
+
+	let options = FormatOptions {
+		final_newline: false,
+		..MINIFY
+	};
+
+	assert_eq!(format("a{b:c}\n\nd{e:f}", options), "a{b:c}d{e:f}");
+}
+
+#[test]
+fn inline_comments() {
+	let cases = [
+		(
+			"a{margin:0 /* top */ auto;width:calc(1px /* c */ + 2px)}",
+			"a {\n\twidth: calc(1px /* c */ + 2px);\n\tmargin: 0 /* top */ auto;\n}\n",
+		),
+		// Trailing comment is sorted along with its declaration
+		(
+			"a{color:red; /* brand */\nmargin:0}",
+			"a {\n\tmargin: 0;\n\n\tcolor: red; /* brand */\n}\n",
+		),
+		("a{color:red;\n/* own line */}", "a {\n\tcolor: red;\n\n\t/* own line */\n}\n"),
+		// Only declarations take a comment along
+		(
+			"a{color:red;&:hover{x:y} /* c */}",
+			"a {\n\tcolor: red;\n\n\t&:hover {\n\t\tx: y;\n\t}\n\n\t/* c */\n}\n",
+		),
+		// Whitespace around is kept, `a/**/.b` is not `a .b`
+		("a /* x */ .b, c/* y */.d{x:y}", "a /* x */ .b,\nc/* y */.d {\n\tx: y;\n}\n"),
+		("@media /* p */ print{a{b:c}}", "@media /* p */ print {\n\ta {\n\t\tb: c;\n\t}\n}\n"),
+		// Never split inside of a comment, nor before a trailing one
+		(
+			concat!(
+				"a{font-family:system-ui, /* a, b, c */ Roboto, \"Helvetica Neue\", Arial;",
+				" /* fonts */}"
+			),
+			concat!(
+				"a {\n\tfont-family:\n\t\tsystem-ui,\n\t\t/* a, b, c */ Roboto,\n",
+				"\t\t\"Helvetica Neue\",\n\t\tArial; /* fonts */\n}\n"
+			),
+		),
+	];
+
+	for (input, expected) in cases {
+		assert_eq!(format(input, FormatOptions::default()), expected, "{input:?}");
+		assert_eq!(format(expected, FormatOptions::default()), expected, "{expected:?}");
+	}
+
+	assert_eq!(
+		format("a /* x */ b{margin:0/* y */auto; /*! z */}", MINIFY),
+		"a b{margin:0 auto;/*! z */}\n"
+	);
+}
+
+#[test]
+fn sorted_comments() {
+	let input = concat!(
+		"a {\n\t/* on top */\n\tz-index: 1;\n\tcolor: red; /* brand */\n",
+		"\tposition: absolute;\n\n\t/* Colors */\n\n\t/* bg */\n\tbackground: blue;\n",
+		"\tdisplay: flex;\n}\n"
+	);
+
+	// Comments right above go along, standalone ones are left after declarations
+	let expected = concat!(
+		"a {\n\tposition: absolute;\n\t/* on top */\n\tz-index: 1;\n\n\tdisplay: flex;\n\n",
+		"\t/* bg */\n\tbackground: blue;\n\n\tcolor: red; /* brand */\n\n\t/* Colors */\n}\n"
+	);
+
+	assert_eq!(format(input, FormatOptions::default()), expected);
+	assert_eq!(format(expected, FormatOptions::default()), expected);
+
+	let options = FormatOptions {
+		sort_boundaries: true,
+		..FormatOptions::DEFAULT
+	};
+
+	// Sections are sorted on their own, a standalone comment stays on top of its one
+	let expected = concat!(
+		"a {\n\tposition: absolute;\n\t/* on top */\n\tz-index: 1;\n\n",
+		"\tcolor: red; /* brand */\n\n\t/* Colors */\n\n\tdisplay: flex;\n\n",
+		"\t/* bg */\n\tbackground: blue;\n}\n"
+	);
+
+	assert_eq!(format(input, options), expected);
+	assert_eq!(format(expected, options), expected);
+
+	assert_eq!(
+		format("a{z-index:1;color:red;\n\nwidth:0;position:static}", options),
+		"a {\n\tz-index: 1;\n\n\tcolor: red;\n\n\tposition: static;\n\n\twidth: 0;\n}\n"
+	);
+
+	let recover = FormatOptions {
+		recover: true,
+		..options
+	};
+
+	// Copied as is, the declaration still takes its comment along
+	assert_eq!(
+		format("a{/* c */\nz:1 !;\nposition:static}", recover),
+		"a {\n\tposition: static;\n\n\t/* c */\n\tz:1 !;\n}\n"
+	);
+}
+
+#[test]
+fn multiline_comments() {
+	let cases = [
+		// Re-indented relative to the line they start on
+		(
+			concat!(
+				"@media print {\n        /*\n         * Doc\n\n         *   nested\n",
+				"         */\n  a{b:c}}"
+			),
+			"@media print {\n\t/*\n\t * Doc\n\n\t *   nested\n\t */\n\ta {\n\t\tb: c;\n\t}\n}\n",
+		),
+		("a{\n  /* one\n     two */\n  b:c}", "a {\n\t/* one\n\t   two */\n\tb: c;\n}\n"),
+		// In the middle of a line, where the line ends up is not known yet
+		("a{margin:0 /* x\n    y */ auto}", "a {\n\tmargin: 0 /* x\n    y */ auto;\n}\n"),
+		// License and documentation comments are kept as is
+		("/*!\n  MIT  */\na{}", "/*!\n  MIT  */\n\na {\n}\n"),
+		(
+			"a{\n    /**  keep\n      as is */\n    b:c}",
+			"a {\n\t/**  keep\n      as is */\n\tb: c;\n}\n",
+		),
+	];
+
+	for (input, expected) in cases {
+		assert_eq!(format(input, FormatOptions::default()), expected, "{input:?}");
+		assert_eq!(format(expected, FormatOptions::default()), expected, "{expected:?}");
+	}
+
+	let spaces = FormatOptions {
+		indent_style: crate::IndentStyle::Spaces,
+		..FormatOptions::DEFAULT
+	};
+
+	assert_eq!(
+		format("a{\n\t/*\n\t * b\n\t */\n\tc:d}", spaces),
+		"a {\n  /*\n   * b\n   */\n  c: d;\n}\n"
+	);
+}
+
+#[test]
+fn strip_comments() {
+	let options = FormatOptions {
+		strip_comments: true,
+		..FormatOptions::DEFAULT
+	};
+
+	let cases = [
+		("/* a */\nb{c:d}", "b {\n\tc: d;\n}\n"),
+		("/*! MIT */\nb{c:d}", "/*! MIT */\n\nb {\n\tc: d;\n}\n"),
+		(
+			"a /* x */ {/* y */\nmargin:0 /* top */ auto; /* z */\n&:hover{} /* w */}",
+			"a {\n\tmargin: 0 auto;\n\n\t&:hover {\n\t}\n}\n",
+		),
+		// Whitespace around is still whitespace
+		("a /* x */ b, c/* y */.d {}", "a b,\nc.d {\n}\n"),
+		("a{width:calc(1px /* c */ + 2px)}", "a {\n\twidth: calc(1px + 2px);\n}\n"),
+		// Directives are kept along with input they apply to
+		(
+			"/* a */\n/* hel-fmt-ignore */\n.x   >   .y{  color:red  }\nb{c:d}",
+			"/* hel-fmt-ignore */\n.x   >   .y{  color:red  }\n\nb {\n\tc: d;\n}\n",
+		),
+		(
+			"a{b:c;/* hel-fmt-ignore */ d :  e}",
+			"a {\n\tb: c;\n\t/* hel-fmt-ignore */\n\td :  e;\n}\n",
+		),
+	];
+
+	for (input, expected) in cases {
+		assert_eq!(format(input, options), expected, "{input:?}");
+	}
+
+	assert_eq!(
+		format("/* a */ /* hel-fmt-ignore */ a  { b:c }", MINIFY),
+		"/* hel-fmt-ignore */a  { b:c }\n"
+	);
+}
+
+#[test]
+fn ignore_directives() {
+	let cases = [
+		// Next rule, or declaration, which is still sorted, and terminated
+		(
+			"/* hel-fmt-ignore */\n.x   >   .y{  color:red  }\nb{c:d}",
+			"/* hel-fmt-ignore */\n.x   >   .y{  color:red  }\n\nb {\n\tc: d;\n}\n",
+		),
+		(
+			"a{color:red;/* hel-fmt-ignore */\ngrid-template-areas:   \"a  b\"\n    \"c  d\"}",
+			"a {\n\t/* hel-fmt-ignore */\n\tgrid-template-areas:   \"a  b\"\n    \"c  d\";\n\n\
+			 \tcolor: red;\n}\n",
+		),
+		// Declarations after a rule are printed before it, nothing is left to separate from
+		(
+			"a{/* hel-fmt-ignore */ &:hover{  x:y}  b:c}",
+			"a {\n\tb: c;\n\n\t/* hel-fmt-ignore */\n\t&:hover{  x:y}\n}\n",
+		),
+		(
+			"a{&:hover{x:y}b:c}",
+			"a {\n\tb: c;\n\n\t&:hover {\n\t\tx: y;\n\t}\n}\n",
+		),
+		// Region stays in place, declarations around are sorted on either side of it
+		(
+			"b{z-index:1;color:red;\n  /* hel-fmt-off */\n  width :  1px ;\n     height:2px;\n  \
+			 /* hel-fmt-on */\n  align-items:center;display:flex}",
+			"b {\n\tz-index: 1;\n\n\tcolor: red;\n\n\t/* hel-fmt-off */\n  width :  1px ;\n     \
+			 height:2px;\n  /* hel-fmt-on */\n\n\tdisplay: flex;\n\talign-items: center;\n}\n",
+		),
+		// Up to the end of the block, without `hel-fmt-on`
+		(
+			"@media x{/*hel-fmt-off*/ a  {b:c}\nd{e:f}}g{h:i}",
+			"@media x {\n\t/*hel-fmt-off*/ a  {b:c}\nd{e:f}\n}\n\ng {\n\th: i;\n}\n",
+		),
+	];
+
+	for (input, expected) in cases {
+		let output = format(input, FormatOptions::DEFAULT);
+
+		assert_eq!(output, expected, "{input:?}");
+		assert_eq!(format(&output, FormatOptions::DEFAULT), output, "{input:?}");
+	}
+
+	// Regions are kept even when comments are left out
+	assert_eq!(
+		format("/* hel-fmt-off */\nc   {  x:y  }\n/* hel-fmt-on */\nd{e:f}", MINIFY),
+		"/* hel-fmt-off */\nc   {  x:y  }\n/* hel-fmt-on */\nd{e:f}\n"
+	);
+}
+
+#[test]
+fn recover() {
+	let options = FormatOptions {
+		recover: true,
+		..FormatOptions::DEFAULT
+	};
+
+	let cases = [
+		// Bad declaration is copied as is, still sorted
+		(
+			"a{color:red;margin 0;b:c}",
+			"a {\n\tmargin 0;\n\n\tcolor: red;\n\n\tb: c;\n}\n",
+			vec![(12, 1, 13)],
+		),
+		// Sorted ahead of valid one, which is not taken in by it
+		(
+			"b{color:red;position:absolute !}",
+			"b {\n\tposition:absolute !;\n\n\tcolor: red;\n}\n",
+			vec![(31, 1, 32)],
+		),
+		// Bad rule
+		(
+			"a{x:y}\n1{x:1}\nb{c:d}",
+			"a {\n\tx: y;\n}\n\n1{x:1}\n\nb {\n\tc: d;\n}\n",
+			vec![(7, 2, 1)],
+		),
+		// Unclosed block takes the rest of input along
+		(
+			"@media x{a{x:y}}\nb{c:d;\ne{f:g}",
+			"@media x {\n\ta {\n\t\tx: y;\n\t}\n}\n\nb{c:d;\ne{f:g}\n",
+			vec![(30, 3, 7)],
+		),
+		(
+			"a{color;b:c}\r\n\r\nd{e:f",
+			"a {\n\tcolor;\n\n\tb: c;\n}\n\nd{e:f\n",
+			vec![(2, 1, 3), (21, 3, 6)],
+		),
+	];
+
+	for (input, expected, errors) in cases {
+		let mut output = Vec::new();
+		let diagnostics = crate::format(input, &mut output, options).expect("recovers");
+
+		let positions: Vec<_> = diagnostics
+			.iter()
+			.map(|err| err.position().expect("has position"))
+			.map(|position| (position.offset, position.line, position.column))
+			.collect();
+
+		assert_eq!(String::from_utf8(output).unwrap(), expected, "{input:?}");
+		assert_eq!(positions, errors, "{input:?}");
+	}
+}
+
+#[test]
+fn minify_recover() {
+	let options = FormatOptions {
+		recover: true,
+		..MINIFY
+	};
+
+	// What is copied as is ends its line, it might not end where a token does
+	assert_eq!(
+		format("a{color:red}\n1{x:1}\ni{margin:0 ;color: red !}", options),
+		"a{color:red}1{x:1}\ni{margin:0;color: red !;\n}\n"
+	);
+	assert_eq!(
+		format("b{color:red;position:absolute !}", options),
+		"b{position:absolute !;\ncolor:red}\n"
+	);
+}
+
+#[test]
+fn minify_fixtures() {
+	fn visit(dir: &Path, count: &mut usize) {
+		for entry in std::fs::read_dir(dir).expect("fixtures directory") {
+			let path = entry.expect("readable entry").path();
+
+			if path.is_dir() {
+				visit(&path, count);
+				continue;
+			}
+
+			if path.file_name().is_none_or(|name| name != "before.css") {
+				continue;
+			}
+
+			let before = std::fs::read_to_string(&path).unwrap();
+			let after = std::fs::read_to_string(path.with_file_name("after.css")).unwrap();
+
+			let minified = format(&before, MINIFY);
+
+			// Formatting changes nothing but whitespace and comments
+			assert_eq!(minified, format(&after, MINIFY), "{}", path.display());
+			assert_eq!(minified.lines().count(), 1, "{}", path.display());
+
+			// ... so it's the same stylesheet, once formatted again
+			let formatted = format(&minified, FormatOptions::default());
+			assert_eq!(format(&formatted, MINIFY), minified, "{}", path.display());
+
+			*count += 1;
+		}
+	}
+
+	let mut count = 0;
+	visit(Path::new(FIXTURES_DIR), &mut count);
+
+	assert!(count > 0, "no fixtures found in {FIXTURES_DIR}");
+}
+
+#[test]
+fn single_line_rules() {
+	let options = FormatOptions {
+		single_line_rules: 2,
+		print_width: 40,
+		..FormatOptions::DEFAULT
+	};
+
+	let cases = [
+		(".mt-1{margin-top:4px}", ".mt-1 { margin-top: 4px; }\n"),
+		(".p-2{color:red;padding:8px}", ".p-2 { padding: 8px; color: red; }\n"),
+		// Exactly `print_width` wide
+		(".a{margin:0 auto;color:red;}", ".a { margin: 0 auto; color: red; }\n"),
+		(".a { }", ".a {\n}\n"),
+		// Too many declarations
+		(".x{a:b;c:d;e:f}", ".x {\n\ta: b;\n\tc: d;\n\te: f;\n}\n"),
+		// Too wide
+		(
+			".a{margin:0 auto;color:rebeccapurple}",
+			".a {\n\tmargin: 0 auto;\n\n\tcolor: rebeccapurple;\n}\n",
+		),
+		// Selectors on several lines
+		("a,b{x:y}", "a,\nb {\n\tx: y;\n}\n"),
+		// Nested rules
+		(
+			"a{b:c;&:hover{d:e}}\n@media print{.a{color:red}}",
+			"a {\n\tb: c;\n\n\t&:hover { d: e; }\n}\n\n@media print {\n\t.a { color: red; }\n}\n",
+		),
+		("a{/* b */c:d}", "a {\n\t/* b */\n\tc: d;\n}\n"),
+	];
+
+	for (input, expected) in cases {
+		assert_eq!(format(input, options), expected, "{input:?}");
+		assert_eq!(format(expected, options), expected, "{expected:?}");
+	}
+
+	let recover = FormatOptions {
+		recover: true,
+		..options
+	};
+
+	// Declarations copied as is stay on their own line
+	assert_eq!(
+		format("a{color:red !}b{color:red;x:y !}", recover),
+		"a {\n\tcolor:red !;\n}\n\nb {\n\tcolor: red;\n\n\tx:y !;\n}\n"
+	);
+}
@@ -1,5 +1,9 @@
 use std::io::Result;
 
+use consts::ASCII;
+
+use crate::{FormatOptions, IndentStyle};
+
 pub trait Helper: std::io::Write {
 	#[inline]
 	fn write_comment(&mut self, bytes: &[u8]) -> Result<()> {
@@ -9,9 +13,19 @@ pub trait Helper: std::io::Write {
 	}
 
 	#[inline]
-	fn write_indent(&mut self, indent: u8) -> Result<()> {
-		for _ in 0..indent {
-			self.write_u8(b'\t')?;
+	fn write_indent(&mut self, indent: u8, options: &FormatOptions) -> Result<()> {
+		match options.indent_style {
+			IndentStyle::Tabs => {
+				for _ in 0..indent {
+					self.write_u8(ASCII::TAB)?;
+				}
+			}
+
+			IndentStyle::Spaces => {
+				for _ in 0..options.indent_columns(indent) {
+					self.write_space()?;
+				}
+			}
 		}
 
 		Ok(())
@@ -23,16 +37,21 @@ pub trait Helper: std::io::Write {
 	}
 
 	#[inline]
-	fn finish_line_with_indent(&mut self, bytes: &[u8], indent: u8) -> Result<()> {
-		self.write_indent(indent)?;
+	fn finish_line_with_indent(
+		&mut self,
+		bytes: &[u8],
+		indent: u8,
+		options: &FormatOptions,
+	) -> Result<()> {
+		self.write_indent(indent, options)?;
 		self.write_all(bytes)?;
 		self.write_newline()
 	}
 
 	#[inline(always)]
-	fn write_newline_with_indent(&mut self, indent: u8) -> Result<()> {
+	fn write_newline_with_indent(&mut self, indent: u8, options: &FormatOptions) -> Result<()> {
 		self.write_newline()?;
-		self.write_indent(indent)
+		self.write_indent(indent, options)
 	}
 
 	#[inline(always)]
@@ -1,27 +1,51 @@
 use crate::utils::Helper;
 use consts::ASCII;
 
+use super::position::Position;
 use super::tokens::Token;
 
-#[derive(Debug)]
+/// Every error points to where it happened in the input
+#[derive(Clone, Copy, Debug)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum Error {
-	BadComment,
-	BadString,
-	EOF,
-	NonASCII,
+	/// Points to comment opening `/*`
+	BadComment(Position),
+	/// Points to opening quote
+	BadString(Position),
+	EOF(Position),
 }
 
 /// An opinionated parser for opinionated CSS formatter
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "simd-tokenizer", allow(dead_code))]
 pub struct Parser<'a> {
 	buf: &'a [u8],
 	// Current position (index)
 	pos: usize,
+	// Start of the last parsed token
+	start: usize,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+	#[inline]
+	pub fn position(&self) -> Position {
+		match self {
+			Error::BadComment(position) | Error::BadString(position) | Error::EOF(position) => {
+				*position
+			}
+		}
+	}
+}
+
+/// Tokenizer behind syntax tree builder, SIMD one with `simd-tokenizer` feature
+#[cfg(not(feature = "simd-tokenizer"))]
+pub type Backend<'a> = Parser<'a>;
+#[cfg(feature = "simd-tokenizer")]
+pub type Backend<'a> = simd::SimdParser<'a>;
+
+#[cfg_attr(feature = "simd-tokenizer", allow(dead_code))]
 impl<'a> Parser<'a> {
 	#[inline(always)]
 	pub fn advance(&mut self, amount: usize) {
@@ -35,7 +59,21 @@ impl<'a> Parser<'a> {
 
 	#[inline]
 	pub fn new(input: &'a [u8]) -> Self {
-		Self { buf: input, pos: 0 }
+		Self {
+			buf: input,
+			pos: 0,
+			start: 0,
+		}
+	}
+
+	#[inline(always)]
+	pub fn input(&self) -> &'a [u8] {
+		self.buf
+	}
+
+	#[inline]
+	fn position(&self, offset: usize) -> Position {
+		Position::at(offset)
 	}
 
 	#[inline(always)]
@@ -46,7 +84,18 @@ impl<'a> Parser<'a> {
 
 	#[inline(always)]
 	fn peek_next_byte(&self) -> Option<u8> {
-		self.buf.get(self.pos() + 1).copied()
+		self.peek(1)
+	}
+
+	/// Byte `i` steps ahead of current one
+	#[inline(always)]
+	fn peek(&self, i: usize) -> Option<u8> {
+		self.buf.get(self.pos() + i).copied()
+	}
+
+	#[inline(always)]
+	fn lookahead(&self, seq: &[u8]) -> bool {
+		self.buf[self.pos()..].starts_with(seq)
 	}
 
 	#[inline]
@@ -54,16 +103,14 @@ impl<'a> Parser<'a> {
 		let bytes = self.buf;
 
 		if self.is_eof() {
-			return Err(Error::EOF);
+			return Err(Error::EOF(self.position(self.pos())));
 		}
 
+		self.start = self.pos();
+
 		let cur = self.get_current_byte();
 		let next = self.peek_next_byte();
 
-		if !cur.is_ascii() {
-			return Err(Error::NonASCII);
-		}
-
 		let token = match cur {
 			// A comment or delim token
 			ASCII::SLASH => {
@@ -83,8 +130,8 @@ impl<'a> Parser<'a> {
 
 			// A hash or delim token
 			ASCII::HASH => {
-				if matches!(next, Some(x) if matches!(x, b'a'..=b'z' | b'A'..=b'Z' | ASCII::UNDERSCORE)) {
-					self.parse_name(bytes)
+				if next.is_ident_char() || self.is_valid_escape(1) {
+					Token::Hash(self.parse_prefixed_name(bytes))
 				} else {
 					self.advance(1);
 					Token::Delim(ASCII::HASH)
@@ -98,52 +145,68 @@ impl<'a> Parser<'a> {
 				self.parse_string(bytes, quote_type)?
 			}
 
-			// Number token
-			b'0'..=b'9' => self.parse_number(bytes)?,
+			// Numeric token
+			b'0'..=b'9' => self.parse_numeric(bytes),
 
-			// Number or delim token
-			#[allow(unused_must_use)]
+			// Numeric or delim token
 			delim @ (ASCII::FULL_STOP | ASCII::PLUS) => {
-				if next.is_digit()
-					|| (next == Some(ASCII::FULL_STOP)
-						&& matches!(bytes.get(self.pos() + 2), Some(x) if x.is_digit()))
-				{
-					self.parse_number(bytes)?
+				if self.starts_number(0) {
+					self.parse_numeric(bytes)
 				} else {
 					self.advance(1);
 					Token::Delim(delim)
 				}
 			}
 
-			// Number or ident or delim token
-			#[allow(unused_must_use)]
+			// Numeric, CDC, ident-like or delim token
 			ASCII::DASH => {
-				if next.is_digit()
-					|| (next == Some(ASCII::FULL_STOP)
-						&& matches!(bytes.get(self.pos() + 2), Some(x) if x.is_digit()))
-				{
-					self.parse_number(bytes)?
-				} else if matches!(next, Some(x) if matches!(x, b'a'..=b'z' | b'A'..=b'Z' | ASCII::UNDERSCORE | ASCII::DASH))
-				{
-					self.parse_name(bytes)
+				if self.starts_number(0) {
+					self.parse_numeric(bytes)
+				} else if self.lookahead(b"-->") {
+					self.advance(3);
+					Token::CDC
+				} else if self.starts_ident(0) {
+					self.parse_ident_like(bytes)
 				} else {
 					self.advance(1);
 					Token::Delim(ASCII::DASH)
 				}
 			}
 
+			// CDO or delim token
+			ASCII::LT => {
+				if self.lookahead(b"<!--") {
+					self.advance(4);
+					Token::CDO
+				} else {
+					self.advance(1);
+					Token::Delim(ASCII::LT)
+				}
+			}
+
 			// AtRule or Delim token
 			ASCII::AT => {
-				if matches!(next, Some(x) if matches!(x, b'a'..=b'z' | b'A'..=b'Z' | ASCII::UNDERSCORE)) {
-					self.parse_name(bytes)
+				if self.starts_ident(1) {
+					Token::AtRule(self.parse_prefixed_name(bytes))
 				} else {
 					self.advance(1);
 					Token::Delim(ASCII::AT)
 				}
 			}
 
-			// Ident token
-			b'a'..=b'z' | b'A'..=b'Z' | ASCII::UNDERSCORE => self.parse_name(bytes),
+			// `U+0025-00FF` or `u+4??`
+			b'u' | b'U'
+				if next == Some(ASCII::PLUS)
+					&& (self.peek(2).is_hex_digit() || self.peek(2) == Some(ASCII::QUESTION)) =>
+			{
+				self.parse_unicode_range(bytes)
+			}
+
+			// Ident-like token, including any non-ASCII code point
+			b'a'..=b'z' | b'A'..=b'Z' | ASCII::UNDERSCORE | 0x80.. => self.parse_ident_like(bytes),
+
+			// Ident-like token starting with escape, e.g. `\31 0col`
+			ASCII::BACKSLASH if self.is_valid_escape(0) => self.parse_ident_like(bytes),
 
 			_ => {
 				self.advance(1);
@@ -171,6 +234,8 @@ impl<'a> Parser<'a> {
 
 	#[inline]
 	fn parse_comment(&mut self, bytes: &'a [u8]) -> Result<Token<'a>> {
+		let opening = self.pos();
+
 		// Step over comment opening seq `/*`
 		self.advance(2);
 
@@ -197,71 +262,297 @@ impl<'a> Parser<'a> {
 			self.advance(1);
 		}
 
-		Err(Error::BadComment)
+		Err(Error::BadComment(self.position(opening)))
+	}
+
+	/// Steps over ident chars and escapes
+	#[inline]
+	fn consume_name(&mut self) {
+		loop {
+			if self.peek(0).is_ident_char() {
+				self.advance(1);
+			} else if self.is_valid_escape(0) {
+				self.consume_escape();
+			} else {
+				break;
+			}
+		}
+	}
+
+	/// Steps over `\` and escaped code point, see [`Self::is_valid_escape`]
+	#[inline]
+	fn consume_escape(&mut self) {
+		self.advance(1);
+
+		if !self.peek(0).is_hex_digit() {
+			// Rest of a multibyte code point is consumed by the caller
+			if !self.is_eof() {
+				self.advance(1);
+			}
+
+			return;
+		}
+
+		self.consume_hex_digits(6);
+
+		// Single whitespace after hex digits belongs to the escape
+		match (self.peek(0), self.peek(1)) {
+			(Some(ASCII::CR), Some(ASCII::LF)) => self.advance(2),
+			(ws, _) if ws.is_whitespace() => self.advance(1),
+			_ => {}
+		}
+	}
+
+	/// Steps over up to `max` hex digits, returns amount consumed
+	#[inline]
+	fn consume_hex_digits(&mut self, max: usize) -> usize {
+		let mut count = 0;
+
+		while count < max && self.peek(0).is_hex_digit() {
+			self.advance(1);
+			count += 1;
+		}
+
+		count
+	}
+
+	#[inline]
+	fn consume_digits(&mut self) {
+		while self.peek(0).is_digit() {
+			self.advance(1);
+		}
+	}
+
+	/// Matches: `1`, `-.5`, `+1.5e-10`
+	#[inline]
+	fn consume_number(&mut self) {
+		if matches!(self.peek(0), Some(ASCII::PLUS | ASCII::DASH)) {
+			self.advance(1);
+		}
+
+		self.consume_digits();
+
+		if self.peek(0) == Some(ASCII::FULL_STOP) && self.peek(1).is_digit() {
+			self.advance(1);
+			self.consume_digits();
+		}
+
+		if matches!(self.peek(0), Some(b'e' | b'E')) {
+			let sign = matches!(self.peek(1), Some(ASCII::PLUS | ASCII::DASH)) as usize;
+
+			if self.peek(1 + sign).is_digit() {
+				self.advance(1 + sign);
+				self.consume_digits();
+			}
+		}
+	}
+
+	/// Two bytes starting at `i` are `\` and anything but a newline
+	#[inline]
+	fn is_valid_escape(&self, i: usize) -> bool {
+		self.peek(i) == Some(ASCII::BACKSLASH)
+			&& !matches!(self.peek(i + 1), Some(ASCII::LF | ASCII::CR | ASCII::FF))
+	}
+
+	/// Bytes starting at `i` would start a number
+	#[inline]
+	fn starts_number(&self, i: usize) -> bool {
+		match self.peek(i) {
+			Some(ASCII::PLUS | ASCII::DASH) => {
+				self.peek(i + 1).is_digit()
+					|| (self.peek(i + 1) == Some(ASCII::FULL_STOP) && self.peek(i + 2).is_digit())
+			}
+			Some(ASCII::FULL_STOP) => self.peek(i + 1).is_digit(),
+			first => first.is_digit(),
+		}
+	}
+
+	/// Bytes starting at `i` would start an ident
+	#[inline]
+	fn starts_ident(&self, i: usize) -> bool {
+		match self.peek(i) {
+			Some(ASCII::DASH) => {
+				self.peek(i + 1).is_ident_start()
+					|| self.peek(i + 1) == Some(ASCII::DASH)
+					|| self.is_valid_escape(i + 1)
+			}
+			Some(ASCII::BACKSLASH) => self.is_valid_escape(i),
+			first => first.is_ident_start(),
+		}
 	}
 
+	/// Ident, function or url token
 	#[inline]
-	fn parse_name(&mut self, bytes: &'a [u8]) -> Token<'a> {
-		let opening = bytes[self.pos()];
+	fn parse_ident_like(&mut self, bytes: &'a [u8]) -> Token<'a> {
 		let start = self.pos();
 
+		self.consume_name();
+
+		let name = &bytes[start..self.pos()];
+
+		if self.peek(0) != Some(ASCII::PAREN_OPEN) {
+			return Token::Ident(name);
+		}
+
+		// Consume opening paren
 		self.advance(1);
 
-		let mut is_function = false;
+		let function = &bytes[start..self.pos()];
 
-		while !self.is_eof() {
-			let cur = self.get_current_byte();
+		if !name.eq_ignore_ascii_case(b"url") {
+			return Token::Function(function);
+		}
+
+		// Leave a single whitespace before a quote, as `url( "a")` is a function
+		while self.peek(0).is_whitespace() && self.peek(1).is_whitespace() {
+			self.advance(1);
+		}
+
+		let first = match self.peek(0) {
+			ws if ws.is_whitespace() => self.peek(1),
+			first => first,
+		};
+
+		if matches!(first, Some(ASCII::DOUBLE_QUOTE | ASCII::SINGLE_QUOTE)) {
+			return Token::Function(function);
+		}
+
+		self.parse_url(bytes)
+	}
+
+	/// Unquoted `url(...)` content, right after opening paren
+	#[inline]
+	fn parse_url(&mut self, bytes: &'a [u8]) -> Token<'a> {
+		while self.peek(0).is_whitespace() {
+			self.advance(1);
+		}
+
+		let start = self.pos();
 
-			if !matches!(cur, b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | ASCII::DASH | ASCII::UNDERSCORE) {
-				if cur == ASCII::PAREN_OPEN && opening != ASCII::AT {
-					is_function = true;
-					// Consume opening paren
+		loop {
+			let Some(cur) = self.peek(0) else {
+				// Unclosed url still is a url
+				return Token::URL(&bytes[start..self.pos()]);
+			};
+
+			match cur {
+				ASCII::PAREN_CLOSE => {
+					let token = Token::URL(&bytes[start..self.pos()]);
 					self.advance(1);
+					return token;
+				}
+
+				ws if ws.is_whitespace() => {
+					let end = self.pos();
+
+					while self.peek(0).is_whitespace() {
+						self.advance(1);
+					}
+
+					match self.peek(0) {
+						None => return Token::URL(&bytes[start..end]),
+						Some(ASCII::PAREN_CLOSE) => {
+							self.advance(1);
+							return Token::URL(&bytes[start..end]);
+						}
+						Some(_) => break,
+					}
 				}
 
+				ASCII::BACKSLASH if self.is_valid_escape(0) => self.consume_escape(),
+
+				// Quotes, parens, non-printable or a bad escape
+				ASCII::DOUBLE_QUOTE
+				| ASCII::SINGLE_QUOTE
+				| ASCII::PAREN_OPEN
+				| ASCII::BACKSLASH
+				| 0x00..=0x08
+				| 0x0B
+				| 0x0E..=0x1F
+				| 0x7F => break,
+
+				_ => self.advance(1),
+			}
+		}
+
+		// Skip the rest of bad url, up to and including `)`
+		while let Some(cur) = self.peek(0) {
+			if cur == ASCII::PAREN_CLOSE {
+				self.advance(1);
 				break;
 			}
 
-			self.advance(1);
+			if self.is_valid_escape(0) {
+				self.consume_escape();
+			} else {
+				self.advance(1);
+			}
 		}
 
-		let bytes = &bytes[start..self.pos()];
+		Token::BadURL
+	}
 
-		let token = match opening {
-			ASCII::AT => Token::AtRule(bytes),
-			ASCII::HASH => Token::Hash(bytes),
-			_ if is_function => Token::Function(bytes),
-			_ => Token::Ident(bytes),
-		};
+	/// Name of a hash or an at-rule, including prefix
+	#[inline]
+	fn parse_prefixed_name(&mut self, bytes: &'a [u8]) -> &'a [u8] {
+		let start = self.pos();
+
+		// Step over `#` or `@`
+		self.advance(1);
+		self.consume_name();
 
-		token
+		&bytes[start..self.pos()]
 	}
 
+	/// Number, percentage or dimension token
 	#[inline]
-	fn parse_number(&mut self, bytes: &'a [u8]) -> Result<Token<'a>> {
+	fn parse_numeric(&mut self, bytes: &'a [u8]) -> Token<'a> {
 		let start = self.pos();
 
-		let mut cur = self.get_current_byte();
+		self.consume_number();
+
+		let value = &bytes[start..self.pos()];
+
+		if self.starts_ident(0) {
+			let unit = self.pos();
+			self.consume_name();
+
+			return Token::Dimension {
+				value,
+				unit: &bytes[unit..self.pos()],
+			};
+		}
 
-		if matches!(cur, ASCII::DASH | ASCII::PLUS | ASCII::FULL_STOP) {
+		if self.peek(0) == Some(ASCII::PERCENTAGE) {
 			self.advance(1);
+			return Token::Percentage(value);
 		}
 
-		while !self.is_eof() {
-			cur = self.get_current_byte();
+		Token::Number(value)
+	}
 
-			// Matches (we don't care about validity): 1px, 1rem, 100%, +110e10, -110, +++++++++1, .1..1
-			if !matches!(cur,  ASCII::PERCENTAGE| ASCII::PLUS | ASCII::DASH | ASCII::FULL_STOP | b'A'..=b'Z'| b'a'..=b'z' |b'0'..=b'9' )
-			{
-				break;
-			}
+	/// Matches `U+` followed by up to 6 hex digits or `?`, or a range of hex digits
+	#[inline]
+	fn parse_unicode_range(&mut self, bytes: &'a [u8]) -> Token<'a> {
+		let start = self.pos();
+
+		// Step over `U+`
+		self.advance(2);
+
+		let digits = self.consume_hex_digits(6);
+		let mut len = digits;
 
+		while len < 6 && self.peek(0) == Some(ASCII::QUESTION) {
 			self.advance(1);
+			len += 1;
 		}
 
-		let bytes = &bytes[start..self.pos()];
+		if len == digits && self.peek(0) == Some(ASCII::DASH) && self.peek(1).is_hex_digit() {
+			self.advance(1);
+			self.consume_hex_digits(6);
+		}
 
-		Ok(Token::Number(bytes))
+		Token::UnicodeRange(&bytes[start..self.pos()])
 	}
 
 	#[inline]
@@ -283,15 +574,19 @@ impl<'a> Parser<'a> {
 				break;
 			}
 
+			// Escaped quote, backslash or newline (`\r\n` included) are part of the string
 			if cur == ASCII::BACKSLASH {
-				// Ignore any escape seq
-				self.advance(1);
+				let len = if self.lookahead(b"\\\r\n") { 3 } else { 2 };
+				self.advance(len.min(self.buf.len() - self.pos()));
+
+				continue;
 			}
 
 			self.advance(1);
 		}
 
-		Err(Error::BadString)
+		// Step back to opening quote
+		Err(Error::BadString(self.position(start - 1)))
 	}
 
 	#[inline(always)]
@@ -299,6 +594,18 @@ impl<'a> Parser<'a> {
 		self.pos
 	}
 
+	/// Moves to `offset` of the input
+	#[inline(always)]
+	pub fn seek(&mut self, offset: usize) {
+		self.pos = offset;
+	}
+
+	/// Byte offset of the last parsed token
+	#[inline(always)]
+	pub fn token_start(&self) -> usize {
+		self.start
+	}
+
 	#[inline]
 	fn skip_whitespace(&mut self) {
 		// Step over once
@@ -319,35 +626,13 @@ impl<'a> Parser<'a> {
 	// #[inline]
 }
 
-impl<'a> std::fmt::Debug for Token<'a> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		use std::str::from_utf8_unchecked as str;
-
-		unsafe {
-			match self {
-				Token::Comment(bytes) => write!(f, "Comment(\"{}\")", str(bytes)),
-				Token::Ident(bytes) => write!(f, "Ident(\"{}\")", str(bytes)),
-				Token::Function(bytes) => write!(f, "Function(\"{}\")", str(bytes)),
-				Token::AtRule(bytes) => write!(f, "AtRule(\"{}\")", str(bytes)),
-				Token::Hash(bytes) => write!(f, "Hash(\"{}\")", str(bytes)),
-				Token::String(bytes) => write!(f, "String(\"{}\")", str(bytes)),
-				Token::Number(bytes) => write!(f, "Number(\"{}\")", str(bytes)),
-				Token::Delim(d) => write!(f, "Delim({})", *d as char),
-				Token::Whitespace => f.write_str("Whitespace"),
-				Token::Colon => f.write_str("Colon"),
-				Token::Semicolon => f.write_str("Semicolon"),
-				Token::Comma => f.write_str("Comma"),
-				Token::BracketRoundOpen => f.write_str("BracketRoundOpen"),
-				Token::BracketRoundClose => f.write_str("BracketRoundClose"),
-				Token::BracketSquareOpen => f.write_str("BracketSquareOpen"),
-				Token::BracketSquareClose => f.write_str("BracketSquareClose"),
-				Token::BracketCurlyOpen => f.write_str("BracketCurlyOpen"),
-				Token::BracketCurlyClose => f.write_str("BracketCurlyClose"),
-			}
-		}
-	}
-}
+#[cfg(feature = "simd-tokenizer")]
+mod differential;
+#[cfg(feature = "simd-tokenizer")]
+mod simd;
 
-mod cache;
+#[cfg(test)]
+mod tests;
 
-pub use cache::Cache;
+#[cfg(feature = "simd-tokenizer")]
+pub use differential::{compare, Divergence};
@@ -0,0 +1,126 @@
+use tokenizer::{Error as TokenizerError, Spanned, Token as SimdToken, Tokenizer};
+
+use super::{Error, Parser, Token};
+
+/// First token [`Parser`] and [`Tokenizer`] disagree on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+	/// Byte offset both tokenizers started the token at
+	pub offset: usize,
+	pub scalar: String,
+	pub simd: String,
+}
+
+impl std::fmt::Display for Divergence {
+	#[inline]
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"tokenizers diverge at byte {}: scalar `{}`, simd `{}`",
+			self.offset, self.scalar, self.simd
+		)
+	}
+}
+
+impl std::error::Error for Divergence {}
+
+/// Feeds `input` to both tokenizers, returns the first token they disagree on.
+///
+/// Tokens have to match in kind, payload and span. Whitespace is also checked to
+/// report a linebreak if and only if it contains one, as [`Parser`] does not track it
+pub fn compare(input: &[u8]) -> Result<(), Divergence> {
+	let mut scalar = Parser::new(input);
+	let mut simd = Tokenizer::new(input);
+
+	loop {
+		let offset = scalar.pos();
+
+		let scalar_res = scalar.next();
+		let scalar_span = scalar.token_start()..scalar.pos();
+
+		let (simd_res, simd_span) = match simd.next_spanned() {
+			Ok(Spanned { value, span }) => (Ok(value), span.range()),
+			Err(Spanned { value, span }) => (Err(value), span.range()),
+		};
+
+		let is_same = match (&scalar_res, &simd_res) {
+			(Ok(scalar), Ok(simd)) => {
+				scalar_span == simd_span && is_same_token(*scalar, *simd, &input[simd_span.clone()])
+			}
+			// Only starts are comparable, as errors leave cursors at different points
+			(Err(scalar), Err(simd)) => is_same_error(scalar, *simd, simd_span.start),
+			_ => false,
+		};
+
+		// Empty token would never let the loop end
+		if !is_same || (scalar_res.is_ok() && scalar_span.is_empty()) {
+			return Err(Divergence {
+				offset,
+				scalar: format!("{scalar_res:?} at {scalar_span:?}"),
+				simd: format!("{simd_res:?} at {simd_span:?}"),
+			});
+		}
+
+		// Both tokenizers leave the cursor at an arbitrary point after an error
+		if scalar_res.is_err() {
+			return Ok(());
+		}
+	}
+}
+
+/// `start` of the error, as spanned by [`Tokenizer`]
+#[inline]
+fn is_same_error(scalar: &Error, simd: TokenizerError, start: usize) -> bool {
+	match (scalar, simd) {
+		(Error::BadComment(position), TokenizerError::BadComment)
+		| (Error::BadString(position), TokenizerError::BadString)
+		| (Error::EOF(position), TokenizerError::EOF) => position.offset == start,
+		_ => false,
+	}
+}
+
+/// `bytes` are the whole token, as spanned by [`Tokenizer`]
+#[inline]
+fn is_same_token(scalar: Token, simd: SimdToken, bytes: &[u8]) -> bool {
+	match (scalar, simd) {
+		(Token::Comment(a), SimdToken::Comment(b))
+		| (Token::Ident(a), SimdToken::Ident(b))
+		| (Token::Function(a), SimdToken::Function(b))
+		| (Token::URL(a), SimdToken::URL(b))
+		| (Token::AtRule(a), SimdToken::AtRule(b))
+		| (Token::Hash(a), SimdToken::Hash(b))
+		| (Token::String(a), SimdToken::String(b))
+		| (Token::Number(a), SimdToken::Number(b))
+		| (Token::Percentage(a), SimdToken::Percentage(b))
+		| (Token::UnicodeRange(a), SimdToken::UnicodeRange(b)) => a == b,
+
+		(
+			Token::Dimension { value, unit },
+			SimdToken::Dimension {
+				value: simd_value,
+				unit: simd_unit,
+			},
+		) => value == simd_value && unit == simd_unit,
+
+		(Token::Delim(a), SimdToken::Delim(b)) => a == b,
+
+		(Token::Whitespace, SimdToken::Whitespace(newline)) => {
+			newline == bytes.iter().any(|&byte| matches!(byte, b'\n' | b'\r'))
+		}
+
+		(Token::BadURL, SimdToken::BadURL)
+		| (Token::CDO, SimdToken::CDO)
+		| (Token::CDC, SimdToken::CDC)
+		| (Token::Colon, SimdToken::Colon)
+		| (Token::Semicolon, SimdToken::Semicolon)
+		| (Token::Comma, SimdToken::Comma)
+		| (Token::BracketRoundOpen, SimdToken::BracketRoundOpen)
+		| (Token::BracketRoundClose, SimdToken::BracketRoundClose)
+		| (Token::BracketSquareOpen, SimdToken::BracketSquareOpen)
+		| (Token::BracketSquareClose, SimdToken::BracketSquareClose)
+		| (Token::BracketCurlyOpen, SimdToken::BracketCurlyOpen)
+		| (Token::BracketCurlyClose, SimdToken::BracketCurlyClose) => true,
+
+		_ => false,
+	}
+}
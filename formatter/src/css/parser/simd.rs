@@ -0,0 +1,104 @@
+use tokenizer::{Error as TokenizerError, Spanned, Token as SimdToken, Tokenizer};
+
+use crate::css::position::Position;
+
+use super::{Error, Result, Token};
+
+/// Same as [`super::Parser`], but driven by SIMD [`Tokenizer`]
+#[derive(Clone, Debug)]
+pub struct SimdParser<'a> {
+	tokenizer: Tokenizer<'a>,
+	// Start of the last parsed token
+	start: usize,
+}
+
+impl<'a> SimdParser<'a> {
+	#[inline]
+	pub fn new(input: &'a [u8]) -> Self {
+		Self {
+			tokenizer: Tokenizer::new(input),
+			start: 0,
+		}
+	}
+
+	#[inline(always)]
+	pub fn input(&self) -> &'a [u8] {
+		self.tokenizer.input()
+	}
+
+	#[inline]
+	fn position(&self, offset: usize) -> Position {
+		Position::at(offset)
+	}
+
+	#[inline]
+	pub fn next(&mut self) -> Result<Token<'a>> {
+		let (res, span) = match self.tokenizer.next_spanned() {
+			Ok(Spanned { value, span }) => (Ok(value), span),
+			Err(Spanned { value, span }) => (Err(value), span),
+		};
+
+		self.start = span.start;
+
+		let token = match res {
+			Ok(token) => token,
+			Err(err) => {
+				let position = self.position(span.start);
+
+				return Err(match err {
+					TokenizerError::BadComment => Error::BadComment(position),
+					TokenizerError::BadString => Error::BadString(position),
+					TokenizerError::EOF => Error::EOF(position),
+				});
+			}
+		};
+
+		let token = match token {
+			SimdToken::Comment(bytes) => Token::Comment(bytes),
+			SimdToken::Ident(bytes) => Token::Ident(bytes),
+			SimdToken::Function(bytes) => Token::Function(bytes),
+			SimdToken::AtRule(bytes) => Token::AtRule(bytes),
+			SimdToken::Hash(bytes) => Token::Hash(bytes),
+			SimdToken::String(bytes) => Token::String(bytes),
+			SimdToken::Number(bytes) => Token::Number(bytes),
+			SimdToken::Percentage(bytes) => Token::Percentage(bytes),
+			SimdToken::Dimension { value, unit } => Token::Dimension { value, unit },
+			SimdToken::UnicodeRange(bytes) => Token::UnicodeRange(bytes),
+			SimdToken::URL(bytes) => Token::URL(bytes),
+			SimdToken::BadURL => Token::BadURL,
+			SimdToken::CDO => Token::CDO,
+			SimdToken::CDC => Token::CDC,
+			SimdToken::Delim(delim) => Token::Delim(delim),
+			SimdToken::Whitespace(_) => Token::Whitespace,
+			SimdToken::Colon => Token::Colon,
+			SimdToken::Semicolon => Token::Semicolon,
+			SimdToken::Comma => Token::Comma,
+			SimdToken::BracketRoundOpen => Token::BracketRoundOpen,
+			SimdToken::BracketRoundClose => Token::BracketRoundClose,
+			SimdToken::BracketSquareOpen => Token::BracketSquareOpen,
+			SimdToken::BracketSquareClose => Token::BracketSquareClose,
+			SimdToken::BracketCurlyOpen => Token::BracketCurlyOpen,
+			SimdToken::BracketCurlyClose => Token::BracketCurlyClose,
+			token => unreachable!("{token} is never produced by the tokenizer"),
+		};
+
+		Ok(token)
+	}
+
+	#[inline(always)]
+	pub fn pos(&self) -> usize {
+		self.tokenizer.pos()
+	}
+
+	/// Moves to `offset` of the input
+	#[inline(always)]
+	pub fn seek(&mut self, offset: usize) {
+		self.tokenizer.seek(offset);
+	}
+
+	/// Byte offset of the last parsed token
+	#[inline(always)]
+	pub fn token_start(&self) -> usize {
+		self.start
+	}
+}
@@ -0,0 +1,30 @@
+use serde_json::{json, Value};
+
+use super::{Error, Parser, Token};
+
+use conformance::to_json;
+
+fn tokenize(input: &str) -> Value {
+	let mut parser = Parser::new(input.as_bytes());
+	let mut tokens = Vec::new();
+
+	loop {
+		match parser.next() {
+			Ok(token) => tokens.push(to_json!(token)),
+			Err(Error::EOF(_)) => break,
+			Err(Error::BadComment(_)) => break tokens.push(json!(["error", "bad-comment"])),
+			Err(Error::BadString(_)) => break tokens.push(json!(["error", "bad-string"])),
+		}
+	}
+
+	Value::Array(tokens)
+}
+
+#[test]
+fn conformance() {
+	conformance::run(tokenize);
+}
+
+/// Shared with `tokenizer` crate
+#[path = "../../../../tokenizer/tests/conformance/support.rs"]
+mod conformance;
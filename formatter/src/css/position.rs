@@ -0,0 +1,71 @@
+/// Location of a byte in the input
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+	/// Zero-based byte offset
+	pub offset: usize,
+	/// One-based line number
+	pub line: usize,
+	/// One-based column, counted in characters
+	pub column: usize,
+}
+
+impl Position {
+	/// Only the offset is set, line and column are filled in later by [`Self::resolve`]
+	/// once errors are returned, so that recovering from many stays linear
+	#[inline(always)]
+	pub(crate) const fn at(offset: usize) -> Self {
+		Self {
+			offset,
+			line: 0,
+			column: 0,
+		}
+	}
+
+	/// Locates every one of `positions` in a single pass over `input`, see [`Self::locate`]
+	pub(crate) fn resolve<'p>(input: &[u8], positions: impl IntoIterator<Item = &'p mut Self>) {
+		let mut positions: Vec<&mut Self> = positions.into_iter().collect();
+		positions.sort_unstable_by_key(|position| position.offset);
+
+		let mut located = Self {
+			offset: 0,
+			line: 1,
+			column: 1,
+		};
+
+		for position in positions {
+			let offset = position.offset.min(input.len());
+
+			for &byte in &input[located.offset..offset] {
+				if byte == b'\n' {
+					located.line += 1;
+					located.column = 1;
+				} else if (byte as i8) >= -0x40 {
+					// Every byte, except UTF-8 continuation bytes
+					located.column += 1;
+				}
+			}
+
+			located.offset = offset;
+			*position = located;
+		}
+	}
+
+	/// Expensive - walks the input up to `offset`, use only when reporting
+	#[inline]
+	pub fn locate(input: &[u8], offset: usize) -> Self {
+		let mut position = Self::at(offset);
+		Self::resolve(input, Some(&mut position));
+
+		position
+	}
+}
+
+impl std::fmt::Display for Position {
+	#[inline]
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}:{}", self.line, self.column)
+	}
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,62 @@
+use super::Position;
+use crate::{format, FormatOptions};
+
+fn position(offset: usize, line: usize, column: usize) -> Position {
+	Position {
+		offset,
+		line,
+		column,
+	}
+}
+
+#[test]
+fn locate() {
+	let cases: [(&str, usize, Position); 8] = [
+		("a{b:c}", 0, position(0, 1, 1)),
+		("a{b:c}", 4, position(4, 1, 5)),
+		("a{\n\tb:c}", 4, position(4, 2, 2)),
+		// `\r` ends the line before, it is not a column of the next one
+		("a{\r\n\tb:c}", 5, position(5, 2, 2)),
+		("a{\r\n\tb:c}", 2, position(2, 1, 3)),
+		// Characters, not bytes
+		("/* ü日 */a", 9, position(9, 1, 7)),
+		// Offset right after a newline starts the next line
+		("a\n", 2, position(2, 2, 1)),
+		// Past the end is clamped
+		("a\nb", 10, position(3, 2, 2)),
+	];
+
+	for (input, offset, expected) in cases {
+		assert_eq!(Position::locate(input.as_bytes(), offset), expected, "{input:?} at {offset}");
+	}
+}
+
+#[test]
+fn resolve() {
+	let input = b"a{\n\tb:c;\r\n}\n\nd{}";
+	let offsets = [13, 4, 0, 13, 10];
+
+	let mut positions = offsets.map(Position::at);
+	Position::resolve(input, positions.iter_mut());
+
+	assert_eq!(positions, offsets.map(|offset| Position::locate(input, offset)));
+	assert_eq!(positions[0], position(13, 5, 1));
+}
+
+#[test]
+fn diagnostics() {
+	let options = FormatOptions {
+		recover: true,
+		..FormatOptions::DEFAULT
+	};
+
+	let input = "a{\r\n\tcolor;\r\n\tb:c}\r\n日{x}\r\n\td e{f:g}";
+	let diagnostics = format(input, &mut Vec::new(), options).expect("recovers");
+
+	let positions: Vec<_> = diagnostics
+		.iter()
+		.map(|err| err.position().expect("has position").to_string())
+		.collect();
+
+	assert_eq!(positions, ["2:2", "4:3"]);
+}
@@ -26,32 +26,30 @@ pub enum Group {
 }
 
 /// A CSS property descriptor
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
-pub struct Descriptor {
-	name: Name,
+#[derive(Clone, Debug, Eq, PartialEq, Default)]
+pub struct Descriptor<'a> {
+	name: Name<'a>,
 	group: Group,
 	order: u16,
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
-pub struct Name {
-	inner: *const str,
+/// Names of known properties are static, others are borrowed from the input, and copied
+/// by [`Descriptor::assign`] into descriptors kept in [`crate::Session`] past it
+#[derive(Clone, Eq, PartialEq)]
+pub enum Name<'a> {
+	Static(&'static str),
+	Borrowed(&'a str),
+	Owned(String),
 }
 
-impl std::ops::Deref for Name {
+impl std::ops::Deref for Name<'_> {
 	type Target = str;
 
 	#[inline(always)]
 	fn deref(&self) -> &Self::Target {
-		unsafe { &*self.inner }
-	}
-}
-
-impl From<&str> for Name {
-	#[inline]
-	fn from(value: &str) -> Self {
-		Self {
-			inner: value as *const str,
+		match self {
+			Name::Static(name) | Name::Borrowed(name) => name,
+			Name::Owned(name) => name,
 		}
 	}
 }
@@ -68,11 +66,11 @@ macro_rules! group_css_props {
 			$($variant),*
 		}
 
-		impl From<Property> for Descriptor {
+		impl From<Property> for Descriptor<'static> {
 			#[inline]
 			fn from(value: Property) -> Self {
 				match value {
-					$(Property::$variant => Descriptor { name: $repr.into(), group: $group, order: value as u16 }),*
+					$(Property::$variant => Descriptor { name: Name::Static($repr), group: $group, order: value as u16 }),*
 				}
 			}
 		}
@@ -316,28 +314,28 @@ group_css_props!(
 
 impl Property {
 	#[inline(always)]
-	pub fn descriptor(self) -> Descriptor {
+	pub fn descriptor(self) -> Descriptor<'static> {
 		std::convert::Into::<Descriptor>::into(self)
 	}
 }
 
-impl Descriptor {
+impl<'a> Descriptor<'a> {
 	#[inline]
-	fn new(name: &str, group: Group) -> Self {
+	fn new(name: &'a str, group: Group) -> Self {
 		Self {
-			name: name.into(),
+			name: Name::Borrowed(name),
 			group,
 			order: 0,
 		}
 	}
 
 	#[inline(always)]
-	pub fn variable(name: &str) -> Self {
+	pub fn variable(name: &'a str) -> Self {
 		Self::new(name, Group::Variable)
 	}
 
 	#[inline(always)]
-	pub fn unknown(name: &str) -> Self {
+	pub fn unknown(name: &'a str) -> Self {
 		Self::new(name, Group::Unknown)
 	}
 
@@ -352,7 +350,26 @@ impl Descriptor {
 	}
 }
 
-impl Ord for Descriptor {
+impl Descriptor<'static> {
+	/// Copies `other` in, a borrowed name goes into the allocation of one copied before
+	#[inline]
+	pub fn assign(&mut self, other: &Descriptor<'_>) {
+		if let Name::Owned(buf) = &mut self.name {
+			buf.clear();
+			buf.push_str(&other.name);
+		} else {
+			self.name = match other.name {
+				Name::Static(name) => Name::Static(name),
+				_ => Name::Owned(other.name.to_string()),
+			};
+		}
+
+		self.group = other.group;
+		self.order = other.order;
+	}
+}
+
+impl Ord for Descriptor<'_> {
 	#[inline]
 	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
 		self
@@ -364,17 +381,17 @@ impl Ord for Descriptor {
 	}
 }
 
-impl PartialOrd for Descriptor {
+impl PartialOrd for Descriptor<'_> {
 	#[inline(always)]
 	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
 		Some(self.cmp(other))
 	}
 }
 
-impl Default for Name {
+impl Default for Name<'_> {
 	#[inline]
 	fn default() -> Self {
-		Self { inner: "" }
+		Self::Static("")
 	}
 }
 
@@ -385,9 +402,9 @@ impl Default for Group {
 	}
 }
 
-impl std::fmt::Debug for Name {
+impl std::fmt::Debug for Name<'_> {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "\"{}\"", unsafe { &*self.inner })
+		write!(f, "\"{}\"", &**self)
 	}
 }
 
@@ -395,13 +412,31 @@ mod trie;
 
 #[cfg(test)]
 mod tests {
-	use super::Name;
+	use super::{Descriptor, Name, Property};
 
 	#[test]
 	fn name() {
 		let name = Name::default();
-		let s = unsafe { &*name.inner };
 
-		assert!(s.is_empty())
+		assert!(name.is_empty())
+	}
+
+	#[test]
+	fn unknown_name() {
+		let mut desc = Descriptor::default();
+		{
+			let input = String::from("-x-unknown");
+			desc.assign(&Descriptor::unknown(&input));
+		}
+
+		assert_eq!(desc.name(), "-x-unknown");
+
+		// Copied name is reused for whatever is assigned next
+		let copied = desc.name().as_ptr();
+		desc.assign(&Property::Color.descriptor());
+		assert_eq!(desc.name(), "color");
+		desc.assign(&Descriptor::variable("--x"));
+		assert_eq!(desc.name(), "--x");
+		assert_eq!(desc.name().as_ptr(), copied);
 	}
 }
@@ -1,12 +1,12 @@
 use radix::Radix;
 
-use super::{Descriptor, Property};
+use super::{Descriptor, Name, Property};
 
 type Local<T> = Radix<T>;
 
 #[derive(Debug)]
 pub struct Trie {
-	inner: Local<Descriptor>,
+	inner: Local<Descriptor<'static>>,
 }
 
 impl Trie {
@@ -17,15 +17,32 @@ impl Trie {
 		for i in 0..std::mem::variant_count::<Property>() {
 			let prop = unsafe { std::mem::transmute::<u16, Property>(i as u16) };
 			let desc = prop.descriptor();
-			inner.insert(desc.name(), desc);
+
+			// Radix points to its keys, names of properties are static
+			let Name::Static(name) = desc.name else {
+				unreachable!("{} has a static name", desc.name());
+			};
+
+			inner.insert(name, desc);
 		}
 
 		Self { inner }
 	}
 }
 
+impl Default for Trie {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+// SAFETY: keys and descriptors only point to `'static` property names
+unsafe impl Send for Trie {}
+unsafe impl Sync for Trie {}
+
 impl std::ops::Deref for Trie {
-	type Target = Local<Descriptor>;
+	type Target = Local<Descriptor<'static>>;
 
 	#[inline]
 	fn deref(&self) -> &Self::Target {
@@ -0,0 +1,547 @@
+use ::css::visit::{self, Visit};
+use ::css::{
+	AtRule, Block, Bracket, Comment, ComponentValue, Declaration, Function, Item, QualifiedRule,
+	SimpleBlock, Span, Stylesheet, Token,
+};
+
+use super::formatter::{Error, Result};
+use super::parser::{Backend, Error as ParserError};
+use super::position::Position;
+
+/// How far input of a failed item is skipped, see [`Item::Invalid`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Skip {
+	/// Ends with `;`, or right before `}` of enclosing block
+	Declaration,
+	/// Ends with `;`, or with `}` of its own block
+	Rule,
+}
+
+/// Builds [`Stylesheet`] out of `input`, nodes borrow from it.
+///
+/// Unlike [`super::format`] never recovers, the first error is returned
+pub fn parse(input: &[u8]) -> Result<'_, Stylesheet<'_>> {
+	build(input, false).map(|(stylesheet, _)| stylesheet)
+}
+
+/// Same as [`parse`], but if `recover` is set, items that fail are kept as [`Item::Invalid`],
+/// errors are returned along with the tree
+pub fn build(input: &[u8], recover: bool) -> Result<'_, (Stylesheet<'_>, Vec<Error<'_>>)> {
+	// Tokens are only split on ASCII bytes, so every token stays valid UTF-8
+	if let Err(err) = std::str::from_utf8(input) {
+		return Err(Error::UnexpectedUTF8(Position::at(err.valid_up_to())));
+	}
+
+	let mut builder = Builder::new(input, recover);
+	let stylesheet = builder.stylesheet()?;
+
+	Ok((stylesheet, builder.diagnostics))
+}
+
+/// Checks that `input` and bytes of every node are UTF-8, as [`build`] does for trees it returns.
+/// Nodes changed since might not be, those are reported at their start
+pub fn validate<'a>(input: &'a [u8], stylesheet: &Stylesheet<'_>) -> Result<'a, ()> {
+	if let Err(err) = std::str::from_utf8(input) {
+		return Err(Error::UnexpectedUTF8(Position::at(err.valid_up_to())));
+	}
+
+	let mut check = Utf8Check { invalid: None };
+	check.visit_stylesheet(stylesheet);
+
+	match check.invalid {
+		Some(offset) => Err(Error::UnexpectedUTF8(Position::at(offset))),
+		None => Ok(()),
+	}
+}
+
+/// Which of [`Skip`]s is used to recover an item starting with `first` byte
+#[inline]
+pub fn skip_for(first: u8, in_block: bool) -> Skip {
+	if in_block && first != b'@' {
+		Skip::Declaration
+	} else {
+		Skip::Rule
+	}
+}
+
+/// Finds the first node that is not UTF-8, see [`validate`]
+struct Utf8Check {
+	invalid: Option<usize>,
+}
+
+impl Utf8Check {
+	#[inline]
+	fn check(&mut self, bytes: &[u8], span: Span) {
+		if self.invalid.is_none() && std::str::from_utf8(bytes).is_err() {
+			self.invalid = Some(span.start);
+		}
+	}
+}
+
+impl<'a> Visit<'a> for Utf8Check {
+	#[inline]
+	fn visit_at_rule(&mut self, rule: &AtRule<'a>) {
+		self.check(rule.name, rule.span);
+		visit::walk_at_rule(self, rule);
+	}
+
+	#[inline]
+	fn visit_declaration(&mut self, declaration: &Declaration<'a>) {
+		self.check(declaration.name, declaration.span);
+		visit::walk_declaration(self, declaration);
+	}
+
+	#[inline]
+	fn visit_comment(&mut self, comment: &Comment<'a>) {
+		self.check(comment.text, comment.span);
+	}
+
+	#[inline]
+	fn visit_component_value(&mut self, value: &ComponentValue<'a>) {
+		match value {
+			ComponentValue::Token { token, span } => {
+				token.map_bytes(|bytes| {
+					self.check(bytes, *span);
+					bytes
+				});
+			}
+			ComponentValue::Function(function) => self.check(function.name, function.span),
+			ComponentValue::Block(_) => {}
+		}
+
+		visit::walk_component_value(self, value);
+	}
+}
+
+struct Builder<'a> {
+	parser: Backend<'a>,
+	/// Token after the last consumed one, [`None`] at the end of input.
+	/// Errors are kept until the token is actually needed
+	next: std::result::Result<Option<(Token<'a>, Span)>, ParserError>,
+	/// Of blocks and functions currently open
+	depth: u8,
+
+	recover: bool,
+	/// Errors recovered from
+	diagnostics: Vec<Error<'a>>,
+}
+
+impl<'a> Builder<'a> {
+	#[inline]
+	fn new(input: &'a [u8], recover: bool) -> Self {
+		let mut builder = Self {
+			parser: Backend::new(input),
+			next: Ok(None),
+			depth: 0,
+
+			recover,
+			diagnostics: Vec::new(),
+		};
+
+		builder.next = builder.read();
+
+		builder
+	}
+
+	#[inline]
+	fn read(&mut self) -> std::result::Result<Option<(Token<'a>, Span)>, ParserError> {
+		match self.parser.next() {
+			Ok(token) => Ok(Some((
+				token,
+				Span::new(self.parser.token_start(), self.parser.pos()),
+			))),
+			Err(ParserError::EOF(_)) => Ok(None),
+			Err(err) => Err(err),
+		}
+	}
+
+	#[inline(always)]
+	fn peek(&self) -> Result<'a, Option<Token<'a>>> {
+		match self.next {
+			Ok(next) => Ok(next.map(|(token, _)| token)),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	/// Consumes next token, caller has to make sure there is one
+	#[inline]
+	fn bump(&mut self) -> Result<'a, (Token<'a>, Span)> {
+		let next = self.next?.expect("bump past the end of input");
+		self.next = self.read();
+
+		Ok(next)
+	}
+
+	/// Where next token, or error, starts
+	#[inline]
+	fn offset(&self) -> usize {
+		match self.next {
+			Ok(Some((_, span))) => span.start,
+			Ok(None) => self.parser.input().len(),
+			Err(err) => err.position().offset,
+		}
+	}
+
+	#[inline]
+	fn position(&self, offset: usize) -> Position {
+		Position::at(offset)
+	}
+
+	#[inline]
+	fn unexpected(&self) -> Error<'a> {
+		match self.next {
+			Ok(Some((token, span))) => Error::UnexpectedToken {
+				token,
+				position: self.position(span.start),
+			},
+			Ok(None) => Error::UnexpectedEOF(self.position(self.parser.input().len())),
+			Err(err) => err.into(),
+		}
+	}
+
+	#[inline]
+	fn enter(&mut self, offset: usize) -> Result<'a, ()> {
+		self.depth = self
+			.depth
+			.checked_add(1)
+			.ok_or_else(|| Error::TooManyLevelsOfIndentation(self.position(offset)))?;
+
+		Ok(())
+	}
+
+	fn stylesheet(&mut self) -> Result<'a, Stylesheet<'a>> {
+		let mut items = Vec::new();
+
+		loop {
+			match self.peek() {
+				Ok(None) => break,
+				// HTML comment tokens are only allowed, and ignored, at top level
+				Ok(Some(Token::Whitespace | Token::CDO | Token::CDC)) => {
+					self.bump()?;
+				}
+				// Item could start with an error as well, e.g. unterminated string
+				_ => items.push(self.recoverable(false)?),
+			}
+		}
+
+		Ok(Stylesheet { items })
+	}
+
+	/// `{` has to be consumed already
+	fn block(&mut self, open: Span) -> Result<'a, Block<'a>> {
+		self.enter(open.start)?;
+
+		let mut items = Vec::new();
+
+		let end = loop {
+			match self.peek() {
+				Ok(None) => return Err(self.unexpected()),
+				Ok(Some(Token::BracketCurlyClose)) => break self.bump()?.1.end,
+				Ok(Some(Token::Whitespace | Token::Semicolon)) => {
+					self.bump()?;
+				}
+				_ => items.push(self.recoverable(true)?),
+			}
+		};
+
+		self.depth -= 1;
+
+		Ok(Block {
+			items,
+			span: Span::new(open.start, end),
+		})
+	}
+
+	/// Same as [`Self::item`], but if recovering, failed item is kept as [`Item::Invalid`]
+	#[inline]
+	fn recoverable(&mut self, in_block: bool) -> Result<'a, Item<'a>> {
+		let start = self.offset();
+		let depth = self.depth;
+
+		let err = match self.item(in_block) {
+			Err(err) if self.recover => err,
+			res => return res,
+		};
+
+		self.depth = depth;
+
+		self.recover(start, in_block, err)
+	}
+
+	/// Skips input from `start` to the end of a failed item
+	#[inline]
+	fn recover(&mut self, start: usize, in_block: bool, err: Error<'a>) -> Result<'a, Item<'a>> {
+		let input = self.parser.input();
+		let (end, terminated) = self.skip_from(start, skip_for(input[start], in_block));
+
+		// Enclosing block is unterminated as well, let it recover instead
+		if !terminated && in_block {
+			return Err(err);
+		}
+
+		self.parser.seek(end);
+		self.next = self.read();
+
+		self.diagnostics.push(err);
+
+		Ok(Item::Invalid(Span::new(start, end)))
+	}
+
+	/// Re-parses input from `start` to the end of a declaration or a rule, keeping braces balanced.
+	///
+	/// Returns end offset of the skipped input and whether it was terminated before EOF
+	fn skip_from(&self, start: usize, skip: Skip) -> (usize, bool) {
+		let mut parser = self.parser.clone();
+		parser.seek(start);
+
+		let mut depth = 0usize;
+		// End of the last non whitespace token
+		let mut end = start;
+
+		let terminated = loop {
+			let token = match parser.next() {
+				Ok(token) => token,
+				Err(ParserError::EOF(_)) => break false,
+				// Unterminated comments run until EOF, and bad strings until newline
+				Err(ParserError::BadComment(_) | ParserError::BadString(_)) => {
+					end = parser.pos();
+					continue;
+				}
+			};
+
+			match token {
+				Token::Whitespace => continue,
+
+				Token::BracketCurlyOpen => depth += 1,
+
+				// Closing `}` of enclosing block, or a stray one
+				Token::BracketCurlyClose if depth == 0 => {
+					if parser.token_start() == start {
+						end = parser.pos();
+					}
+
+					break true;
+				}
+
+				Token::BracketCurlyClose => {
+					depth -= 1;
+
+					if depth == 0 && skip == Skip::Rule {
+						end = parser.pos();
+						break true;
+					}
+				}
+
+				Token::Semicolon if depth == 0 => {
+					end = parser.pos();
+					break true;
+				}
+
+				_ => {}
+			}
+
+			end = parser.pos();
+		};
+
+		let end = if terminated { end } else { parser.input().len() };
+
+		(end, terminated)
+	}
+
+	/// Declarations are only allowed `in_block`
+	fn item(&mut self, in_block: bool) -> Result<'a, Item<'a>> {
+		match self.peek()? {
+			Some(Token::Comment(text)) => {
+				let (_, span) = self.bump()?;
+				Ok(Item::Comment(Comment { text, span }))
+			}
+			Some(Token::AtRule(_)) => self.at_rule().map(Item::AtRule),
+			// Stray at top level, blocks skip `;` and end with `}` before getting here
+			Some(Token::Semicolon | Token::BracketCurlyClose) => Err(self.unexpected()),
+			_ => self.rule_or_declaration(in_block),
+		}
+	}
+
+	fn at_rule(&mut self) -> Result<'a, AtRule<'a>> {
+		let (Token::AtRule(name), span) = self.bump()? else {
+			unreachable!("at-rule starts with at-keyword")
+		};
+
+		let mut prelude = Vec::new();
+		let mut end = span.end;
+		let mut block = None;
+
+		loop {
+			match self.peek()? {
+				None | Some(Token::BracketCurlyClose) => break,
+				Some(Token::Semicolon) => {
+					end = self.bump()?.1.end;
+					break;
+				}
+				Some(Token::BracketCurlyOpen) => {
+					let (_, open) = self.bump()?;
+					let inner = self.block(open)?;
+					end = inner.span.end;
+					block = Some(inner);
+					break;
+				}
+				Some(_) => {
+					let value = self.component_value()?;
+					end = value.span().end;
+					prelude.push(value);
+				}
+			}
+		}
+
+		trim(&mut prelude);
+
+		Ok(AtRule {
+			name,
+			prelude,
+			block,
+			span: Span::new(span.start, end),
+		})
+	}
+
+	/// Collects component values up to `{`, making it a qualified rule,
+	/// or up to `;`, `}` or end of input, making it a declaration
+	fn rule_or_declaration(&mut self, in_block: bool) -> Result<'a, Item<'a>> {
+		let start = self.offset();
+		let mut values = Vec::new();
+
+		loop {
+			match self.peek()? {
+				None | Some(Token::Semicolon | Token::BracketCurlyClose) => break,
+				Some(Token::BracketCurlyOpen) => {
+					let (_, open) = self.bump()?;
+					let block = self.block(open)?;
+					trim(&mut values);
+
+					return Ok(Item::QualifiedRule(QualifiedRule {
+						prelude: values,
+						span: Span::new(start, block.span.end),
+						block,
+					}));
+				}
+				Some(_) => values.push(self.component_value()?),
+			}
+		}
+
+		trim(&mut values);
+
+		let name = match values.first() {
+			Some(&ComponentValue::Token {
+				token: Token::Ident(name),
+				span,
+			}) if in_block => {
+				let colon = values
+					.iter()
+					.skip(1)
+					.position(|value| !value.is_whitespace());
+
+				match colon.map(|i| values[i + 1].token()) {
+					Some(Some(Token::Colon)) => {}
+					_ => {
+						return Err(Error::UnexpectedToken {
+							token: Token::Ident(name),
+							position: self.position(span.start),
+						})
+					}
+				}
+
+				name
+			}
+			_ => return Err(self.unexpected()),
+		};
+
+		let colon = values
+			.iter()
+			.position(|value| value.token() == Some(Token::Colon))
+			.unwrap();
+		let mut end = values.last().map_or(start, |value| value.span().end);
+
+		if self.peek()? == Some(Token::Semicolon) {
+			end = self.bump()?.1.end;
+		}
+
+		let mut value = values.split_off(colon + 1);
+		trim(&mut value);
+
+		Ok(Item::Declaration(Declaration {
+			name,
+			value,
+			span: Span::new(start, end),
+		}))
+	}
+
+	fn component_value(&mut self) -> Result<'a, ComponentValue<'a>> {
+		let (token, span) = self.bump()?;
+
+		let bracket = match token {
+			Token::Function(name) => {
+				let (arguments, end) = self.nested(span, Token::BracketRoundClose)?;
+
+				return Ok(ComponentValue::Function(Function {
+					name: &name[..name.len() - 1],
+					arguments,
+					span: Span::new(span.start, end),
+				}));
+			}
+			Token::BracketRoundOpen => Bracket::Round,
+			Token::BracketSquareOpen => Bracket::Square,
+			Token::BracketCurlyOpen => Bracket::Curly,
+			token => return Ok(ComponentValue::Token { token, span }),
+		};
+
+		let (values, end) = self.nested(span, bracket.tokens().1)?;
+
+		Ok(ComponentValue::Block(SimpleBlock {
+			bracket,
+			values,
+			span: Span::new(span.start, end),
+		}))
+	}
+
+	/// Values up to `close`, returns them along with the end of `close`.
+	/// Stray `}` would close a rule block, so it is an error, unlike stray `)` or `]`
+	fn nested(
+		&mut self,
+		open: Span,
+		close: Token,
+	) -> Result<'a, (Vec<ComponentValue<'a>>, usize)> {
+		self.enter(open.start)?;
+
+		let mut values = Vec::new();
+
+		let end = loop {
+			match self.peek()? {
+				Some(token) if token == close => break self.bump()?.1.end,
+				None | Some(Token::BracketCurlyClose) => return Err(self.unexpected()),
+				Some(_) => values.push(self.component_value()?),
+			}
+		};
+
+		self.depth -= 1;
+
+		Ok((values, end))
+	}
+}
+
+/// Removes leading and trailing whitespace
+#[inline]
+fn trim(values: &mut Vec<ComponentValue>) {
+	let end = values
+		.iter()
+		.rposition(|value| !value.is_whitespace())
+		.map_or(0, |i| i + 1);
+	values.truncate(end);
+
+	let start = values
+		.iter()
+		.position(|value| !value.is_whitespace())
+		.unwrap_or(0);
+	values.drain(..start);
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,548 @@
+use std::path::Path;
+
+use ::css::visit::{self, Visit, VisitMut};
+use ::css::{Bracket, ComponentValue, Declaration, Item, Span, Stylesheet, Token, TokenBuf};
+
+use super::{parse, Error};
+use crate::css::{format_tree, OwnedError, Session};
+use crate::FormatOptions;
+
+const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/css");
+
+/// Input and a check its parse error has to pass
+type ErrorCase = (&'static str, fn(&Error) -> bool);
+
+fn tree(input: &str) -> Stylesheet<'_> {
+	match parse(input.as_bytes()) {
+		Ok(tree) => tree,
+		Err(err) => panic!("{input:?} failed to parse: {err:?}"),
+	}
+}
+
+fn source(input: &str, span: Span) -> &str {
+	&input[span.start..span.end]
+}
+
+/// Source of every value, whitespace as `" "`
+fn values<'a>(input: &'a str, values: &[ComponentValue]) -> Vec<&'a str> {
+	values
+		.iter()
+		.map(|value| match value.is_whitespace() {
+			true => " ",
+			false => source(input, value.span()),
+		})
+		.collect()
+}
+
+#[test]
+fn empty() {
+	assert_eq!(tree("").items, []);
+	assert_eq!(tree(" \n\t").items, []);
+	assert_eq!(tree("<!-- -->").items, []);
+}
+
+#[test]
+fn qualified_rule() {
+	let input = "a:hover, .b > c  {\n\tcolor: red;\n\tmargin : 0 auto !important\n}";
+	let [Item::QualifiedRule(rule)] = &tree(input).items[..] else {
+		panic!("one rule expected");
+	};
+
+	assert_eq!(rule.span, Span::new(0, input.len()));
+	assert_eq!(source(input, rule.block.span), &input[17..]);
+	assert_eq!(
+		values(input, &rule.prelude),
+		["a", ":", "hover", ",", " ", ".", "b", " ", ">", " ", "c"]
+	);
+
+	let [Item::Declaration(color), Item::Declaration(margin)] = &rule.block.items[..] else {
+		panic!("two declarations expected");
+	};
+
+	assert_eq!(color.name, b"color");
+	assert_eq!(source(input, color.span), "color: red;");
+	assert_eq!(values(input, &color.value), ["red"]);
+	assert!(!color.is_important());
+
+	assert_eq!(margin.name, b"margin");
+	assert_eq!(source(input, margin.span), "margin : 0 auto !important");
+	assert_eq!(
+		values(input, &margin.value),
+		["0", " ", "auto", " ", "!", "important"]
+	);
+	assert!(margin.is_important());
+}
+
+#[test]
+fn nested_rule_or_declaration() {
+	let input = "a { color: red; b:hover { top: 0 } &:focus{} --x: 1 }";
+	let [Item::QualifiedRule(rule)] = &tree(input).items[..] else {
+		panic!("one rule expected");
+	};
+
+	let [
+		Item::Declaration(color),
+		Item::QualifiedRule(hover),
+		Item::QualifiedRule(focus),
+		Item::Declaration(custom),
+	] = &rule.block.items[..]
+	else {
+		panic!(
+			"declaration, two rules and declaration expected, got {:#?}",
+			rule.block.items
+		);
+	};
+
+	assert_eq!(color.name, b"color");
+	assert_eq!(source(input, hover.span), "b:hover { top: 0 }");
+	assert!(matches!(hover.block.items[..], [Item::Declaration(_)]));
+	assert_eq!(source(input, focus.span), "&:focus{}");
+	assert_eq!(focus.block.items, []);
+	assert_eq!(custom.name, b"--x");
+	assert_eq!(source(input, custom.span), "--x: 1");
+}
+
+#[test]
+fn functions_and_blocks() {
+	let input = "a { width: calc(100% - var(--x, 1px)); grid-area: [a] (b) }";
+	let [Item::QualifiedRule(rule)] = &tree(input).items[..] else {
+		panic!("one rule expected");
+	};
+	let [Item::Declaration(width), Item::Declaration(grid)] = &rule.block.items[..] else {
+		panic!("two declarations expected");
+	};
+
+	let [ComponentValue::Function(calc)] = &width.value[..] else {
+		panic!("one function expected");
+	};
+
+	assert_eq!(calc.name, b"calc");
+	assert_eq!(source(input, calc.span), "calc(100% - var(--x, 1px))");
+
+	let Some(ComponentValue::Function(var)) = calc.arguments.last() else {
+		panic!("var() expected last");
+	};
+
+	assert_eq!(var.name, b"var");
+	assert_eq!(values(input, &var.arguments), ["--x", ",", " ", "1px"]);
+
+	let [ComponentValue::Block(square), _, ComponentValue::Block(round)] = &grid.value[..] else {
+		panic!("two blocks expected");
+	};
+
+	assert_eq!(square.bracket, Bracket::Square);
+	assert_eq!(source(input, square.span), "[a]");
+	assert_eq!(round.bracket, Bracket::Round);
+	assert_eq!(values(input, &round.values), ["b"]);
+}
+
+#[test]
+fn at_rules() {
+	let input = concat!(
+		"@charset \"utf-8\";\n",
+		"@import url(a.css) screen;\n",
+		"@media (width > 1px) {\n\ta { top: 0 }\n}\n",
+		"@font-face{src:url(b)}",
+	);
+	let items = tree(input).items;
+
+	let [Item::AtRule(charset), Item::AtRule(import), Item::AtRule(media), Item::AtRule(font)] =
+		&items[..]
+	else {
+		panic!("four at-rules expected, got {items:#?}");
+	};
+
+	assert_eq!(charset.name, b"@charset");
+	assert_eq!(source(input, charset.span), "@charset \"utf-8\";");
+	assert_eq!(
+		charset.prelude[..],
+		[ComponentValue::Token {
+			token: Token::String(b"utf-8"),
+			span: Span::new(9, 16),
+		}]
+	);
+	assert_eq!(charset.block, None);
+
+	assert_eq!(
+		values(input, &import.prelude),
+		["url(a.css)", " ", "screen"]
+	);
+	assert_eq!(import.block, None);
+
+	assert_eq!(media.name, b"@media");
+	let [ComponentValue::Block(query)] = &media.prelude[..] else {
+		panic!("one block expected");
+	};
+	assert_eq!(
+		values(input, &query.values),
+		["width", " ", ">", " ", "1px"]
+	);
+
+	let block = media.block.as_ref().unwrap();
+	assert!(matches!(block.items[..], [Item::QualifiedRule(_)]));
+	assert_eq!(
+		source(input, media.span),
+		"@media (width > 1px) {\n\ta { top: 0 }\n}"
+	);
+
+	// Font-face declarations parse the same way as in qualified rules
+	let block = font.block.as_ref().unwrap();
+	assert!(matches!(&block.items[..], [Item::Declaration(src)] if src.name == b"src"));
+}
+
+#[test]
+fn comments() {
+	let input = "/* top */\na /* prelude */ {\n\t/* inside */\n\tcolor: red /* value */;\n}";
+	let items = tree(input).items;
+
+	let [Item::Comment(top), Item::QualifiedRule(rule)] = &items[..] else {
+		panic!("comment and rule expected, got {items:#?}");
+	};
+
+	assert_eq!(top.text, b" top ");
+	assert_eq!(top.span, Span::new(0, 9));
+	assert_eq!(values(input, &rule.prelude), ["a", " ", "/* prelude */"]);
+
+	let [Item::Comment(inside), Item::Declaration(color)] = &rule.block.items[..] else {
+		panic!("comment and declaration expected");
+	};
+
+	assert_eq!(inside.text, b" inside ");
+	assert_eq!(values(input, &color.value), ["red", " ", "/* value */"]);
+}
+
+#[test]
+fn errors() {
+	let cases: &[ErrorCase] = &[
+		("a { color: red", |err| {
+			matches!(err, Error::UnexpectedEOF(_))
+		}),
+		("a { color: rgb(0 0 0 }", |err| {
+			matches!(
+				err,
+				Error::UnexpectedToken {
+					token: Token::BracketCurlyClose,
+					..
+				}
+			)
+		}),
+		("@media screen { a { top: 0 }", |err| {
+			matches!(err, Error::UnexpectedEOF(_))
+		}),
+		("}", |err| {
+			matches!(
+				err,
+				Error::UnexpectedToken {
+					token: Token::BracketCurlyClose,
+					..
+				}
+			)
+		}),
+		(";", |err| {
+			matches!(
+				err,
+				Error::UnexpectedToken {
+					token: Token::Semicolon,
+					..
+				}
+			)
+		}),
+		("color: red;", |err| {
+			matches!(
+				err,
+				Error::UnexpectedToken {
+					token: Token::Semicolon,
+					..
+				}
+			)
+		}),
+		("a { b c }", |err| {
+			matches!(
+				err,
+				Error::UnexpectedToken {
+					token: Token::Ident(b"b"),
+					..
+				}
+			)
+		}),
+		("a { 1px: 0 }", |err| {
+			matches!(
+				err,
+				Error::UnexpectedToken {
+					token: Token::BracketCurlyClose,
+					..
+				}
+			)
+		}),
+		("a { content: 'b\n }", |err| {
+			matches!(err, Error::BadString(_))
+		}),
+		("a { /* b }", |err| matches!(err, Error::BadComment(_))),
+	];
+
+	for (input, is_expected) in cases {
+		match parse(input.as_bytes()) {
+			Ok(tree) => panic!("{input:?} should fail, got {tree:#?}"),
+			Err(err) => assert!(is_expected(&err), "{input:?} failed with {err:?}"),
+		}
+	}
+
+	let Err(Error::UnexpectedUTF8(position)) = parse(b"a { b: \xff }") else {
+		panic!("invalid UTF-8 should fail");
+	};
+	assert_eq!(position.offset, 7);
+
+	let deep = format!("a {{ b: {}{} }}", "(".repeat(300), ")".repeat(300));
+	assert!(matches!(
+		parse(deep.as_bytes()),
+		Err(Error::TooManyLevelsOfIndentation(_))
+	));
+}
+
+#[test]
+fn owned_errors() {
+	let tokens = [
+		Token::Ident(b"color"),
+		Token::Dimension {
+			value: b"1.5",
+			unit: b"em",
+		},
+		Token::Dimension {
+			value: b"",
+			unit: b"",
+		},
+		Token::Delim(b'>'),
+		Token::Semicolon,
+	];
+
+	for token in tokens {
+		let owned = TokenBuf::from(token);
+
+		assert_eq!(owned.token(), token);
+		assert_eq!(format!("{owned:?}"), format!("{token:?}"));
+	}
+
+	// Outlives the input
+	let err = {
+		let input = String::from("a { b c }");
+		parse(input.as_bytes()).unwrap_err().into_owned()
+	};
+
+	let OwnedError::UnexpectedToken { token, position } = err else {
+		panic!("unexpected token error");
+	};
+	assert_eq!(token.token(), Token::Ident(b"b"));
+	assert_eq!(position.offset, 4);
+}
+
+#[test]
+fn visit() {
+	#[derive(Default)]
+	struct Names<'a> {
+		properties: Vec<&'a [u8]>,
+		at_rules: Vec<&'a [u8]>,
+		selectors: usize,
+		functions: Vec<&'a [u8]>,
+	}
+
+	impl<'a> Visit<'a> for Names<'a> {
+		fn visit_declaration(&mut self, declaration: &Declaration<'a>) {
+			self.properties.push(declaration.name);
+			visit::walk_declaration(self, declaration);
+		}
+
+		fn visit_at_rule(&mut self, rule: &::css::AtRule<'a>) {
+			self.at_rules.push(rule.name);
+			visit::walk_at_rule(self, rule);
+		}
+
+		fn visit_selector(&mut self, selector: &[ComponentValue<'a>]) {
+			self.selectors += 1;
+			visit::walk_values(self, selector);
+		}
+
+		fn visit_component_value(&mut self, value: &ComponentValue<'a>) {
+			if let ComponentValue::Function(function) = value {
+				self.functions.push(function.name);
+			}
+
+			visit::walk_component_value(self, value);
+		}
+	}
+
+	let input = concat!(
+		"a:is(.b) { color: rgb(0 var(--c) 0); }\n",
+		"@media (width > 1px) { d { top: 0; &:hover { left: calc(1px) } } }",
+	);
+
+	let mut names = Names::default();
+	names.visit_stylesheet(&tree(input));
+
+	assert_eq!(names.properties, [&b"color"[..], b"top", b"left"]);
+	assert_eq!(names.at_rules, [b"@media"]);
+	assert_eq!(names.selectors, 3);
+	assert_eq!(names.functions, [&b"is"[..], b"rgb", b"var", b"calc"]);
+}
+
+#[test]
+fn visit_mut() {
+	/// Renames `--old-*` design tokens and drops `zoom`
+	struct Codemod;
+
+	impl<'a> VisitMut<'a> for Codemod {
+		fn visit_items_mut(&mut self, items: &mut Vec<Item<'a>>) {
+			items.retain(|item| !matches!(item, Item::Declaration(d) if d.name == b"zoom"));
+			visit::walk_items_mut(self, items);
+		}
+
+		fn visit_declaration_mut(&mut self, declaration: &mut Declaration<'a>) {
+			if let Some(name) = declaration.name.strip_prefix(b"--old-") {
+				declaration.name = [b"--new-", name].concat().leak();
+			}
+
+			visit::walk_declaration_mut(self, declaration);
+		}
+
+		fn visit_component_value_mut(&mut self, value: &mut ComponentValue<'a>) {
+			if let ComponentValue::Token {
+				token: Token::Ident(name),
+				..
+			} = value
+			{
+				if let Some(rest) = name.strip_prefix(b"--old-") {
+					*name = [b"--new-", rest].concat().leak();
+				}
+			}
+
+			visit::walk_component_value_mut(self, value);
+		}
+	}
+
+	let input = "a{zoom:2;--old-gap:1px;margin:var( --old-gap )}@media print{b{zoom:1}}";
+	let mut stylesheet = tree(input);
+	Codemod.visit_stylesheet_mut(&mut stylesheet);
+
+	// Items added by hand have no span
+	let Item::QualifiedRule(rule) = &mut stylesheet.items[0] else {
+		panic!("rule expected");
+	};
+	rule.block.items.push(Item::Declaration(Declaration {
+		name: b"color",
+		value: vec![ComponentValue::Token {
+			token: Token::Ident(b"red"),
+			span: Span::default(),
+		}],
+		span: Span::default(),
+	}));
+
+	let mut session = Session::default();
+	let mut output = Vec::new();
+	let options = FormatOptions::default();
+	let result = format_tree(&mut session, &stylesheet, input.as_bytes(), &mut output, options);
+
+	assert!(result.is_ok_and(|diagnostics| diagnostics.is_empty()));
+	assert_eq!(
+		String::from_utf8(output).unwrap(),
+		concat!(
+			"a {\n\tmargin: var(--new-gap);\n\n\tcolor: red;\n\n\t--new-gap: 1px;\n}\n\n",
+			"@media print {\n\tb {\n\t}\n}\n",
+		)
+	);
+}
+
+#[test]
+fn visit_mut_utf8() {
+	/// Sets names of declarations and functions to bytes that are not UTF-8
+	struct Garble;
+
+	impl<'a> VisitMut<'a> for Garble {
+		fn visit_declaration_mut(&mut self, declaration: &mut Declaration<'a>) {
+			if declaration.name == b"color" {
+				declaration.name = b"-\xff\xfe";
+			}
+
+			visit::walk_declaration_mut(self, declaration);
+		}
+
+		fn visit_component_value_mut(&mut self, value: &mut ComponentValue<'a>) {
+			if let ComponentValue::Function(function) = value {
+				function.name = b"\xc3";
+			}
+
+			visit::walk_component_value_mut(self, value);
+		}
+	}
+
+	let cases = [
+		("a{color:red}", (2, 1, 3)),
+		("a{\n\ttop: rgb(0 0 0)\n}", (9, 2, 7)),
+	];
+
+	for (input, expected) in cases {
+		let mut stylesheet = tree(input);
+		Garble.visit_stylesheet_mut(&mut stylesheet);
+
+		let mut session = Session::default();
+		let mut output = Vec::new();
+		let options = FormatOptions::default();
+		let result = format_tree(&mut session, &stylesheet, input.as_bytes(), &mut output, options);
+
+		let Err(Error::UnexpectedUTF8(position)) = result else {
+			panic!("{input:?} has to fail on UTF-8, got {result:?}");
+		};
+		assert_eq!((position.offset, position.line, position.column), expected, "{input:?}");
+	}
+}
+
+#[test]
+fn fixtures() {
+	/// HTML comment tokens are dropped at top level
+	fn is_blank(gap: &str) -> bool {
+		gap.replace("<!--", "").replace("-->", "").trim().is_empty()
+	}
+
+	fn visit(dir: &Path, count: &mut usize) {
+		for entry in std::fs::read_dir(dir).expect("fixtures directory") {
+			let path = entry.expect("readable entry").path();
+
+			if path.is_dir() {
+				visit(&path, count);
+				continue;
+			}
+
+			if path.file_name().is_none_or(|name| name != "before.css") {
+				continue;
+			}
+
+			let input = std::fs::read_to_string(&path).unwrap();
+			let tree = match parse(input.as_bytes()) {
+				Ok(tree) => tree,
+				Err(err) => panic!("{} failed to parse: {err:?}", path.display()),
+			};
+
+			// Top level items are ordered and do not overlap, nothing but whitespace is left out
+			let mut end = 0;
+			for item in &tree.items {
+				let span = item.span();
+
+				assert!(
+					span.start >= end && span.end > span.start,
+					"{}",
+					path.display()
+				);
+				assert!(is_blank(&input[end..span.start]), "{}", path.display());
+
+				end = span.end;
+			}
+
+			assert!(is_blank(&input[end..]), "{}", path.display());
+
+			*count += 1;
+		}
+	}
+
+	let mut count = 0;
+	visit(Path::new(FIXTURES_DIR), &mut count);
+
+	assert!(count > 0, "no fixtures found in {FIXTURES_DIR}");
+}
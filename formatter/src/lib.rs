@@ -4,34 +4,101 @@
 #![feature(once_cell_get_mut)]
 #![feature(specialization)]
 #![feature(test)]
-#![feature(thread_local)]
 #![feature(variant_count)]
 #![feature(portable_simd)]
 #![feature(debug_closure_helpers)]
 
-use crate::css::Error as CSSError;
+use crate::css::{Error as CSSError, OwnedError};
+
+pub use css::{Position, Session};
+/// Differential check of the scalar parser against `tokenizer` crate, used by fuzz targets
+#[cfg(feature = "simd-tokenizer")]
+#[doc(hidden)]
+pub use css::{compare_tokenizers, Divergence};
+pub use options::{EndOfLine, FormatOptions, IndentStyle};
+pub use source_map::{Mapping, SourceMap};
+/// Typed syntax tree returned by [`parse`]
+pub use ::css as tree;
 
 #[allow(clippy::upper_case_acronyms)]
 pub enum Error {
 	NoInput,
 	EmptyInput,
-	CSS(CSSError<'static>),
+	/// Missing or invalid value of a CLI flag
+	BadArgument(String),
+	CSS(OwnedError),
 	IO(std::io::Error),
 }
 
+/// Returns errors that were recovered from, see [`FormatOptions::recover`]
 pub fn format<'a, S: AsRef<[u8]> + 'a>(
 	input: S,
 	output: &'a mut impl std::io::Write,
-) -> Result<(), Error> {
+	options: FormatOptions,
+) -> Result<Vec<Error>, Error> {
+	format_with_session(&mut Session::default(), input, output, options)
+}
+
+/// Same as [`format`], but reuses allocations of `session` between calls
+pub fn format_with_session<'a, S: AsRef<[u8]> + 'a>(
+	session: &'a mut Session,
+	input: S,
+	output: &'a mut impl std::io::Write,
+	options: FormatOptions,
+) -> Result<Vec<Error>, Error> {
 	let input = input.as_ref();
 
 	if input.is_empty() {
 		return Err(Error::EmptyInput);
 	};
 
-	css::format(input, output)?;
+	let diagnostics = css::format(session, input, output, options)?;
+
+	Ok(diagnostics.into_iter().map(Error::from).collect())
+}
+
+/// Formats `stylesheet` built by [`parse`] out of `input`, e.g. after changing it with
+/// [`tree::visit::VisitMut`]. `input` is only read for error positions and for items copied
+/// as is, see [`FormatOptions::recover`].
+///
+/// Bytes put into the tree have to be UTF-8, as the input has, nodes that are not fail the run
+pub fn format_tree<'a>(
+	stylesheet: &tree::Stylesheet<'a>,
+	input: &'a [u8],
+	output: &'a mut impl std::io::Write,
+	options: FormatOptions,
+) -> Result<Vec<Error>, Error> {
+	format_tree_with_session(&mut Session::default(), stylesheet, input, output, options)
+}
 
-	Ok(())
+/// Same as [`format_tree`], but reuses allocations of `session` between calls
+pub fn format_tree_with_session<'a>(
+	session: &'a mut Session,
+	stylesheet: &tree::Stylesheet<'a>,
+	input: &'a [u8],
+	output: &'a mut impl std::io::Write,
+	options: FormatOptions,
+) -> Result<Vec<Error>, Error> {
+	let diagnostics = css::format_tree(session, stylesheet, input, output, options)?;
+
+	Ok(diagnostics.into_iter().map(Error::from).collect())
+}
+
+/// Builds syntax tree of `input`, never recovers from errors
+#[inline]
+pub fn parse(input: &[u8]) -> Result<tree::Stylesheet<'_>, Error> {
+	css::parse(input).map_err(Error::from)
+}
+
+impl Error {
+	/// Where in the input error occurred, if applicable
+	#[inline]
+	pub fn position(&self) -> Option<Position> {
+		match self {
+			Error::CSS(err) => err.position(),
+			_ => None,
+		}
+	}
 }
 
 impl std::process::Termination for Error {
@@ -44,7 +111,7 @@ impl<'a> From<CSSError<'a>> for Error {
 	fn from(value: CSSError<'a>) -> Self {
 		match value {
 			CSSError::IO(err) => Self::IO(err),
-			err => Self::CSS(err.leak()),
+			err => Self::CSS(err.into_owned()),
 		}
 	}
 }
@@ -60,6 +127,7 @@ impl std::fmt::Debug for Error {
 		match self {
 			Error::NoInput => f.write_str("No input argument been provided after \"--input\" flag!"),
 			Error::EmptyInput => f.write_str("Input is empty"),
+			Error::BadArgument(flag) => write!(f, "Missing or invalid value after \"{flag}\" flag!"),
 			Error::CSS(err) => write!(f, "{err:?}"),
 			Error::IO(err) => write!(f, "{err:?}"),
 		}
@@ -67,4 +135,6 @@ impl std::fmt::Debug for Error {
 }
 
 pub(crate) mod css;
+mod options;
+mod source_map;
 mod utils;
@@ -3,43 +3,114 @@ fn BigEndian() {
 	compile_error!("Big Endian is not supported")
 }
 
-use std::io::Read;
+use std::io::{Read, Write};
 
-use hel_css_formatter::format;
+use hel_css_formatter::format_with_session;
 use hel_css_formatter::Error;
+use hel_css_formatter::FormatOptions;
+use hel_css_formatter::Position;
+use hel_css_formatter::Session;
 
 fn main() -> Result<(), Error> {
-	let mut args = std::env::args();
+	let mut args = std::env::args().skip(1);
 
 	let mut writer = std::io::BufWriter::new(std::io::stdout());
 
-	for arg in args.by_ref() {
-		if arg == "--input" {
-			let Some(input) = args.next() else {
-				return Err(Error::NoInput);
+	let mut options = FormatOptions::default();
+	let mut input = None;
+	let mut source_map: Option<String> = None;
+	let mut source_name = String::from("input.css");
+
+	while let Some(arg) = args.next() {
+		macro_rules! value {
+			() => {
+				args
+					.next()
+					.and_then(|value| value.parse().ok())
+					.ok_or_else(|| Error::BadArgument(arg.clone()))?
 			};
+		}
 
-			format(input, &mut writer)?;
+		match arg.as_str() {
+			"--input" => input = Some(args.next().ok_or(Error::NoInput)?),
 
-			return Ok(());
+			"--indent-style" => options.indent_style = value!(),
+			"--indent-width" => options.indent_width = value!(),
+			"--print-width" => options.print_width = value!(),
+			"--tab-width" => options.tab_width = value!(),
+			"--end-of-line" => options.end_of_line = value!(),
+			"--final-newline" => options.final_newline = true,
+			"--no-final-newline" => options.final_newline = false,
+			"--recover" => options.recover = true,
+			"--minify" => options.minify = true,
+			"--single-line-rules" => options.single_line_rules = value!(),
+			"--sort-boundaries" => options.sort_boundaries = true,
+			"--strip-comments" => options.strip_comments = true,
+			"--source-map" => source_map = Some(value!()),
+			"--source-name" => source_name = value!(),
+
+			_ => return Err(Error::BadArgument(arg)),
 		}
 	}
 
+	options.source_map = source_map.is_some();
+
+	let mut session = Session::default();
+	// Written next to the output after every successful run
+	let write_source_map = |session: &Session, writer: &mut dyn Write| -> Result<(), Error> {
+		let Some(path) = &source_map else {
+			return Ok(());
+		};
+
+		let file = path.strip_suffix(".map").unwrap_or(path);
+		std::fs::write(path, session.source_map().to_json(file, &source_name))?;
+
+		writeln!(writer, "/*# sourceMappingURL={path} */")?;
+
+		Ok(())
+	};
+
+	if let Some(input) = input {
+		return match format_with_session(&mut session, &input, &mut writer, options) {
+			Ok(diagnostics) => {
+				for err in diagnostics {
+					report(input.as_bytes(), &err);
+				}
+
+				write_source_map(&session, &mut writer)
+			}
+			Err(err @ Error::CSS(_)) => {
+				report(input.as_bytes(), &err);
+				std::process::exit(1);
+			}
+			Err(err) => Err(err),
+		};
+	}
+
 	loop {
-		let mut input = String::new();
+		let mut input = Vec::new();
 
 		// TODO: change to `[u8; 8]` stream
-		std::io::stdin().read_to_string(&mut input)?;
+		std::io::stdin().read_to_end(&mut input)?;
+
+		let err = match format_with_session(&mut session, &input, &mut writer, options) {
+			Ok(diagnostics) => {
+				for err in diagnostics {
+					report(&input, &err);
+				}
+
+				write_source_map(&session, &mut writer)?;
 
-		let Err(err) = format(input, &mut writer) else {
-			continue;
+				continue;
+			}
+			Err(err) => err,
 		};
 
 		match err {
 			Error::EmptyInput => break,
-			Error::CSS(err) => {
-				eprintln!("{err:?}");
-				panic!();
+			Error::CSS(_) => {
+				report(&input, &err);
+				std::process::exit(1);
 			}
 			Error::IO(_) => todo!(),
 			_ => unreachable!(),
@@ -48,3 +119,92 @@ fn main() -> Result<(), Error> {
 
 	Ok(())
 }
+
+/// Prints an error followed by the offending line of input, if error has a position
+fn report(input: &[u8], err: &Error) {
+	eprintln!("{err:?}");
+
+	if let Some(position) = err.position() {
+		eprint!("{}", code_frame(input, position));
+	}
+}
+
+/// Line of input `position` is on, with a caret under it
+fn code_frame(input: &[u8], position: Position) -> String {
+	let Position { offset, line, .. } = position;
+
+	let start = input[..offset]
+		.iter()
+		.rposition(|&byte| byte == b'\n')
+		.map_or(0, |i| i + 1);
+	let end = input[offset..]
+		.iter()
+		.position(|&byte| byte == b'\n')
+		.map_or(input.len(), |i| offset + i);
+
+	let source = String::from_utf8_lossy(&input[start..end]);
+	// Keep tabs, so caret lines up with the source
+	let padding: String = String::from_utf8_lossy(&input[start..offset])
+		.chars()
+		.map(|ch| if ch == '\t' { '\t' } else { ' ' })
+		.collect();
+
+	let gutter = " ".repeat(line.to_string().len());
+
+	format!(
+		"{gutter} |\n{line} | {}\n{gutter} | {padding}^\n",
+		source.trim_end_matches('\r')
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use hel_css_formatter::{format, FormatOptions};
+
+	use super::code_frame;
+
+	/// Code frames of errors `input` is recovered from
+	fn frames(input: &str) -> Vec<String> {
+		let options = FormatOptions {
+			recover: true,
+			..FormatOptions::DEFAULT
+		};
+
+		format(input, &mut Vec::new(), options)
+			.expect("recovers")
+			.iter()
+			.map(|err| code_frame(input.as_bytes(), err.position().expect("has position")))
+			.collect()
+	}
+
+	#[test]
+	fn code_frames() {
+		let cases = [
+			("a{color}", vec!["  |\n1 | a{color}\n  |   ^\n"]),
+			// Tabs are kept in the caret line
+			("a{\n\tb:c;\n\t\tcolor}", vec!["  |\n3 | \t\tcolor}\n  | \t\t^\n"]),
+			// `\r` is left out of the source line
+			(
+				"a{\r\n\tb:c;\r\n\tcolor;\r\n}",
+				vec!["  |\n3 | \tcolor;\n  | \t^\n"],
+			),
+			// Wide gutter, a space per character
+			(
+				"\n\n\n\n\n\n\n\n\n/* ü */a{color}",
+				vec!["   |\n10 | /* ü */a{color}\n   |          ^\n"],
+			),
+			(
+				"a{color}\nb{x}\nc{y}",
+				vec![
+					"  |\n1 | a{color}\n  |   ^\n",
+					"  |\n2 | b{x}\n  |   ^\n",
+					"  |\n3 | c{y}\n  |   ^\n",
+				],
+			),
+		];
+
+		for (input, expected) in cases {
+			assert_eq!(frames(input), expected, "{input:?}");
+		}
+	}
+}
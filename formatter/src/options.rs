@@ -0,0 +1,119 @@
+/// Indentation character
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndentStyle {
+	#[default]
+	Tabs,
+	Spaces,
+}
+
+/// Line ending written after every line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EndOfLine {
+	#[default]
+	Lf,
+	CrLf,
+}
+
+/// Options accepted by [`crate::format`]
+///
+/// [`Default`] matches the formatter's opinion: tabs, 80 columns, `\n`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatOptions {
+	pub indent_style: IndentStyle,
+	/// Amount of spaces per indentation level, ignored for [`IndentStyle::Tabs`]
+	pub indent_width: u8,
+	/// Lines longer than this are split into multiple lines
+	pub print_width: usize,
+	/// Amount of columns a tab takes when measuring a line against `print_width`.
+	/// `0` leaves tab indentation out of the measurement
+	pub tab_width: u8,
+	/// End output with a single newline
+	pub final_newline: bool,
+	pub end_of_line: EndOfLine,
+	/// Copy rules and declarations that could not be formatted as is,
+	/// instead of failing on the first error
+	pub recover: bool,
+	/// Map output back to the input, see [`crate::Session::source_map`].
+	/// What [`Self::recover`] copies as is is left unmapped
+	pub source_map: bool,
+	/// Leave out optional whitespace, the last `;` of blocks and comments,
+	/// but `/*! ... */` license ones. Indentation and `print_width` are ignored
+	pub minify: bool,
+	/// Rules with at most this many declarations stay on a single line,
+	/// e.g. `a { color: red; }`, if it fits `print_width`. `0` turns it off
+	pub single_line_rules: usize,
+	/// Blank lines and standalone comments between declarations split them
+	/// into sections, each sorted on its own
+	pub sort_boundaries: bool,
+	/// Leave out comments, but `/*! ... */` license ones
+	pub strip_comments: bool,
+}
+
+impl FormatOptions {
+	pub const DEFAULT: Self = Self {
+		indent_style: IndentStyle::Tabs,
+		indent_width: 2,
+		print_width: 80,
+		tab_width: 0,
+		final_newline: true,
+		end_of_line: EndOfLine::Lf,
+		recover: false,
+		source_map: false,
+		minify: false,
+		single_line_rules: 0,
+		sort_boundaries: false,
+		strip_comments: false,
+	};
+
+	/// Amount of columns taken by `indent` levels of indentation
+	#[inline]
+	pub fn indent_columns(&self, indent: u8) -> usize {
+		let width = match self.indent_style {
+			IndentStyle::Tabs => self.tab_width,
+			IndentStyle::Spaces => self.indent_width,
+		};
+
+		indent as usize * width as usize
+	}
+}
+
+impl Default for FormatOptions {
+	#[inline]
+	fn default() -> Self {
+		Self::DEFAULT
+	}
+}
+
+impl EndOfLine {
+	#[inline(always)]
+	pub fn as_bytes(self) -> &'static [u8] {
+		match self {
+			EndOfLine::Lf => b"\n",
+			EndOfLine::CrLf => b"\r\n",
+		}
+	}
+}
+
+impl std::str::FromStr for IndentStyle {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"tab" | "tabs" => Ok(Self::Tabs),
+			"space" | "spaces" => Ok(Self::Spaces),
+			_ => Err(()),
+		}
+	}
+}
+
+impl std::str::FromStr for EndOfLine {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"lf" => Ok(Self::Lf),
+			"crlf" => Ok(Self::CrLf),
+			_ => Err(()),
+		}
+	}
+}
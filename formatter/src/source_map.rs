@@ -0,0 +1,164 @@
+use crate::utils::utf16_len;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// [Source Map v3](https://sourcemaps.info/spec.html) of the last formatting run,
+/// see [`crate::FormatOptions::source_map`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+	mappings: Vec<Mapping>,
+	/// Input offsets of `mappings`, until [`Self::resolve`]d into lines and columns
+	offsets: Vec<usize>,
+}
+
+/// Token of the output and where it came from in the input.
+///
+/// Zero-based, columns are counted in UTF-16 code units
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Mapping {
+	pub generated_line: u32,
+	pub generated_column: u32,
+	pub original_line: u32,
+	pub original_column: u32,
+}
+
+impl SourceMap {
+	/// In order of the output
+	#[inline(always)]
+	pub fn mappings(&self) -> &[Mapping] {
+		&self.mappings
+	}
+
+	/// Source map JSON, `file` is the name of the formatted file and `source` of the input one
+	pub fn to_json(&self, file: &str, source: &str) -> String {
+		let mut json = String::from("{\"version\":3,\"file\":");
+		write_json_string(&mut json, file);
+		json.push_str(",\"sources\":[");
+		write_json_string(&mut json, source);
+		json.push_str("],\"names\":[],\"mappings\":\"");
+
+		let mut prev = Mapping::default();
+		let mut line = 0;
+
+		for (i, mapping) in self.mappings.iter().enumerate() {
+			if mapping.generated_line != line {
+				for _ in line..mapping.generated_line {
+					json.push(';');
+				}
+
+				line = mapping.generated_line;
+				prev.generated_column = 0;
+			} else if i > 0 {
+				json.push(',');
+			}
+
+			write_vlq(&mut json, mapping.generated_column, prev.generated_column);
+			// Index of the only source
+			write_vlq(&mut json, 0, 0);
+			write_vlq(&mut json, mapping.original_line, prev.original_line);
+			write_vlq(&mut json, mapping.original_column, prev.original_column);
+
+			prev = *mapping;
+		}
+
+		json.push_str("\"}");
+
+		json
+	}
+
+	#[inline]
+	pub(crate) fn clear(&mut self) {
+		self.mappings.clear();
+		self.offsets.clear();
+	}
+
+	/// Maps generated position to byte `offset` of the input
+	#[inline]
+	pub(crate) fn push(&mut self, line: u32, column: u32, offset: usize) {
+		// Several tokens could start at the same place, e.g. `{` of an empty block
+		if let Some(last) = self.mappings.last() {
+			if last.generated_line == line && last.generated_column == column {
+				return;
+			}
+		}
+
+		self.mappings.push(Mapping {
+			generated_line: line,
+			generated_column: column,
+			..Mapping::default()
+		});
+		self.offsets.push(offset);
+	}
+
+	/// Turns input offsets into lines and columns
+	pub(crate) fn resolve(&mut self, input: &[u8]) {
+		let line_starts: Vec<usize> = std::iter::once(0)
+			.chain(
+				input
+					.iter()
+					.enumerate()
+					.filter(|(_, &byte)| byte == b'\n')
+					.map(|(i, _)| i + 1),
+			)
+			.collect();
+
+		for (mapping, &offset) in self.mappings.iter_mut().zip(&self.offsets) {
+			let line = line_starts.partition_point(|&start| start <= offset) - 1;
+
+			mapping.original_line = line as u32;
+			mapping.original_column = utf16_len(&input[line_starts[line]..offset]) as u32;
+		}
+
+		self.offsets.clear();
+	}
+}
+
+/// Base64 VLQ of the difference between `value` and `prev`
+#[inline]
+fn write_vlq(out: &mut String, value: u32, prev: u32) {
+	let delta = value as i64 - prev as i64;
+	// Sign goes into the lowest bit
+	let mut vlq = (if delta < 0 {
+		(-delta << 1) | 1
+	} else {
+		delta << 1
+	}) as u64;
+
+	loop {
+		let mut digit = (vlq & 0b11111) as usize;
+		vlq >>= 5;
+
+		if vlq > 0 {
+			// Continuation bit
+			digit |= 0b100000;
+		}
+
+		out.push(BASE64[digit] as char);
+
+		if vlq == 0 {
+			break;
+		}
+	}
+}
+
+#[inline]
+fn write_json_string(out: &mut String, s: &str) {
+	out.push('"');
+
+	for ch in s.chars() {
+		match ch {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			ch if (ch as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", ch as u32)),
+			ch => out.push(ch),
+		}
+	}
+
+	out.push('"');
+}
+
+#[cfg(test)]
+mod tests;
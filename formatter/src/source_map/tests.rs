@@ -0,0 +1,340 @@
+use std::path::Path;
+
+use super::{Mapping, SourceMap};
+use crate::{format_with_session, FormatOptions, Session};
+
+const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/css");
+
+const OPTIONS: FormatOptions = FormatOptions {
+	source_map: true,
+	..FormatOptions::DEFAULT
+};
+
+fn format(input: &str, options: FormatOptions) -> (String, SourceMap) {
+	let mut session = Session::default();
+	let mut output = Vec::new();
+
+	if let Err(err) = format_with_session(&mut session, input, &mut output, options) {
+		panic!("{input:?} failed to format: {err:?}");
+	}
+
+	(
+		String::from_utf8(output).unwrap(),
+		session.source_map().clone(),
+	)
+}
+
+/// Rest of the line at zero-based `line` and UTF-16 `column`
+fn at<'a>(lines: &[&'a str], line: u32, column: u32) -> &'a str {
+	let line = lines.get(line as usize).expect("line in range");
+
+	let mut units = 0;
+	let offset = line
+		.char_indices()
+		.find(|(_, ch)| {
+			let found = units >= column as usize;
+			units += ch.len_utf16();
+			found
+		})
+		.map_or(line.len(), |(i, _)| i);
+
+	assert_eq!(
+		units.min(column as usize),
+		column as usize,
+		"column in range"
+	);
+
+	&line[offset..]
+}
+
+/// Output and input each mapping points at, first `len` chars of both
+fn pairs<'a>(
+	input: &'a str,
+	output: &'a str,
+	map: &SourceMap,
+	len: usize,
+) -> Vec<(&'a str, &'a str)> {
+	let prefix =
+		|text: &'a str| &text[..text.char_indices().nth(len).map_or(text.len(), |(i, _)| i)];
+
+	let input: Vec<_> = input.split('\n').collect();
+	let output: Vec<_> = output.split('\n').collect();
+
+	map.mappings()
+		.iter()
+		.map(|mapping| {
+			(
+				prefix(at(
+					&output,
+					mapping.generated_line,
+					mapping.generated_column,
+				)),
+				prefix(at(&input, mapping.original_line, mapping.original_column)),
+			)
+		})
+		.collect()
+}
+
+#[test]
+fn json() {
+	let mut map = SourceMap::default();
+
+	map.push(0, 0, 0);
+	map.push(0, 40, 1);
+	// Same generated position is mapped once
+	map.push(0, 40, 5);
+	map.push(1, 1, 7);
+	map.push(3, 0, 29);
+	map.resolve(b"a{color:red}\n\n\tb {\n\t\t--x: 1;\n}");
+
+	assert_eq!(
+		map.mappings(),
+		[
+			Mapping {
+				generated_line: 0,
+				generated_column: 0,
+				original_line: 0,
+				original_column: 0,
+			},
+			Mapping {
+				generated_line: 0,
+				generated_column: 40,
+				original_line: 0,
+				original_column: 1,
+			},
+			Mapping {
+				generated_line: 1,
+				generated_column: 1,
+				original_line: 0,
+				original_column: 7,
+			},
+			Mapping {
+				generated_line: 3,
+				generated_column: 0,
+				original_line: 4,
+				original_column: 0,
+			},
+		]
+	);
+
+	// Negative deltas and multi-digit VLQ
+	assert_eq!(
+		map.to_json("out.css", "in \"1\".css"),
+		concat!(
+			r#"{"version":3,"file":"out.css","sources":["in \"1\".css"],"names":[],"#,
+			r#""mappings":"AAAA,wCAAC;CAAM;;AAIP"}"#
+		)
+	);
+
+	assert_eq!(
+		SourceMap::default().to_json("", ""),
+		r#"{"version":3,"file":"","sources":[""],"names":[],"mappings":""}"#
+	);
+}
+
+#[test]
+fn disabled() {
+	let (_, map) = format("a{color:red}", FormatOptions::default());
+
+	assert!(map.mappings().is_empty());
+}
+
+#[test]
+fn tokens() {
+	let input = "a{color:red;margin:0 auto}\n@media print{b{x:1}}";
+	let (output, map) = format(input, OPTIONS);
+
+	assert_eq!(
+		pairs(input, &output, &map, 2),
+		[
+			("a ", "a{"),
+			("{", "{c"),
+			("ma", "ma"),
+			("0 ", "0 "),
+			("au", "au"),
+			("co", "co"),
+			("re", "re"),
+			("}", "}"),
+			("@m", "@m"),
+			("pr", "pr"),
+			("{", "{b"),
+			("b ", "b{"),
+			("{", "{x"),
+			("x:", "x:"),
+			("1;", "1}"),
+			("}", "}}"),
+			("}", "}"),
+		]
+	);
+}
+
+#[test]
+fn sorted() {
+	let input = "a {\n  z-index: 1;\n  color: red;\n  --gap: 0;\n}\n";
+	let (output, map) = format(input, OPTIONS);
+
+	let lines: Vec<_> = map
+		.mappings()
+		.iter()
+		.map(|mapping| (mapping.generated_line, mapping.original_line))
+		.collect();
+
+	assert_eq!(
+		output,
+		"a {\n\tz-index: 1;\n\n\tcolor: red;\n\n\t--gap: 0;\n}\n"
+	);
+	assert_eq!(
+		lines,
+		[
+			(0, 0),
+			(0, 0),
+			(1, 1),
+			(1, 1),
+			(3, 2),
+			(3, 2),
+			(5, 3),
+			(5, 3),
+			(6, 4)
+		]
+	);
+}
+
+#[test]
+fn split() {
+	let input = "a{font-family:system-ui,-apple-system,\"Segoe UI\",Roboto,\"Helvetica Neue\",\
+	             \"Noto Sans\",\"Liberation Sans\",Arial,sans-serif}";
+	let (output, map) = format(input, OPTIONS);
+
+	assert!(output.lines().count() > 3, "{output}");
+
+	for (generated, original) in pairs(input, &output, &map, 1) {
+		assert_eq!(generated, original);
+	}
+}
+
+#[test]
+fn utf16() {
+	let input = "a{content:\"é😀\";color:red}";
+	let (output, map) = format(input, OPTIONS);
+	let output: Vec<_> = output.split('\n').collect();
+
+	let red = map
+		.mappings()
+		.iter()
+		.find(|mapping| at(&output, mapping.generated_line, mapping.generated_column) == "red;")
+		.expect("mapping of `red`");
+
+	// `é` is a single code unit, `😀` is a surrogate pair
+	assert_eq!((red.original_line, red.original_column), (0, 22));
+}
+
+#[test]
+fn recover() {
+	let input = "a{color:red}\nb{color:red !}\n1{x:1}\n\ni{margin:0}";
+	let options = FormatOptions {
+		recover: true,
+		..OPTIONS
+	};
+	let (output, map) = format(input, options);
+
+	// What is copied as is is not mapped, marks of rolled back `1{x:1}` are gone
+	assert_eq!(
+		pairs(input, &output, &map, 1),
+		[
+			("a", "a"),
+			("{", "{"),
+			("c", "c"),
+			("r", "r"),
+			("}", "}"),
+			("b", "b"),
+			("{", "{"),
+			("}", "}"),
+			("i", "i"),
+			("{", "{"),
+			("m", "m"),
+			("0", "0"),
+			("}", "}"),
+		]
+	);
+}
+
+#[test]
+fn minify() {
+	let input = "a {\n\tmargin: 0 auto;\n\n\tcolor: red;\n}\n\nb > i {\n\tx: 1;\n}\n";
+	let options = FormatOptions {
+		minify: true,
+		..OPTIONS
+	};
+	let (output, map) = format(input, options);
+
+	assert_eq!(output, "a{margin:0 auto;color:red}b>i{x:1}\n");
+	assert!(map.mappings().iter().all(|mapping| mapping.generated_line == 0));
+
+	for (generated, original) in pairs(input, &output, &map, 1) {
+		assert_eq!(generated, original);
+	}
+}
+
+#[test]
+fn single_line_rules() {
+	let input = "a {\n\tcolor: red;\n}\n\nb {\n\tx: 1;\n\ty: 2;\n\tz: 3;\n}\n";
+	let options = FormatOptions {
+		single_line_rules: 2,
+		..OPTIONS
+	};
+	let (output, map) = format(input, options);
+
+	assert_eq!(output, "a { color: red; }\n\nb {\n\tx: 1;\n\ty: 2;\n\tz: 3;\n}\n");
+
+	for (generated, original) in pairs(input, &output, &map, 1) {
+		assert_eq!(generated, original);
+	}
+}
+
+#[test]
+fn session_reuse() {
+	let mut session = Session::default();
+
+	for input in ["a{color:red}", "b{}"] {
+		format_with_session(&mut session, input, &mut Vec::new(), OPTIONS).unwrap();
+	}
+
+	assert_eq!(session.source_map().mappings().len(), 3);
+}
+
+#[test]
+fn fixtures() {
+	fn visit(dir: &Path, count: &mut usize) {
+		for entry in std::fs::read_dir(dir).expect("fixtures directory") {
+			let path = entry.expect("readable entry").path();
+
+			if path.is_dir() {
+				visit(&path, count);
+				continue;
+			}
+
+			if path.file_name().is_none_or(|name| name != "before.css") {
+				continue;
+			}
+
+			let input = std::fs::read_to_string(&path).unwrap();
+			let (output, map) = format(&input, OPTIONS);
+
+			assert!(!map.mappings().is_empty(), "{}", path.display());
+
+			// Every mapping points at the same character, quotes of strings could change
+			for (generated, original) in pairs(&input, &output, &map, 1) {
+				let same = generated == original || matches!((generated, original), ("\"", "'"));
+
+				assert!(same, "{}: {generated:?} {original:?}", path.display());
+			}
+
+			*count += 1;
+		}
+	}
+
+	let mut count = 0;
+	visit(Path::new(FIXTURES_DIR), &mut count);
+
+	assert!(count > 0, "no fixtures found in {FIXTURES_DIR}");
+}
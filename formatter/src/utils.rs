@@ -1,6 +1,9 @@
 pub trait Helper {
 	fn is_digit(&self) -> bool;
 	fn is_ident_start(&self) -> bool;
+	fn is_ident_char(&self) -> bool;
+	fn is_hex_digit(&self) -> bool;
+	fn is_whitespace(&self) -> bool;
 }
 
 impl Helper for u8 {
@@ -9,9 +12,25 @@ impl Helper for u8 {
 		self.is_ascii_digit()
 	}
 
+	/// Any byte of a non-ASCII code point starts an ident
 	#[inline(always)]
 	fn is_ident_start(&self) -> bool {
-		matches!(self, b'a'..=b'z' | b'A'..=b'Z' | b'_')
+		matches!(self, b'a'..=b'z' | b'A'..=b'Z' | b'_' | 0x80..)
+	}
+
+	#[inline(always)]
+	fn is_ident_char(&self) -> bool {
+		self.is_ident_start() || matches!(self, b'0'..=b'9' | b'-')
+	}
+
+	#[inline(always)]
+	fn is_hex_digit(&self) -> bool {
+		self.is_ascii_hexdigit()
+	}
+
+	#[inline(always)]
+	fn is_whitespace(&self) -> bool {
+		matches!(self, b' ' | b'\t' | b'\n' | b'\r')
 	}
 }
 
@@ -25,4 +44,81 @@ impl Helper for Option<u8> {
 	fn is_ident_start(&self) -> bool {
 		matches!(self, Some(x) if x.is_ident_start())
 	}
+
+	#[inline(always)]
+	fn is_ident_char(&self) -> bool {
+		matches!(self, Some(x) if x.is_ident_char())
+	}
+
+	#[inline(always)]
+	fn is_hex_digit(&self) -> bool {
+		matches!(self, Some(x) if x.is_hex_digit())
+	}
+
+	#[inline(always)]
+	fn is_whitespace(&self) -> bool {
+		matches!(self, Some(x) if x.is_whitespace())
+	}
+}
+
+/// Amount of columns `bytes` take in a terminal (or an editor)
+///
+/// East Asian wide characters take two columns, combining marks take none.
+/// Invalid UTF-8 takes a column per byte
+#[inline]
+pub fn display_width(bytes: &[u8]) -> usize {
+	if bytes.is_ascii() {
+		return bytes.len();
+	}
+
+	bytes
+		.utf8_chunks()
+		.map(|chunk| chunk.valid().chars().map(char_width).sum::<usize>() + chunk.invalid().len())
+		.sum()
+}
+
+/// Length of UTF-8 `bytes` in UTF-16 code units, as columns of source maps are counted
+#[inline]
+pub fn utf16_len(bytes: &[u8]) -> usize {
+	bytes
+		.iter()
+		.map(|&byte| match byte {
+			// Continuation bytes
+			0x80..=0xBF => 0,
+			// Code points above U+FFFF take a surrogate pair
+			0xF0.. => 2,
+			_ => 1,
+		})
+		.sum()
+}
+
+#[inline]
+fn char_width(ch: char) -> usize {
+	match ch as u32 {
+		// Combining marks, zero width spaces and joiners, variation selectors
+		0x0300..=0x036F
+		| 0x200B..=0x200F
+		| 0x20D0..=0x20FF
+		| 0xFE00..=0xFE0F
+		| 0xFE20..=0xFE2F
+		| 0xE0100..=0xE01EF => 0,
+
+		// Hangul Jamo, CJK, Hangul syllables, fullwidth forms, emoji
+		0x1100..=0x115F
+		| 0x2E80..=0x303E
+		| 0x3041..=0x33FF
+		| 0x3400..=0x4DBF
+		| 0x4E00..=0x9FFF
+		| 0xA000..=0xA4CF
+		| 0xAC00..=0xD7A3
+		| 0xF900..=0xFAFF
+		| 0xFE30..=0xFE4F
+		| 0xFF00..=0xFF60
+		| 0xFFE0..=0xFFE6
+		| 0x1F300..=0x1F64F
+		| 0x1F900..=0x1F9FF
+		| 0x20000..=0x3FFFD => 2,
+
+		_ => 1,
+	}
 }
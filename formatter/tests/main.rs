@@ -117,4 +117,74 @@ fn test_all() {
 	}
 }
 
+/// Formats every case in-process, from several threads and from inside of another formatter
+#[test]
+fn reentrant() {
+	use hel_css_formatter::{format_with_session, FormatOptions, Session};
+
+	/// Formats a nested stylesheet on every write of the outer one
+	struct Nested<'a> {
+		session: Session,
+		input: &'a str,
+		output: Vec<u8>,
+	}
+
+	impl std::io::Write for Nested<'_> {
+		fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+			let mut output = Vec::new();
+
+			format_with_session(
+				&mut self.session,
+				self.input,
+				&mut output,
+				FormatOptions::default(),
+			)
+			.map_err(|err| std::io::Error::other(format!("{err:?}")))?;
+
+			self.output = output;
+
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> std::io::Result<()> {
+			Ok(())
+		}
+	}
+
+	let cases: Vec<_> = get_test_cases().collect();
+
+	std::thread::scope(|scope| {
+		for chunk in cases.chunks(cases.len().div_ceil(4)) {
+			scope.spawn(move || {
+				let mut session = Session::default();
+
+				for case in chunk {
+					let mut output = Vec::new();
+
+					format_with_session(
+						&mut session,
+						&case.before,
+						&mut output,
+						FormatOptions::default(),
+					)
+					.unwrap_or_else(|err| panic!("{case:?}: {err:?}"));
+
+					assert!(output == case.after.as_bytes(), "{case:?}");
+				}
+			});
+		}
+	});
+
+	let case = &cases[0];
+	let mut nested = Nested {
+		session: Session::default(),
+		input: &case.before,
+		output: Vec::new(),
+	};
+
+	hel_css_formatter::format(&case.before, &mut nested, FormatOptions::default()).unwrap();
+
+	assert!(nested.output == case.after.as_bytes(), "{case:?}");
+}
+
 mod utils;
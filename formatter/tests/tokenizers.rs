@@ -0,0 +1,104 @@
+//! Replays differential fuzzing findings, see `fuzz/fuzz_targets/tokenizers.rs`.
+//!
+//! Every case in `fuzz/regressions/tokenizers` has to pass. Inputs `cargo fuzz` left in
+//! `fuzz/artifacts/tokenizers` are minimized into a scratch directory of the target dir,
+//! to be reviewed and copied to regression cases along with a fix
+#![cfg(feature = "simd-tokenizer")]
+
+use std::path::{Path, PathBuf};
+
+use hel_css_formatter::compare_tokenizers;
+
+const FUZZ_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../fuzz");
+
+fn read_dir(dir: &Path) -> Vec<PathBuf> {
+	let Ok(entries) = std::fs::read_dir(dir) else {
+		return Vec::new();
+	};
+
+	let mut paths: Vec<_> = entries
+		.map(|entry| entry.expect("readable entry").path())
+		.filter(|path| path.is_file())
+		.collect();
+	paths.sort();
+
+	paths
+}
+
+/// Removes chunks of `input`, halving chunk size down to a single byte,
+/// as long as tokenizers still diverge
+fn minimize(mut input: Vec<u8>) -> Vec<u8> {
+	let mut chunk = input.len().div_ceil(2);
+
+	while chunk > 0 {
+		let mut at = 0;
+
+		while at < input.len() {
+			let mut candidate = input.clone();
+			candidate.drain(at..(at + chunk).min(input.len()));
+
+			if compare_tokenizers(&candidate).is_err() {
+				input = candidate;
+			} else {
+				at += chunk;
+			}
+		}
+
+		chunk /= 2;
+	}
+
+	input
+}
+
+#[test]
+fn regressions() {
+	let dir = Path::new(FUZZ_DIR).join("regressions/tokenizers");
+	let cases = read_dir(&dir);
+
+	assert!(!cases.is_empty(), "no regression cases in {}", dir.display());
+
+	let failed: Vec<_> = cases
+		.iter()
+		.filter_map(|path| {
+			let input = std::fs::read(path).unwrap();
+			let divergence = compare_tokenizers(&input).err()?;
+
+			Some(format!("{}: {divergence}", path.display()))
+		})
+		.collect();
+
+	assert!(failed.is_empty(), "\n{}", failed.join("\n"));
+}
+
+#[test]
+fn artifacts() {
+	let minimized = Path::new(env!("CARGO_TARGET_TMPDIR")).join("tokenizers");
+	let mut saved = Vec::new();
+
+	for path in read_dir(&Path::new(FUZZ_DIR).join("artifacts/tokenizers")) {
+		let input = std::fs::read(&path).unwrap();
+
+		// Already fixed, or not a divergence at all (e.g. a timeout)
+		if compare_tokenizers(&input).is_ok() {
+			continue;
+		}
+
+		let input = minimize(input);
+		let divergence = compare_tokenizers(&input).unwrap_err();
+
+		let name = path.file_name().unwrap().to_string_lossy();
+		let target = minimized.join(name.as_ref());
+
+		std::fs::create_dir_all(&minimized).unwrap();
+		std::fs::write(&target, &input).unwrap();
+		let input = String::from_utf8_lossy(&input);
+		saved.push(format!("{} ({input:?}): {divergence}", target.display()));
+	}
+
+	assert!(
+		saved.is_empty(),
+		"minimized, copy them to {} along with a fix:\n{}",
+		Path::new(FUZZ_DIR).join("regressions/tokenizers").display(),
+		saved.join("\n")
+	);
+}
@@ -0,0 +1,21 @@
+#![no_main]
+
+use hel_css_formatter::{format, FormatOptions};
+use libfuzzer_sys::fuzz_target;
+
+// Run with `cargo +nightly fuzz run format`, add `--features simd-tokenizer`
+// to fuzz SIMD tokenizer backend
+fuzz_target!(|input: &[u8]| {
+	for recover in [false, true] {
+		let options = FormatOptions {
+			recover,
+			..FormatOptions::default()
+		};
+
+		let mut output = Vec::new();
+
+		if format(input, &mut output, options).is_ok() {
+			assert!(std::str::from_utf8(&output).is_ok(), "Output is not UTF-8");
+		}
+	}
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use hel_css_formatter::compare_tokenizers;
+use libfuzzer_sys::fuzz_target;
+
+// Run with `cargo +nightly fuzz run tokenizers --features simd-tokenizer -- -dict=dicts/css.dict`,
+// then `cargo test -p hel-css-formatter --features simd-tokenizer --test tokenizers`
+// minimizes findings for regression cases
+fuzz_target!(|input: &[u8]| {
+	if let Err(divergence) = compare_tokenizers(input) {
+		panic!("{divergence}");
+	}
+});
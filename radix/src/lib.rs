@@ -42,8 +42,6 @@ trait Compare {
 impl<T> Node<T> {
 	#[inline]
 	fn get(&self, key: &[u8]) -> Option<&T> {
-		debug_assert!(!key.is_empty());
-
 		let (inner, rest) = match *self {
 			Node::Empty => return None,
 
@@ -59,8 +57,8 @@ impl<T> Node<T> {
 			}
 
 			Node::Map(ref map) => {
-				let (&key, rest) = unsafe { key.split_first().unwrap_unchecked() };
-				let inner = map.get(key);
+				let (&key, rest) = key.split_first()?;
+				let inner = map.get(key)?;
 
 				(inner, rest)
 			}
@@ -42,9 +42,14 @@ impl<T> Map<T> {
 		}
 	}
 
+	/// Returns [`None`] for bytes outside of [`CSS_IDENTS_CHARS`]
 	#[inline]
-	pub fn get(&self, key: u8) -> &Inner<T> {
-		&self.inner[U8_TO_IDX[key as usize]]
+	pub fn get(&self, key: u8) -> Option<&Inner<T>> {
+		if !matches!(key, b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'-' | b'_') {
+			return None;
+		}
+
+		Some(&self.inner[U8_TO_IDX[key as usize]])
 	}
 
 	#[inline]
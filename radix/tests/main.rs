@@ -68,3 +68,16 @@ fn simple() {
 	assert_eq!(radix.get("backpack"), Some(&102));
 	assert_eq!(radix.get("aaaa"), Some(&104));
 }
+
+#[test]
+fn bad_keys() {
+	let mut radix = Radix::new();
+
+	radix.insert("color", 1);
+	radix.insert("columns", 2);
+
+	assert_eq!(radix.get(""), None);
+	assert_eq!(radix.get("colé"), None);
+	assert_eq!(radix.get("co.or"), None);
+	assert_eq!(radix.get("col\u{ff}"), None);
+}
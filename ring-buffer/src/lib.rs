@@ -5,8 +5,7 @@ const SIZE: usize = 16;
 
 /// Fixed length ring buffer
 ///
-/// Proper testing in debug mode required.
-/// Unsafe af
+/// Keeps up to `N` last written values, reading outside of them yields [`None`]
 pub struct RingBuffer<T, const N: usize = SIZE> {
 	buf: [T; N],
 	writer: usize,
@@ -32,26 +31,48 @@ impl<T: Copy, const N: usize> RingBuffer<T, N> {
 }
 
 impl<T, const N: usize> RingBuffer<T, N> {
+	/// Moves reader by `vector`, at most one step past the last written value.
+	///
+	/// Returns `false` and stays in place, if it would leave the buffer
 	#[inline]
-	pub fn go(&mut self, vector: isize) {
-		self.reader = (self.reader as isize + vector) as usize;
+	pub fn go(&mut self, vector: isize) -> bool {
+		match self.absolute(vector) {
+			Some(reader) if reader <= self.writer => {
+				self.reader = reader;
+				true
+			}
+			_ => false,
+		}
 	}
 
 	#[inline(always)]
-	pub fn go_next(&mut self) {
+	pub fn go_next(&mut self) -> bool {
 		self.go(1)
 	}
 
 	#[inline(always)]
-	pub fn go_prev(&mut self) {
+	pub fn go_prev(&mut self) -> bool {
 		self.go(-1)
 	}
 
+	/// Value at `index` relative to reader, if it is still in the buffer
+	#[inline]
+	pub fn get(&self, index: isize) -> Option<&T> {
+		let i = self.absolute(index)?;
+
+		if i >= self.writer || i + N < self.writer {
+			return None;
+		}
+
+		Some(&self.buf[i & (N - 1)])
+	}
+
+	/// Overwrites the oldest value, once the buffer is full
 	#[inline]
 	pub fn push(&mut self, value: T) {
 		debug_assert!(
-			self.writer - self.reader < SIZE,
-			"`Writer` is lap ahead of `Reader`. This is Undefined Behavior"
+			self.writer - self.reader < N,
+			"`Writer` is lap ahead of `Reader`, current value is lost"
 		);
 
 		let i = self.writer & (N - 1);
@@ -65,22 +86,25 @@ impl<T, const N: usize> RingBuffer<T, N> {
 		self.behind() > 0
 	}
 
+	/// Amount of values written ahead of reader
 	#[inline(always)]
 	pub fn behind(&self) -> usize {
-		self.writer - (self.reader + 1)
+		self.writer.saturating_sub(self.reader + 1)
+	}
+
+	#[inline(always)]
+	fn absolute(&self, index: isize) -> Option<usize> {
+		self.reader.checked_add_signed(index)
 	}
 }
 
 impl<T, const N: usize> std::ops::Index<isize> for RingBuffer<T, N> {
 	type Output = T;
 
+	/// Panics if `index` is out of the buffer, see [`RingBuffer::get`]
 	#[inline]
 	fn index(&self, index: isize) -> &Self::Output {
-		let i = (self.reader as isize + index) as usize & (N - 1);
-
-		debug_assert!(i < self.writer, "Reading ahead of writer!");
-
-		&self.buf[i]
+		self.get(index).expect("Reading outside of RingBuffer")
 	}
 }
 
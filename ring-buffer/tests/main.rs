@@ -27,3 +27,24 @@ fn basic() {
 
 	assert_eq!(ring[0], (14 + 1) * 10);
 }
+
+#[test]
+fn out_of_bounds() {
+	let mut ring = RingBuffer::<usize, SIZE>::new(0);
+
+	// Nothing was written before initial value, or after it
+	assert_eq!(ring.get(-1), None);
+	assert_eq!(ring.get(1), None);
+	assert!(!ring.go_prev());
+	assert_eq!(ring.behind(), 0);
+
+	for i in 1..=SIZE {
+		ring.push(i);
+		ring.go_next();
+	}
+
+	// Initial value got overwritten
+	assert_eq!(ring.get(-(SIZE as isize)), None);
+	assert_eq!(ring.get(-(SIZE as isize) + 1), Some(&1));
+	assert_eq!(ring[0], SIZE);
+}
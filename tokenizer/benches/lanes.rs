@@ -0,0 +1,95 @@
+//! Tokenizer throughput per lane width, run with `cargo bench -p tokenizer`.
+//!
+//! Every input is about 1 MiB and leans on a single scanner, `mixed` is a regular stylesheet.
+//! Widths current CPU does not support are skipped
+#![feature(test)]
+
+extern crate test;
+
+use std::sync::LazyLock;
+
+use test::Bencher;
+use tokenizer::{LaneWidth, Tokenizer};
+
+const SIZE: usize = 1 << 20;
+
+fn repeat(chunk: &str) -> String {
+	chunk.repeat(SIZE.div_ceil(chunk.len()))
+}
+
+static COMMENTS: LazyLock<String> = LazyLock::new(|| {
+	repeat("/* Generated by a build step, keep in sync with design tokens * and / inside */\n")
+});
+
+static STRINGS: LazyLock<String> = LazyLock::new(|| {
+	repeat("a::before { content: \"\\201C Quoted text, long enough to span lanes \\201D\" }\n")
+});
+
+static WHITESPACE: LazyLock<String> = LazyLock::new(|| {
+	repeat("a {\n\n\t\t\t\t\t\t\t\t                                                color: red }\n")
+});
+
+static NAMES: LazyLock<String> = LazyLock::new(|| {
+	repeat(".navigation-bar__menu-item--is-active_and-highlighted-by-default-theme {}\n")
+});
+
+static MIXED: LazyLock<String> = LazyLock::new(|| {
+	repeat(concat!(
+		"/* Buttons */\n",
+		".btn-primary:hover, .btn-primary:focus-visible {\n",
+		"\tbackground: url(\"images/button.png\") no-repeat 0 0 / 100% auto;\n",
+		"\tfont: italic 600 1.25rem/1.5 \"Helvetica Neue\", Arial, sans-serif;\n",
+		"\tcolor: #1a2b3c;\n",
+		"\ttransition: color .2s ease-in-out, transform 150ms cubic-bezier(.4, 0, .2, 1);\n",
+		"}\n\n",
+		"@media (min-width: 768px) and (prefers-reduced-motion: no-preference) {\n",
+		"\t.grid { grid-template-columns: repeat(auto-fill, minmax(12rem, 1fr)); }\n",
+		"}\n\n",
+	))
+});
+
+fn run(b: &mut Bencher, input: &str, lanes: LaneWidth) {
+	if !lanes.is_supported() {
+		eprintln!("skipping, CPU does not support lanes of {lanes}");
+		return;
+	}
+
+	// Aligned copy, if any, is made once here and shared by every clone
+	let tokenizer = Tokenizer::with_lane_width(input.as_bytes(), lanes);
+
+	b.bytes = input.len() as u64;
+	b.iter(|| tokenizer.clone().count());
+}
+
+macro_rules! benches {
+	($($name:ident: $input:expr;)*) => {
+		$(
+			mod $name {
+				use super::*;
+
+				#[bench]
+				fn w16(b: &mut Bencher) {
+					run(b, &$input, LaneWidth::W16);
+				}
+
+				#[bench]
+				fn w32(b: &mut Bencher) {
+					run(b, &$input, LaneWidth::W32);
+				}
+
+				#[bench]
+				fn w64(b: &mut Bencher) {
+					run(b, &$input, LaneWidth::W64);
+				}
+			}
+		)*
+	};
+}
+
+benches! {
+	comments: COMMENTS;
+	strings: STRINGS;
+	whitespace: WHITESPACE;
+	names: NAMES;
+	mixed: MIXED;
+}
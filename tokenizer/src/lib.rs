@@ -1,14 +1,25 @@
 #![feature(portable_simd)]
 #![feature(test)]
 
+pub use line_index::{LineColumn, LineIndex};
+pub use safe::Tokenizer;
+pub use simd::LaneWidth;
+pub use span::{Span, Spanned};
 pub use token::Token;
 
+pub(crate) use token::RawToken;
+
 use consts::ASCII;
-use simd::tokenize_comment_simd;
+use simd::{
+	consume_name_simd, tokenize_comment_simd, tokenize_string_simd, tokenize_whitespace_simd,
+};
 use utils::ByteHelper;
 
+/// Portable lane width, used whenever CPU has nothing wider, see [`LaneWidth::detect`]
 pub const LANE_WIDTH: usize = 16;
 pub const LANE_WIDTH_MASK: usize = LANE_WIDTH - 1;
+/// Widest lanes, aligned copies of input are aligned to it
+pub const MAX_LANE_WIDTH: usize = 64;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[allow(clippy::upper_case_acronyms)]
@@ -16,9 +27,21 @@ pub enum Error {
 	BadComment,
 	BadString,
 	EOF,
-	NonASCII,
 }
 
+impl std::fmt::Display for Error {
+	#[inline]
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Error::BadComment => "unterminated comment",
+			Error::BadString => "unterminated string",
+			Error::EOF => "unexpected end of input",
+		})
+	}
+}
+
+impl std::error::Error for Error {}
+
 macro_rules! pat {
 	// alpha
 	(A) => {
@@ -32,19 +55,26 @@ macro_rules! pat {
 	(AN) => {
 		pat!(A) | pat!(N)
 	};
+	// any byte of a non-ASCII code point
+	(U) => {
+		0x80..
+	};
 }
 
-#[derive(Debug)]
-// pub struct Tokenizer<const LANE_WIDTH: usize = 16> { // this is not supported i guess?
-pub struct Tokenizer {
+/// Unchecked fast path behind [`Tokenizer`],
+/// input has to be aligned to `lanes` and outlive the tokenizer
+#[derive(Clone, Debug)]
+pub(crate) struct RawTokenizer {
 	cursor: *const u8,
 	eof: *const u8, // this would be a null terminator position in C
+	lanes: LaneWidth,
 }
 
-pub type Result = std::result::Result<Token, Error>;
+pub type Result<'a> = std::result::Result<Token<'a>, Error>;
+pub type SpannedResult<'a> = std::result::Result<Spanned<Token<'a>>, Spanned<Error>>;
+pub(crate) type RawResult = std::result::Result<RawToken, Error>;
 
-// impl<const LANE_WIDTH: usize> Tokenizer<LANE_WIDTH> {
-impl Tokenizer {
+impl RawTokenizer {
 	#[inline(always)]
 	pub fn advance(&mut self, steps: usize) {
 		self.cursor = unsafe { self.cursor.add(steps) };
@@ -61,24 +91,24 @@ impl Tokenizer {
 	}
 
 	#[inline]
-	pub fn new(input: *const [u8]) -> Self {
+	pub fn new(input: *const [u8], lanes: LaneWidth) -> Self {
 		const {
-			assert!(LANE_WIDTH.is_power_of_two());
-			assert!(LANE_WIDTH >= 16);
-			assert!(LANE_WIDTH <= 64);
+			assert!(LANE_WIDTH == LaneWidth::W16.bytes());
+			assert!(MAX_LANE_WIDTH == LaneWidth::W64.bytes());
 		}
 
 		let ptr = input as *const u8;
 
+		assert!(lanes.is_supported(), "CPU does not support lanes of {lanes}");
 		assert!(
-			ptr.addr() & LANE_WIDTH_MASK == 0,
-			"pointer is not aligned to width - {}",
-			{ LANE_WIDTH }
+			ptr.addr().is_multiple_of(lanes.bytes()),
+			"pointer is not aligned to width - {lanes}"
 		);
 
 		Self {
 			cursor: ptr,
 			eof: unsafe { ptr.add(input.len()) },
+			lanes,
 		}
 	}
 
@@ -110,7 +140,7 @@ impl Tokenizer {
 	}
 
 	#[inline]
-	pub fn next_token(&mut self) -> Result {
+	pub fn next_token(&mut self) -> RawResult {
 		if self.is_eof() {
 			return Err(Error::EOF);
 		}
@@ -118,10 +148,6 @@ impl Tokenizer {
 		let ch = self.current_byte();
 		let next_ch = self.try_peek_next_byte();
 
-		if !ch.is_ascii() {
-			return Err(Error::NonASCII);
-		}
-
 		let token = match ch {
 			// A comment or delim token
 			ASCII::SLASH => {
@@ -129,7 +155,7 @@ impl Tokenizer {
 					self.tokenize_comment()?
 				} else {
 					self.advance(1);
-					Token::Delim(ASCII::SLASH)
+					RawToken::Delim(ASCII::SLASH)
 				}
 			}
 
@@ -138,61 +164,79 @@ impl Tokenizer {
 
 			// A hash or delim token
 			ASCII::HASH => {
-				if matches!(next_ch, Some(x) if matches!(x, pat!(A) | ASCII::UNDERSCORE)) {
-					self.tokenize_name()
+				if next_ch.is_ident_char() || self.is_valid_escape(1) {
+					self.tokenize_prefixed_name(RawToken::Hash)
 				} else {
 					self.advance(1);
-					Token::Delim(ASCII::HASH)
+					RawToken::Delim(ASCII::HASH)
 				}
 			}
 
 			// String token
 			ASCII::SINGLE_QUOTE | ASCII::QUOTE => self.tokenize_string()?,
 
-			// Number token
-			pat!(N) => self.tokenize_number(),
+			// Numeric token
+			pat!(N) => self.tokenize_numeric(),
 
-			// Number or delim token
+			// Numeric or delim token
 			delim @ (ASCII::FULL_STOP | ASCII::PLUS) => {
-				if next_ch.is_digit()
-					|| (matches!(next_ch, Some(ASCII::FULL_STOP))
-						&& matches!(self.try_peek(2), Some(x) if x.is_digit()))
-				{
-					self.tokenize_number()
+				if self.starts_number(0) {
+					self.tokenize_numeric()
 				} else {
 					self.advance(1);
-					Token::Delim(delim)
+					RawToken::Delim(delim)
 				}
 			}
 
-			// Number or ident or delim token
+			// Numeric, CDC, ident-like or delim token
 			ASCII::DASH => {
-				if next_ch.is_digit()
-					|| (next_ch == Some(ASCII::FULL_STOP)
-						&& matches!(self.try_peek(2), Some(x) if x.is_digit()))
-				{
-					self.tokenize_number()
-				} else if matches!(next_ch, Some(x) if matches!(x, pat!(A) | ASCII::UNDERSCORE | ASCII::DASH))
-				{
-					self.tokenize_name()
+				if self.starts_number(0) {
+					self.tokenize_numeric()
+				} else if self.lookahead(b"-->") {
+					self.advance(3);
+					RawToken::CDC
+				} else if self.starts_ident(0) {
+					self.tokenize_ident_like()
 				} else {
 					self.advance(1);
-					Token::Delim(ASCII::DASH)
+					RawToken::Delim(ASCII::DASH)
+				}
+			}
+
+			// CDO or delim token
+			ASCII::LT => {
+				if self.lookahead(b"<!--") {
+					self.advance(4);
+					RawToken::CDO
+				} else {
+					self.advance(1);
+					RawToken::Delim(ASCII::LT)
 				}
 			}
 
 			// AtRule or Delim token
 			ASCII::AT => {
-				if matches!(next_ch, Some(x) if matches!(x, pat!(A) | ASCII::UNDERSCORE)) {
-					self.tokenize_name()
+				if self.starts_ident(1) {
+					self.tokenize_prefixed_name(RawToken::AtRule)
 				} else {
 					self.advance(1);
-					Token::Delim(ASCII::AT)
+					RawToken::Delim(ASCII::AT)
 				}
 			}
 
-			// Ident token
-			pat!(A) | ASCII::UNDERSCORE => self.tokenize_name(),
+			// `U+0025-00FF` or `u+4??`
+			b'u' | b'U'
+				if next_ch == Some(ASCII::PLUS)
+					&& (self.try_peek(2).is_hex_digit() || self.try_peek(2) == Some(ASCII::QUESTION)) =>
+			{
+				self.tokenize_unicode_range()
+			}
+
+			// Ident-like token, including any non-ASCII code point
+			pat!(A) | pat!(U) | ASCII::UNDERSCORE => self.tokenize_ident_like(),
+
+			// Ident-like token starting with escape, e.g. `\31 0col`
+			ASCII::BACKSLASH if self.is_valid_escape(0) => self.tokenize_ident_like(),
 
 			_ => {
 				self.advance(1);
@@ -201,18 +245,18 @@ impl Tokenizer {
 					// null bytes are valid in utf8
 					b'\0' => self.next_token()?,
 
-					ASCII::PAREN_OPEN => Token::BracketRoundOpen,
-					ASCII::PAREN_CLOSE => Token::BracketRoundClose,
-					ASCII::SQUARED_OPEN => Token::BracketSquareOpen,
-					ASCII::SQUARED_CLOSE => Token::BracketSquareClose,
-					ASCII::CURLY_OPEN => Token::BracketCurlyOpen,
-					ASCII::CURLY_CLOSE => Token::BracketCurlyClose,
-					ASCII::COMMA => Token::Comma,
-					ASCII::COLON => Token::Colon,
-					ASCII::SEMICOLON => Token::Semicolon,
+					ASCII::PAREN_OPEN => RawToken::BracketRoundOpen,
+					ASCII::PAREN_CLOSE => RawToken::BracketRoundClose,
+					ASCII::SQUARED_OPEN => RawToken::BracketSquareOpen,
+					ASCII::SQUARED_CLOSE => RawToken::BracketSquareClose,
+					ASCII::CURLY_OPEN => RawToken::BracketCurlyOpen,
+					ASCII::CURLY_CLOSE => RawToken::BracketCurlyClose,
+					ASCII::COMMA => RawToken::Comma,
+					ASCII::COLON => RawToken::Colon,
+					ASCII::SEMICOLON => RawToken::Semicolon,
 
 					// Anything else is a delim
-					delim => Token::Delim(delim),
+					delim => RawToken::Delim(delim),
 				}
 			}
 		};
@@ -221,141 +265,333 @@ impl Tokenizer {
 	}
 
 	#[inline]
-	fn tokenize_comment(&mut self) -> Result {
+	fn tokenize_comment(&mut self) -> RawResult {
 		// Step over comment opening seq `/*`
 		self.advance(2);
 
-		let token = tokenize_comment_simd(self);
+		let token = tokenize_comment_simd(self)?;
 
 		// Step over comment closing seq `*/`
 		self.advance(2);
 
-		token
+		Ok(token)
+	}
+
+	#[inline(always)]
+	fn lookahead(&self, seq: &[u8]) -> bool {
+		self.rem() >= seq.len() && (0..seq.len()).all(|i| self.peek(i) == seq[i])
 	}
 
-	#[inline]
-	fn tokenize_name(&mut self) -> Token {
-		let opening = self.current_byte();
-		let ptr = self.cursor;
+	/// Steps over ident chars and escapes
+	#[inline(always)]
+	fn consume_name(&mut self) {
+		consume_name_simd(self);
+	}
 
-		// first character is already processed at this point
+	/// Steps over `\` and escaped code point, see [`Self::is_valid_escape`]
+	#[inline]
+	fn consume_escape(&mut self) {
 		self.advance(1);
 
-		let mut is_function = false;
+		if !self.try_peek(0).is_hex_digit() {
+			// Rest of a multibyte code point is consumed by the caller
+			if !self.is_eof() {
+				self.advance(1);
+			}
 
-		while !self.is_eof() {
-			let ch = self.current_byte();
+			return;
+		}
 
-			if !matches!(ch, pat!(AN) | ASCII::DASH | ASCII::UNDERSCORE) {
-				if ch == ASCII::PAREN_OPEN && opening != ASCII::AT {
-					is_function = true;
-					// Consume opening paren
-					self.advance(1);
-				}
+		self.consume_hex_digits(6);
 
-				break;
-			}
+		// Single whitespace after hex digits belongs to the escape
+		match (self.try_peek(0), self.try_peek(1)) {
+			(Some(ASCII::CR), Some(ASCII::LF)) => self.advance(2),
+			(ws, _) if ws.is_whitespace() => self.advance(1),
+			_ => {}
+		}
+	}
 
+	/// Steps over up to `max` hex digits, returns amount consumed
+	#[inline]
+	fn consume_hex_digits(&mut self, max: usize) -> usize {
+		let mut count = 0;
+
+		while count < max && self.try_peek(0).is_hex_digit() {
 			self.advance(1);
+			count += 1;
 		}
 
-		let variant = match opening {
-			ASCII::AT => Token::AtRule,
-			ASCII::HASH => Token::Hash,
-			_ if is_function => Token::Function,
-			_ => Token::Ident,
-		};
-
-		Token::from(variant, ptr, self.cursor)
+		count
 	}
 
+	/// Matches: `1`, `-.5`, `+1.5e-10`
 	#[inline]
-	fn tokenize_number(&mut self) -> Token {
-		let ptr = self.pos();
-		let ch = self.current_byte();
+	fn consume_number(&mut self) {
+		if matches!(self.try_peek(0), Some(ASCII::PLUS | ASCII::DASH)) {
+			self.advance(1);
+		}
+
+		self.consume_digits();
 
-		if matches!(ch, ASCII::DASH | ASCII::PLUS | ASCII::FULL_STOP) {
+		if self.try_peek(0) == Some(ASCII::FULL_STOP) && self.try_peek(1).is_digit() {
 			self.advance(1);
+			self.consume_digits();
 		}
 
-		while !self.is_eof() {
-			let ch = self.current_byte();
+		if matches!(self.try_peek(0), Some(b'e' | b'E')) {
+			let sign = matches!(self.try_peek(1), Some(ASCII::PLUS | ASCII::DASH)) as usize;
 
-			// Matches (we don't care about validity): 1px, 1rem, 100%, +110e10, -110, +++++++++1, .1..1
-			if !matches!(
-				ch,
-				ASCII::PERCENTAGE | ASCII::PLUS | ASCII::DASH | ASCII::FULL_STOP | pat!(AN)
-			) {
-				break;
+			if self.try_peek(1 + sign).is_digit() {
+				self.advance(1 + sign);
+				self.consume_digits();
 			}
+		}
+	}
 
+	#[inline]
+	fn consume_digits(&mut self) {
+		while self.try_peek(0).is_digit() {
 			self.advance(1);
 		}
+	}
 
-		Token::from(Token::Number, ptr, self.cursor)
+	/// Two bytes starting at `i` are `\` and anything but a newline
+	#[inline]
+	fn is_valid_escape(&self, i: usize) -> bool {
+		self.try_peek(i) == Some(ASCII::BACKSLASH)
+			&& !matches!(self.try_peek(i + 1), Some(ASCII::LF | ASCII::CR | ASCII::FF))
 	}
 
+	/// Bytes starting at `i` would start a number
 	#[inline]
-	fn tokenize_string(&mut self) -> Result {
-		// Step over opening quote
+	fn starts_number(&self, i: usize) -> bool {
+		match self.try_peek(i) {
+			Some(ASCII::PLUS | ASCII::DASH) => {
+				self.try_peek(i + 1).is_digit()
+					|| (self.try_peek(i + 1) == Some(ASCII::FULL_STOP)
+						&& self.try_peek(i + 2).is_digit())
+			}
+			Some(ASCII::FULL_STOP) => self.try_peek(i + 1).is_digit(),
+			first => first.is_digit(),
+		}
+	}
+
+	/// Bytes starting at `i` would start an ident
+	#[inline]
+	fn starts_ident(&self, i: usize) -> bool {
+		match self.try_peek(i) {
+			Some(ASCII::DASH) => {
+				self.try_peek(i + 1).is_ident_start()
+					|| self.try_peek(i + 1) == Some(ASCII::DASH)
+					|| self.is_valid_escape(i + 1)
+			}
+			Some(ASCII::BACKSLASH) => self.is_valid_escape(i),
+			first => first.is_ident_start(),
+		}
+	}
+
+	/// Ident, function or url token
+	#[inline]
+	fn tokenize_ident_like(&mut self) -> RawToken {
+		let ptr = self.cursor;
+
+		self.consume_name();
+
+		let is_url = self.cursor.addr() - ptr.addr() == 3
+			&& unsafe { std::slice::from_raw_parts(ptr, 3) }.eq_ignore_ascii_case(b"url");
+
+		if self.try_peek(0) != Some(ASCII::PAREN_OPEN) {
+			return RawToken::from(RawToken::Ident, ptr, self.cursor);
+		}
+
+		// Consume opening paren
 		self.advance(1);
 
-		let opening_quote = self.current_byte();
+		let function = RawToken::from(RawToken::Function, ptr, self.cursor);
+
+		if !is_url {
+			return function;
+		}
+
+		// Leave a single whitespace before a quote, as `url( "a")` is a function
+		while self.try_peek(0).is_whitespace() && self.try_peek(1).is_whitespace() {
+			self.advance(1);
+		}
+
+		let first = match self.try_peek(0) {
+			ws if ws.is_whitespace() => self.try_peek(1),
+			first => first,
+		};
+
+		if matches!(first, Some(ASCII::QUOTE | ASCII::SINGLE_QUOTE)) {
+			return function;
+		}
+
+		self.tokenize_url()
+	}
+
+	/// Unquoted `url(...)` content, right after opening paren
+	#[inline]
+	fn tokenize_url(&mut self) -> RawToken {
+		while self.try_peek(0).is_whitespace() {
+			self.advance(1);
+		}
+
 		let ptr = self.cursor;
 
-		while !self.is_eof() {
-			let ch = self.current_byte();
+		loop {
+			let Some(ch) = self.try_peek(0) else {
+				// Unclosed url still is a url
+				return RawToken::from(RawToken::URL, ptr, self.cursor);
+			};
 
-			if ch == opening_quote {
-				let token = Token::from(Token::String, ptr, self.cursor);
-				self.advance(1);
-				return Ok(token);
+			match ch {
+				ASCII::PAREN_CLOSE => {
+					let token = RawToken::from(RawToken::URL, ptr, self.cursor);
+					self.advance(1);
+					return token;
+				}
+
+				ws if ws.is_whitespace() => {
+					let end = self.cursor;
+
+					while self.try_peek(0).is_whitespace() {
+						self.advance(1);
+					}
+
+					match self.try_peek(0) {
+						None => return RawToken::from(RawToken::URL, ptr, end),
+						Some(ASCII::PAREN_CLOSE) => {
+							self.advance(1);
+							return RawToken::from(RawToken::URL, ptr, end);
+						}
+						Some(_) => break,
+					}
+				}
+
+				ASCII::BACKSLASH if self.is_valid_escape(0) => self.consume_escape(),
+
+				// Quotes, parens, non-printable or a bad escape
+				ASCII::QUOTE
+				| ASCII::SINGLE_QUOTE
+				| ASCII::PAREN_OPEN
+				| ASCII::BACKSLASH
+				| 0x00..=0x08
+				| 0x0B
+				| 0x0E..=0x1F
+				| 0x7F => break,
+
+				_ => self.advance(1),
 			}
+		}
 
-			if matches!(ch, ASCII::LF | ASCII::CR) {
+		// Skip the rest of bad url, up to and including `)`
+		while let Some(ch) = self.try_peek(0) {
+			if ch == ASCII::PAREN_CLOSE {
 				self.advance(1);
-				// Unescaped newline - parse error
 				break;
 			}
 
-			if ch == ASCII::BACKSLASH {
-				// Ignore any escape seq
+			if self.is_valid_escape(0) {
+				self.consume_escape();
+			} else {
 				self.advance(1);
 			}
-
-			self.advance(1);
 		}
 
-		Err(Error::BadString)
+		RawToken::BadURL
 	}
 
-	#[inline(always)]
-	pub fn pos(&self) -> *const u8 {
-		self.cursor
+	/// Hash or at-rule, including prefix
+	#[inline]
+	fn tokenize_prefixed_name<F>(&mut self, variant: F) -> RawToken
+	where
+		F: Fn(*const u8, u32) -> RawToken,
+	{
+		let ptr = self.cursor;
+
+		// Step over `#` or `@`
+		self.advance(1);
+		self.consume_name();
+
+		RawToken::from(variant, ptr, self.cursor)
 	}
 
+	/// Number, percentage or dimension token
 	#[inline]
-	fn tokenize_whitespace(&mut self) -> Token {
-		let mut is_newline = false;
+	fn tokenize_numeric(&mut self) -> RawToken {
+		let ptr = self.cursor;
 
-		while !self.is_eof() {
-			let ch = self.current_byte();
+		self.consume_number();
 
-			match ch {
-				ASCII::LF | ASCII::CR => is_newline = true,
-				ASCII::TAB | ASCII::SPACE => {}
-				_ => break,
-			}
+		if self.starts_ident(0) {
+			self.consume_name();
 
+			return RawToken::from(RawToken::Dimension, ptr, self.cursor);
+		}
+
+		if self.try_peek(0) == Some(ASCII::PERCENTAGE) {
+			let token = RawToken::from(RawToken::Percentage, ptr, self.cursor);
+			self.advance(1);
+			return token;
+		}
+
+		RawToken::from(RawToken::Number, ptr, self.cursor)
+	}
+
+	/// Matches `U+` followed by up to 6 hex digits or `?`, or a range of hex digits
+	#[inline]
+	fn tokenize_unicode_range(&mut self) -> RawToken {
+		let ptr = self.cursor;
+
+		// Step over `U+`
+		self.advance(2);
+
+		let digits = self.consume_hex_digits(6);
+		let mut len = digits;
+
+		while len < 6 && self.try_peek(0) == Some(ASCII::QUESTION) {
 			self.advance(1);
+			len += 1;
 		}
 
-		Token::Whitespace(is_newline)
+		if len == digits && self.try_peek(0) == Some(ASCII::DASH) && self.try_peek(1).is_hex_digit() {
+			self.advance(1);
+			self.consume_hex_digits(6);
+		}
+
+		RawToken::from(RawToken::UnicodeRange, ptr, self.cursor)
+	}
+
+	#[inline]
+	fn tokenize_string(&mut self) -> RawResult {
+		let opening_quote = self.current_byte();
+
+		// Step over opening quote
+		self.advance(1);
+
+		let token = tokenize_string_simd(self, opening_quote)?;
+
+		// Step over closing quote
+		self.advance(1);
+
+		Ok(token)
+	}
+
+	#[inline(always)]
+	pub fn pos(&self) -> *const u8 {
+		self.cursor
+	}
+
+	#[inline(always)]
+	fn tokenize_whitespace(&mut self) -> RawToken {
+		tokenize_whitespace_simd(self)
 	}
 }
 
-impl Iterator for Tokenizer {
-	type Item = Result;
+impl Iterator for RawTokenizer {
+	type Item = RawResult;
 
 	#[inline]
 	fn next(&mut self) -> Option<Self::Item> {
@@ -369,6 +605,8 @@ impl Iterator for Tokenizer {
 	}
 }
 
+mod line_index;
+mod safe;
 mod simd;
 mod span;
 mod token;
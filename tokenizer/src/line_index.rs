@@ -0,0 +1,89 @@
+use consts::ASCII;
+
+/// One-based line and column, column is counted in characters
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LineColumn {
+	pub line: usize,
+	pub column: usize,
+}
+
+/// Maps byte offsets to [`LineColumn`] and back.
+///
+/// Lines are separated by `\n`, `\r` before it belongs to the line.
+/// Built once, every lookup after that is a binary search over line starts
+#[derive(Clone, Debug)]
+pub struct LineIndex<'a> {
+	input: &'a [u8],
+	/// Offset of the first byte of every line, first one is always `0`
+	line_starts: Vec<usize>,
+}
+
+/// Every byte, except UTF-8 continuation bytes, starts a character
+#[inline(always)]
+fn is_char_start(byte: u8) -> bool {
+	(byte as i8) >= -0x40
+}
+
+impl<'a> LineIndex<'a> {
+	#[inline]
+	pub fn new(input: &'a [u8]) -> Self {
+		let line_starts = std::iter::once(0)
+			.chain(
+				input
+					.iter()
+					.enumerate()
+					.filter(|&(_, &byte)| byte == ASCII::LF)
+					.map(|(i, _)| i + 1),
+			)
+			.collect();
+
+		Self { input, line_starts }
+	}
+
+	#[inline(always)]
+	pub fn line_count(&self) -> usize {
+		self.line_starts.len()
+	}
+
+	/// Location of `offset`, clamped to the end of input
+	#[inline]
+	pub fn line_column(&self, offset: usize) -> LineColumn {
+		let offset = offset.min(self.input.len());
+
+		// Index of the last line that starts at or before `offset`
+		let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+		let start = self.line_starts[line];
+
+		let column = self.input[start..offset]
+			.iter()
+			.filter(|&&byte| is_char_start(byte))
+			.count();
+
+		LineColumn {
+			line: line + 1,
+			column: column + 1,
+		}
+	}
+
+	/// Byte offset of `location`, [`None`] if it is past the end of its line or input.
+	/// Column right after the last character of a line is still valid
+	#[inline]
+	pub fn offset(&self, location: LineColumn) -> Option<usize> {
+		let LineColumn { line, column } = location;
+
+		let start = *self.line_starts.get(line.checked_sub(1)?)?;
+		let end = self
+			.line_starts
+			.get(line)
+			.map_or(self.input.len(), |&next| next - 1);
+
+		let mut chars = self.input[start..end]
+			.iter()
+			.enumerate()
+			.filter(|&(_, &byte)| is_char_start(byte))
+			.map(|(i, _)| start + i)
+			.chain(std::iter::once(end));
+
+		chars.nth(column.checked_sub(1)?)
+	}
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use crate::{
+	Error, LaneWidth, RawTokenizer, Result, Span, Spanned, SpannedResult, Token, MAX_LANE_WIDTH,
+};
+
+/// Chunk of input aligned to the widest lanes, [`RawTokenizer`] reads whole lanes
+#[derive(Clone, Copy, Debug)]
+#[repr(C, align(64))]
+struct Chunk([u8; MAX_LANE_WIDTH]);
+
+const _ASSERT_ALIGN: () = assert!(std::mem::align_of::<Chunk>() == MAX_LANE_WIDTH);
+
+/// Tokenizer over any byte slice, tokens borrow from it.
+///
+/// Input that is not aligned to the lane width is copied once into an aligned buffer
+#[derive(Clone, Debug)]
+pub struct Tokenizer<'a> {
+	input: &'a [u8],
+	/// Aligned copy of `input`, if `input` itself is not aligned
+	aligned: Option<Arc<[Chunk]>>,
+	raw: RawTokenizer,
+}
+
+// SAFETY: `raw` only points into `input` or `aligned`, both of which are shared read only
+unsafe impl Send for Tokenizer<'_> {}
+unsafe impl Sync for Tokenizer<'_> {}
+
+impl<'a> Tokenizer<'a> {
+	/// Steps over `amount` of bytes, never past the end of input
+	#[inline]
+	pub fn advance(&mut self, amount: usize) {
+		let amount = amount.min(self.input.len() - self.pos());
+
+		self.raw.advance(amount)
+	}
+
+	#[inline(always)]
+	pub fn input(&self) -> &'a [u8] {
+		self.input
+	}
+
+	#[inline(always)]
+	pub fn is_eof(&self) -> bool {
+		self.raw.is_eof()
+	}
+
+	/// Uses the widest lanes current CPU supports, see [`LaneWidth::detect`]
+	#[inline]
+	pub fn new(input: &'a [u8]) -> Self {
+		Self::with_lane_width(input, LaneWidth::detect())
+	}
+
+	/// Panics if current CPU does not support `lanes`, see [`LaneWidth::is_supported`]
+	#[inline]
+	pub fn with_lane_width(input: &'a [u8], lanes: LaneWidth) -> Self {
+		let aligned = if input.as_ptr().addr().is_multiple_of(lanes.bytes()) {
+			None
+		} else {
+			let mut chunks =
+				vec![Chunk([0; MAX_LANE_WIDTH]); input.len().div_ceil(MAX_LANE_WIDTH)];
+
+			for (chunk, bytes) in chunks.iter_mut().zip(input.chunks(MAX_LANE_WIDTH)) {
+				chunk.0[..bytes.len()].copy_from_slice(bytes);
+			}
+
+			Some(chunks.into())
+		};
+
+		Self {
+			raw: RawTokenizer::new(Self::buffer(input, aligned.as_deref()), lanes),
+			input,
+			aligned,
+		}
+	}
+
+	#[inline(always)]
+	pub fn lane_width(&self) -> LaneWidth {
+		self.raw.lanes
+	}
+
+	/// Buffer [`RawTokenizer`] runs over
+	#[inline(always)]
+	fn buffer(input: &[u8], aligned: Option<&[Chunk]>) -> *const [u8] {
+		let ptr = aligned.map_or(input.as_ptr(), |chunks| chunks.as_ptr().cast());
+
+		std::ptr::slice_from_raw_parts(ptr, input.len())
+	}
+
+	#[inline(always)]
+	fn base(&self) -> *const u8 {
+		Self::buffer(self.input, self.aligned.as_deref()).cast()
+	}
+
+	#[inline]
+	pub fn next_token(&mut self) -> Result<'a> {
+		self.next_spanned().map(|token| token.value).map_err(|err| err.value)
+	}
+
+	/// Same as [`Self::next_token`], along with location of the token or the error
+	#[inline]
+	pub fn next_spanned(&mut self) -> SpannedResult<'a> {
+		// Null bytes are skipped, so they are never a part of the span
+		while self.input.get(self.pos()) == Some(&b'\0') {
+			self.advance(1);
+		}
+
+		let start = self.pos();
+		let res = self.raw.next_token();
+		let span = Span::new(start, self.pos());
+
+		match res {
+			Ok(raw) => Ok(Spanned {
+				value: Token::from_raw(raw, self.input, self.base()),
+				span,
+			}),
+			Err(value) => Err(Spanned { value, span }),
+		}
+	}
+
+	/// Byte offset into the input
+	#[inline(always)]
+	pub fn pos(&self) -> usize {
+		self.raw.pos().addr() - self.base().addr()
+	}
+
+	/// Moves to `offset` of the input, or to its end
+	#[inline]
+	pub fn seek(&mut self, offset: usize) {
+		let buffer = Self::buffer(self.input, self.aligned.as_deref());
+
+		self.raw = RawTokenizer::new(buffer, self.raw.lanes);
+		self.advance(offset);
+	}
+
+	/// Iterator over tokens along with their locations, see [`Self::next_spanned`]
+	#[inline]
+	pub fn spanned(mut self) -> impl Iterator<Item = SpannedResult<'a>> {
+		std::iter::from_fn(move || match self.next_spanned() {
+			Err(Spanned { value: Error::EOF, .. }) => None,
+			res => Some(res),
+		})
+	}
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+	type Item = Result<'a>;
+
+	#[inline]
+	fn next(&mut self) -> Option<Self::Item> {
+		match self.next_token() {
+			Err(Error::EOF) => None,
+			res => Some(res),
+		}
+	}
+}
@@ -1,5 +1,12 @@
 pub use comment::tokenize_comment_simd;
+pub use lanes::LaneWidth;
+pub use name::consume_name_simd;
+pub use string::tokenize_string_simd;
+pub use whitespace::tokenize_whitespace_simd;
 
 mod comment;
+mod lanes;
+mod name;
 mod string;
 mod utils;
+mod whitespace;
@@ -2,63 +2,67 @@ use std::simd::{cmp::SimdPartialEq, Simd};
 
 use consts::ASCII;
 
-use crate::{Error, Result, Token, Tokenizer, LANE_WIDTH, LANE_WIDTH_MASK};
+use super::{
+	lanes::dispatch,
+	utils::{load, Lane},
+};
+use crate::{Error, RawResult, RawToken, RawTokenizer};
+
+dispatch! {
+	pub fn tokenize_comment_simd() -> RawResult => tokenize_comment
+}
 
-#[inline]
-pub fn tokenize_comment_simd(tokenizer: &mut Tokenizer) -> Result {
+#[inline(always)]
+fn tokenize_comment<const N: usize>(tokenizer: &mut RawTokenizer) -> RawResult {
 	macro_rules! token {
 		($ptr: expr) => {
-			Ok(Token::from(Token::Comment, $ptr, tokenizer.cursor))
+			Ok(RawToken::from(RawToken::Comment, $ptr, tokenizer.cursor))
 		};
 	}
 
 	let at_ptr = tokenizer.cursor;
 	// go back to alignment bounds, if not aligned
-	let misalignment = at_ptr.addr() & LANE_WIDTH_MASK;
+	let misalignment = at_ptr.addr() & (N - 1);
 	// skip every match before cursor
 	let mut skip_mask = u64::MAX << misalignment;
-	let mut aligned_ptr = unsafe { at_ptr.sub(misalignment) };
+	let mut aligned_ptr = at_ptr.wrapping_sub(misalignment);
 
 	// vector
-	while aligned_ptr.addr() + LANE_WIDTH <= tokenizer.eof.addr() {
+	while aligned_ptr.addr() + N <= tokenizer.eof.addr() {
 		// this probably won't work on big endian
-		const ASTERISKS_MASK: Simd<u8, LANE_WIDTH> = Simd::splat(b'*');
-		const SLASHES_MASK: Simd<u8, LANE_WIDTH> = Simd::splat(b'/');
-		const BORDER_STAR: u64 = 1 << LANE_WIDTH_MASK;
-
-		// noop
-		let chunk = aligned_ptr as *const [u8; LANE_WIDTH];
+		let asterisks_mask: Lane<N> = Simd::splat(b'*');
+		let slashes_mask: Lane<N> = Simd::splat(b'/');
+		let border_star: u64 = 1 << (N - 1);
 
-		let op = unsafe { Simd::from_array(*chunk) };
+		let op = load(aligned_ptr);
 
 		// should be good for OoOE
-		let asterisks = op.simd_eq(ASTERISKS_MASK).to_bitmask();
-		let slashes = op.simd_eq(SLASHES_MASK).to_bitmask();
+		let asterisks = op.simd_eq(asterisks_mask).to_bitmask();
+		let slashes = op.simd_eq(slashes_mask).to_bitmask();
 		let shifted_slashed = slashes >> 1;
 
 		let result = asterisks & shifted_slashed & skip_mask;
 
 		if result > 0 {
-			tokenizer.cursor = unsafe { aligned_ptr.add(result.trailing_zeros() as usize) };
+			tokenizer.cursor = aligned_ptr.wrapping_add(result.trailing_zeros() as usize);
 			return token!(at_ptr);
 		}
 
-		// aligned_ptr = unsafe { aligned_ptr.add(LANE_WIDTH) };
 		// REASON: i want this to be fast even with `#[cfg(debug_assertions)]` or non-release build
-		// could technically overflow tho, but highly unlikely
-		aligned_ptr = (aligned_ptr.addr() + LANE_WIDTH) as *const u8;
+		aligned_ptr = aligned_ptr.wrapping_add(N);
 
 		// check comment span over chunk border
-		if asterisks & BORDER_STAR > 0 && aligned_ptr < tokenizer.eof && unsafe { *aligned_ptr } == b'/'
+		if asterisks & border_star > 0 && aligned_ptr < tokenizer.eof && unsafe { *aligned_ptr } == b'/'
 		{
-			tokenizer.cursor = unsafe { aligned_ptr.sub(1) };
+			tokenizer.cursor = aligned_ptr.wrapping_sub(1);
 			return token!(at_ptr);
 		}
 
 		skip_mask = u64::MAX;
 	}
 
-	tokenizer.cursor = aligned_ptr;
+	// Vector loop might not run at all, never go back before the comment
+	tokenizer.cursor = aligned_ptr.max(at_ptr);
 
 	// scalar
 	while !tokenizer.is_eof() {
@@ -86,9 +90,12 @@ mod test {
 	use consts::ASCII;
 
 	use super::tokenize_comment_simd;
-	use crate::{simd::utils::copy_to_aligned, Error, Result, Token, Tokenizer, LANE_WIDTH_MASK};
+	use crate::{
+		simd::utils::{copy_to_aligned, supported_lanes},
+		Error, RawResult, RawToken, RawTokenizer, MAX_LANE_WIDTH,
+	};
 
-	fn scalar<S: AsRef<[u8]>>(bytes: S) -> Result {
+	fn scalar<S: AsRef<[u8]>>(bytes: S) -> RawResult {
 		let bytes = bytes.as_ref();
 
 		for (i, seq) in bytes.windows(2).enumerate() {
@@ -98,7 +105,7 @@ mod test {
 
 			let ptr = bytes.as_ptr();
 
-			return Ok(Token::from(Token::Comment, ptr, unsafe { ptr.add(i) }));
+			return Ok(RawToken::from(RawToken::Comment, ptr, unsafe { ptr.add(i) }));
 		}
 
 		Err(Error::BadComment)
@@ -107,11 +114,13 @@ mod test {
 	fn helper<S: AsRef<[u8]>>(input: S) {
 		let input = copy_to_aligned(input);
 
-		let mut tokenizer = Tokenizer::new(input);
-		let scalar_res = scalar(input);
-		let simd_res = tokenize_comment_simd(&mut tokenizer);
+		for lanes in supported_lanes() {
+			let mut tokenizer = RawTokenizer::new(input, lanes);
+			let scalar_res = scalar(input);
+			let simd_res = tokenize_comment_simd(&mut tokenizer);
 
-		assert_eq!(scalar_res, simd_res);
+			assert_eq!(scalar_res, simd_res, "{lanes}");
+		}
 	}
 
 	#[test]
@@ -123,10 +132,34 @@ mod test {
 
 	#[test]
 	fn span_over_chunk_border() {
-		let len = LANE_WIDTH_MASK;
-		let lane_fill = "*".repeat(len);
-		let subject = format!("{lane_fill}*/ div {{}}");
+		for lanes in supported_lanes() {
+			let lane_fill = "*".repeat(lanes.bytes() - 1);
+			let subject = format!("{lane_fill}*/ div {{}}");
+
+			helper(subject);
+		}
+	}
+
+	#[test]
+	fn starts_in_last_lane() {
+		const START: usize = 4;
+
+		// Shorter than a lane, so scalar loop does all the work
+		let input = &copy_to_aligned("a /*/ */")[..8];
+
+		for lanes in supported_lanes() {
+			let mut tokenizer = RawTokenizer::new(input, lanes);
+			tokenizer.advance(START);
+
+			assert_eq!(scalar(&input[START..]), tokenize_comment_simd(&mut tokenizer));
+		}
+	}
+
+	#[test]
+	fn span_over_widest_lanes() {
+		let fill = "a".repeat(MAX_LANE_WIDTH * 2 + 7);
 
-		helper(subject);
+		helper(format!("{fill}*/"));
+		helper(format!("{fill}*"));
 	}
 }
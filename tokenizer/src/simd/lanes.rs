@@ -0,0 +1,113 @@
+/// Width of SIMD lanes scanners run with, in bytes
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum LaneWidth {
+	W16,
+	W32,
+	W64,
+}
+
+impl LaneWidth {
+	pub const ALL: [LaneWidth; 3] = [LaneWidth::W16, LaneWidth::W32, LaneWidth::W64];
+
+	#[inline(always)]
+	pub const fn bytes(self) -> usize {
+		match self {
+			LaneWidth::W16 => 16,
+			LaneWidth::W32 => 32,
+			LaneWidth::W64 => 64,
+		}
+	}
+
+	/// Widest lanes current CPU has instructions for.
+	///
+	/// On x86_64 32 bytes need AVX2 and 64 bytes need AVX-512BW,
+	/// elsewhere portable 16 byte lanes are used
+	#[inline]
+	pub fn detect() -> Self {
+		Self::ALL
+			.into_iter()
+			.rev()
+			.find(|&width| width == LaneWidth::W16 || width.is_native())
+			.unwrap_or(LaneWidth::W16)
+	}
+
+	/// Lanes can be used on current CPU.
+	///
+	/// Every width is supported outside of x86_64, although anything above 16 bytes
+	/// runs a portable kernel there
+	#[inline]
+	pub fn is_supported(self) -> bool {
+		cfg!(not(target_arch = "x86_64")) || self == LaneWidth::W16 || self.is_native()
+	}
+
+	#[inline]
+	fn is_native(self) -> bool {
+		#[cfg(target_arch = "x86_64")]
+		{
+			match self {
+				LaneWidth::W16 => true,
+				LaneWidth::W32 => is_x86_feature_detected!("avx2"),
+				LaneWidth::W64 => {
+					is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw")
+				}
+			}
+		}
+
+		#[cfg(not(target_arch = "x86_64"))]
+		{
+			false
+		}
+	}
+}
+
+impl std::fmt::Display for LaneWidth {
+	#[inline]
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} bytes", self.bytes())
+	}
+}
+
+/// Defines `$name`, that runs `$kernel::<N>` with lane width of the tokenizer,
+/// `tokenizer: &mut RawTokenizer` is always the first argument of both.
+///
+/// On x86_64 wider kernels are compiled with AVX2 or AVX-512 enabled,
+/// so `$kernel` has to be `#[inline(always)]` to be compiled with them as well
+macro_rules! dispatch {
+	(
+		$(#[$meta:meta])*
+		$vis:vis fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty => $kernel:ident
+	) => {
+		$(#[$meta])*
+		#[inline]
+		$vis fn $name(tokenizer: &mut RawTokenizer, $($arg: $ty),*) -> $ret {
+			#[cfg(target_arch = "x86_64")]
+			#[target_feature(enable = "avx2")]
+			unsafe fn avx2(tokenizer: &mut RawTokenizer, $($arg: $ty),*) -> $ret {
+				$kernel::<32>(tokenizer, $($arg),*)
+			}
+
+			#[cfg(target_arch = "x86_64")]
+			#[target_feature(enable = "avx512f,avx512bw")]
+			unsafe fn avx512(tokenizer: &mut RawTokenizer, $($arg: $ty),*) -> $ret {
+				$kernel::<64>(tokenizer, $($arg),*)
+			}
+
+			match tokenizer.lanes {
+				$crate::LaneWidth::W16 => $kernel::<16>(tokenizer, $($arg),*),
+
+				// SAFETY: `RawTokenizer` is never created with lanes CPU does not support
+				#[cfg(target_arch = "x86_64")]
+				$crate::LaneWidth::W32 => unsafe { avx2(tokenizer, $($arg),*) },
+				#[cfg(target_arch = "x86_64")]
+				$crate::LaneWidth::W64 => unsafe { avx512(tokenizer, $($arg),*) },
+
+				#[cfg(not(target_arch = "x86_64"))]
+				$crate::LaneWidth::W32 => $kernel::<32>(tokenizer, $($arg),*),
+				#[cfg(not(target_arch = "x86_64"))]
+				$crate::LaneWidth::W64 => $kernel::<64>(tokenizer, $($arg),*),
+			}
+		}
+	};
+}
+
+pub(crate) use dispatch;
@@ -0,0 +1,171 @@
+use std::simd::{
+	cmp::{SimdPartialEq, SimdPartialOrd},
+	Simd,
+};
+
+use consts::ASCII;
+
+use super::{
+	lanes::dispatch,
+	utils::{skip_until, Lane, LaneMask},
+};
+use crate::{utils::ByteHelper, RawTokenizer};
+
+dispatch! {
+	/// Steps over ident chars and escapes
+	pub fn consume_name_simd() -> () => consume_name
+}
+
+/// Lanes matching anything but `[a-zA-Z0-9_-]` and non-ASCII bytes
+#[inline(always)]
+fn non_ident_chars<const N: usize>(op: Lane<N>) -> LaneMask<N> {
+	let case_bit = Simd::splat(0x20);
+	let lower_a = Simd::splat(b'a');
+	let alpha_len = Simd::splat(26);
+	let zero = Simd::splat(b'0');
+	let digit_len = Simd::splat(10);
+	let underscores = Simd::splat(ASCII::UNDERSCORE);
+	let dashes = Simd::splat(ASCII::DASH);
+	let non_ascii = Simd::splat(0x80);
+
+	// wrapping sub turns both range checks into a single unsigned comparison
+	let alpha = ((op | case_bit) - lower_a).simd_lt(alpha_len);
+	let digit = (op - zero).simd_lt(digit_len);
+
+	let ident =
+		alpha | digit | op.simd_eq(underscores) | op.simd_eq(dashes) | op.simd_ge(non_ascii);
+
+	!ident
+}
+
+#[inline(always)]
+fn consume_name<const N: usize>(tokenizer: &mut RawTokenizer) {
+	loop {
+		tokenizer.cursor = skip_until(tokenizer.cursor, tokenizer.eof, non_ident_chars::<N>);
+
+		// Partial lane before EOF is checked here as well
+		if tokenizer.try_peek(0).is_ident_char() {
+			tokenizer.advance(1);
+		} else if tokenizer.is_valid_escape(0) {
+			tokenizer.consume_escape();
+		} else {
+			break;
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::consume_name_simd;
+	use crate::{
+		simd::utils::{copy_to_aligned, supported_lanes},
+		utils::ByteHelper,
+		RawTokenizer, MAX_LANE_WIDTH,
+	};
+
+	/// Byte by byte, returns length of the name
+	fn scalar(bytes: &[u8]) -> usize {
+		let mut i = 0;
+
+		loop {
+			match bytes.get(i) {
+				Some(byte) if byte.is_ident_char() => i += 1,
+				Some(b'\\') if !matches!(bytes.get(i + 1), Some(b'\n' | b'\r' | 0x0C)) => {
+					i += 1;
+
+					let hex = bytes[i..]
+						.iter()
+						.take(6)
+						.take_while(|byte| byte.is_hex_digit())
+						.count();
+
+					if hex == 0 {
+						i = (i + 1).min(bytes.len());
+						continue;
+					}
+
+					i += hex;
+
+					if bytes[i..].starts_with(b"\r\n") {
+						i += 2;
+					} else if bytes.get(i).copied().is_whitespace() {
+						i += 1;
+					}
+				}
+				_ => return i,
+			}
+		}
+	}
+
+	fn helper_at<S: AsRef<[u8]>>(input: S, start: usize) {
+		let len = input.as_ref().len();
+		let input = &copy_to_aligned(input)[..len];
+
+		for lanes in supported_lanes() {
+			let mut tokenizer = RawTokenizer::new(input, lanes);
+			tokenizer.advance(start);
+
+			consume_name_simd(&mut tokenizer);
+			let simd_len = tokenizer.cursor.addr() - input.as_ptr().addr() - start;
+
+			assert_eq!(
+				scalar(&input[start..]),
+				simd_len,
+				"{lanes}: {:?}",
+				String::from_utf8_lossy(input)
+			);
+		}
+	}
+
+	fn helper<S: AsRef<[u8]>>(input: S) {
+		helper_at(input, 0);
+	}
+
+	#[test]
+	fn main() {
+		helper("div {}");
+		helper("--custom-property_1: 0");
+		helper("ÿnïcödé.class");
+		helper("AZaz09_-@[`{/:");
+		helper("name\\:hover");
+		helper("trailing-backslash\\");
+		helper("backslash-newline\\\n");
+		helper("\\31 0col\\0000411\\a\r\nb c");
+	}
+
+	#[test]
+	fn span_over_lanes() {
+		let fill = "a-Z_9".repeat(MAX_LANE_WIDTH);
+
+		helper(format!("{fill} div"));
+		helper(format!("{fill}\\.{fill}("));
+		helper(&fill);
+	}
+
+	#[test]
+	fn escape_over_lane_border() {
+		for offset in 0..MAX_LANE_WIDTH {
+			let fill = "a".repeat(MAX_LANE_WIDTH * 2 - 1 - offset);
+
+			helper(format!("{fill}\\.b c"));
+			helper(format!("{fill}\\\n"));
+		}
+	}
+
+	#[test]
+	fn every_byte() {
+		for byte in 0..=u8::MAX {
+			let mut input = vec![b'a'; MAX_LANE_WIDTH + 3];
+			input[MAX_LANE_WIDTH - 2] = byte;
+
+			helper(&input);
+		}
+	}
+
+	#[test]
+	fn starts_in_the_middle_of_lane() {
+		let input = format!(".class-{}.b", "c".repeat(MAX_LANE_WIDTH));
+
+		helper_at(&input, 1);
+	}
+}
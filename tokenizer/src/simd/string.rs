@@ -1,5 +1,152 @@
-use crate::Tokenizer;
+use std::simd::{cmp::SimdPartialEq, Simd};
 
-pub fn tokenize_string_simd(tokenizer: &mut Tokenizer) {
-	todo!();
+use consts::ASCII;
+
+use super::{
+	lanes::dispatch,
+	utils::{skip_until, Lane},
+};
+use crate::{Error, RawResult, RawToken, RawTokenizer};
+
+dispatch! {
+	/// String content right after opening `quote`, leaves cursor at the closing quote
+	pub fn tokenize_string_simd(quote: u8) -> RawResult => tokenize_string
+}
+
+#[inline(always)]
+fn tokenize_string<const N: usize>(tokenizer: &mut RawTokenizer, quote: u8) -> RawResult {
+	let backslashes: Lane<N> = Simd::splat(ASCII::BACKSLASH);
+	let lfs: Lane<N> = Simd::splat(ASCII::LF);
+	let crs: Lane<N> = Simd::splat(ASCII::CR);
+
+	let at_ptr = tokenizer.cursor;
+	let quotes: Lane<N> = Simd::splat(quote);
+
+	loop {
+		// Jump to the next byte that needs a look, plain content is skipped lane by lane
+		tokenizer.cursor = skip_until(tokenizer.cursor, tokenizer.eof, |op| {
+			op.simd_eq(quotes) | op.simd_eq(backslashes) | op.simd_eq(lfs) | op.simd_eq(crs)
+		});
+
+		if tokenizer.is_eof() {
+			return Err(Error::BadString);
+		}
+
+		match tokenizer.current_byte() {
+			ch if ch == quote => {
+				return Ok(RawToken::from(RawToken::String, at_ptr, tokenizer.cursor))
+			}
+
+			// Unescaped newline - parse error
+			ASCII::LF | ASCII::CR => {
+				tokenizer.advance(1);
+				return Err(Error::BadString);
+			}
+
+			// Escaped quote, backslash or newline (`\r\n` included) are part of the string
+			ASCII::BACKSLASH => {
+				let len = if tokenizer.lookahead(b"\\\r\n") { 3 } else { 2 };
+				tokenizer.advance(len.min(tokenizer.rem()));
+			}
+
+			// Partial lane before EOF
+			_ => tokenizer.advance(1),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use consts::ASCII;
+
+	use super::tokenize_string_simd;
+	use crate::{
+		simd::utils::{copy_to_aligned, supported_lanes},
+		Error, RawResult, RawToken, RawTokenizer, MAX_LANE_WIDTH,
+	};
+
+	/// Byte by byte, returns token and offset of the cursor after it
+	fn scalar(bytes: &[u8], quote: u8) -> (RawResult, usize) {
+		let ptr = bytes.as_ptr();
+		let mut i = 0;
+
+		while i < bytes.len() {
+			match bytes[i] {
+				ch if ch == quote => {
+					return (
+						Ok(RawToken::from(RawToken::String, ptr, unsafe { ptr.add(i) })),
+						i,
+					)
+				}
+				ASCII::LF | ASCII::CR => return (Err(Error::BadString), i + 1),
+				ASCII::BACKSLASH if bytes[i..].starts_with(b"\\\r\n") => i += 3,
+				ASCII::BACKSLASH => i += 2,
+				_ => i += 1,
+			}
+		}
+
+		(Err(Error::BadString), i.min(bytes.len()))
+	}
+
+	fn helper_at<S: AsRef<[u8]>>(input: S, quote: u8, start: usize) {
+		let len = input.as_ref().len();
+		let input = &copy_to_aligned(input)[..len];
+
+		for lanes in supported_lanes() {
+			let mut tokenizer = RawTokenizer::new(input, lanes);
+			tokenizer.advance(start);
+
+			let simd_res = tokenize_string_simd(&mut tokenizer, quote);
+			let simd_pos = tokenizer.cursor.addr() - input.as_ptr().addr() - start;
+
+			assert_eq!(
+				scalar(&input[start..], quote),
+				(simd_res, simd_pos),
+				"{lanes}: {:?}",
+				String::from_utf8_lossy(input)
+			);
+		}
+	}
+
+	fn helper<S: AsRef<[u8]>>(input: S, quote: u8) {
+		helper_at(input, quote, 0);
+	}
+
+	#[test]
+	fn main() {
+		helper("string\" div {}", b'"');
+		helper("string' \" div {}", b'\'');
+		helper("no closing quote", b'"');
+		helper("newline \n\" ", b'"');
+		helper("carriage return \r\" ", b'"');
+		helper(r#"escaped \" \\" div"#, b'"');
+		helper("escaped newline \\\r\n still \\\n string\"", b'"');
+		helper("trailing backslash \\", b'"');
+	}
+
+	#[test]
+	fn span_over_lanes() {
+		let fill = "a".repeat(MAX_LANE_WIDTH * 3 + 5);
+
+		helper(format!("{fill}\""), b'"');
+		helper(format!("{fill}\\\"{fill}\""), b'"');
+		helper(format!("{fill}\n\""), b'"');
+		helper(&fill, b'"');
+	}
+
+	#[test]
+	fn escape_over_lane_border() {
+		for offset in 0..MAX_LANE_WIDTH {
+			let fill = "a".repeat(MAX_LANE_WIDTH * 2 - 1 - offset);
+
+			helper(format!("{fill}\\\"\""), b'"');
+			helper(format!("{fill}\\\r\n\""), b'"');
+			helper(format!("{fill}\\\\\"'"), b'"');
+		}
+	}
+
+	#[test]
+	fn starts_in_the_middle_of_lane() {
+		helper_at(r#"a: " \" \\ string with ' in it" div"#, b'"', 4);
+	}
 }
@@ -1,16 +1,60 @@
+use std::simd::{Mask, Simd};
+
+pub type Lane<const N: usize> = Simd<u8, N>;
+pub type LaneMask<const N: usize> = Mask<i8, N>;
+
+/// Loads a lane, `ptr` has to be aligned and lane has to end at or before EOF
+#[inline(always)]
+pub fn load<const N: usize>(ptr: *const u8) -> Lane<N> {
+	debug_assert!(ptr.addr() & (N - 1) == 0);
+
+	unsafe { Simd::from_array(*(ptr as *const [u8; N])) }
+}
+
+/// Returns first byte at or after `from` that `stop` matches.
+///
+/// Only whole aligned lanes before `eof` are scanned, if none matched,
+/// pointer to the remaining partial lane is returned and the rest is up to the caller
+#[inline(always)]
+pub fn skip_until<const N: usize, F>(from: *const u8, eof: *const u8, stop: F) -> *const u8
+where
+	F: Fn(Lane<N>) -> LaneMask<N>,
+{
+	// go back to alignment bounds, if not aligned
+	let misalignment = from.addr() & (N - 1);
+	// skip every match before `from`
+	let mut skip_mask = u64::MAX << misalignment;
+	let mut aligned_ptr = from.wrapping_sub(misalignment);
+
+	while aligned_ptr.addr() + N <= eof.addr() {
+		let result = stop(load(aligned_ptr)).to_bitmask() & skip_mask;
+
+		if result > 0 {
+			return aligned_ptr.wrapping_add(result.trailing_zeros() as usize);
+		}
+
+		aligned_ptr = aligned_ptr.wrapping_add(N);
+		skip_mask = u64::MAX;
+	}
+
+	// Vector loop might not run at all, never go back before `from`
+	aligned_ptr.max(from)
+}
+
+/// Copies `bytes` into a buffer aligned to the widest lanes, padded with `-` up to the next lane
 #[cfg(test)]
 pub fn copy_to_aligned<S: AsRef<[u8]>>(bytes: S) -> &'static [u8] {
-	use crate::{LANE_WIDTH, LANE_WIDTH_MASK};
+	use crate::MAX_LANE_WIDTH;
 
-	const FILL: [u8; LANE_WIDTH] = [b'-'; LANE_WIDTH];
+	const FILL: [u8; MAX_LANE_WIDTH] = [b'-'; MAX_LANE_WIDTH];
 	let bytes = bytes.as_ref();
 
 	let old_len = bytes.len();
 	let old_buf = bytes.as_ptr();
 
-	let rem_width = LANE_WIDTH - (old_len & LANE_WIDTH_MASK);
+	let rem_width = MAX_LANE_WIDTH - (old_len & (MAX_LANE_WIDTH - 1));
 	let new_len = old_len + rem_width;
-	let layout = std::alloc::Layout::from_size_align(new_len, LANE_WIDTH).expect("layout");
+	let layout = std::alloc::Layout::from_size_align(new_len, MAX_LANE_WIDTH).expect("layout");
 	let new_buf = unsafe { std::alloc::alloc(layout) };
 
 	if new_buf.is_null() {
@@ -24,3 +68,9 @@ pub fn copy_to_aligned<S: AsRef<[u8]>>(bytes: S) -> &'static [u8] {
 		std::slice::from_raw_parts(new_buf, new_len)
 	}
 }
+
+/// Every lane width current CPU supports, tests run each of them
+#[cfg(test)]
+pub fn supported_lanes() -> impl Iterator<Item = crate::LaneWidth> {
+	crate::LaneWidth::ALL.into_iter().filter(|lanes| lanes.is_supported())
+}
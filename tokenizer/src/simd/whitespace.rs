@@ -0,0 +1,153 @@
+use std::simd::{cmp::SimdPartialEq, Simd};
+
+use consts::ASCII;
+
+use super::{
+	lanes::dispatch,
+	utils::{load, Lane},
+};
+use crate::{RawToken, RawTokenizer};
+
+dispatch! {
+	/// Steps over spaces, tabs and newlines, token reports if there was a newline
+	pub fn tokenize_whitespace_simd() -> RawToken => tokenize_whitespace
+}
+
+#[inline(always)]
+fn tokenize_whitespace<const N: usize>(tokenizer: &mut RawTokenizer) -> RawToken {
+	let spaces: Lane<N> = Simd::splat(ASCII::SPACE);
+	let tabs: Lane<N> = Simd::splat(ASCII::TAB);
+	let lfs: Lane<N> = Simd::splat(ASCII::LF);
+	let crs: Lane<N> = Simd::splat(ASCII::CR);
+
+	let at_ptr = tokenizer.cursor;
+	let mut is_newline = false;
+
+	// go back to alignment bounds, if not aligned
+	let misalignment = at_ptr.addr() & (N - 1);
+	// skip every byte before cursor
+	let mut skip_mask = u64::MAX << misalignment;
+	let mut aligned_ptr = at_ptr.wrapping_sub(misalignment);
+
+	// vector
+	while aligned_ptr.addr() + N <= tokenizer.eof.addr() {
+		let op = load(aligned_ptr);
+
+		let newlines = op.simd_eq(lfs) | op.simd_eq(crs);
+		let whitespace = newlines | op.simd_eq(spaces) | op.simd_eq(tabs);
+
+		let newlines = newlines.to_bitmask() & skip_mask;
+		let end = (!whitespace).to_bitmask() & skip_mask;
+
+		if end > 0 {
+			let len = end.trailing_zeros();
+			// only newlines before the first non-whitespace byte count
+			let before_end = !(u64::MAX << len);
+
+			tokenizer.cursor = aligned_ptr.wrapping_add(len as usize);
+			return RawToken::Whitespace(is_newline || newlines & before_end > 0);
+		}
+
+		is_newline |= newlines > 0;
+		aligned_ptr = aligned_ptr.wrapping_add(N);
+		skip_mask = u64::MAX;
+	}
+
+	// Vector loop might not run at all, never go back before the whitespace
+	tokenizer.cursor = aligned_ptr.max(at_ptr);
+
+	// scalar
+	while !tokenizer.is_eof() {
+		match tokenizer.current_byte() {
+			ASCII::LF | ASCII::CR => is_newline = true,
+			ASCII::TAB | ASCII::SPACE => {}
+			_ => break,
+		}
+
+		tokenizer.advance(1);
+	}
+
+	RawToken::Whitespace(is_newline)
+}
+
+#[cfg(test)]
+mod test {
+	use super::tokenize_whitespace_simd;
+	use crate::{
+		simd::utils::{copy_to_aligned, supported_lanes},
+		RawToken, RawTokenizer, MAX_LANE_WIDTH,
+	};
+
+	/// Byte by byte, returns token and its length
+	fn scalar(bytes: &[u8]) -> (RawToken, usize) {
+		let len = bytes
+			.iter()
+			.position(|byte| !matches!(byte, b' ' | b'\t' | b'\n' | b'\r'))
+			.unwrap_or(bytes.len());
+		let is_newline = bytes[..len]
+			.iter()
+			.any(|byte| matches!(byte, b'\n' | b'\r'));
+
+		(RawToken::Whitespace(is_newline), len)
+	}
+
+	fn helper_at<S: AsRef<[u8]>>(input: S, start: usize) {
+		let len = input.as_ref().len();
+		let input = &copy_to_aligned(input)[..len];
+
+		for lanes in supported_lanes() {
+			let mut tokenizer = RawTokenizer::new(input, lanes);
+			tokenizer.advance(start);
+
+			let simd_res = tokenize_whitespace_simd(&mut tokenizer);
+			let simd_len = tokenizer.cursor.addr() - input.as_ptr().addr() - start;
+
+			assert_eq!(
+				scalar(&input[start..]),
+				(simd_res, simd_len),
+				"{lanes}: {:?}",
+				String::from_utf8_lossy(input)
+			);
+		}
+	}
+
+	fn helper<S: AsRef<[u8]>>(input: S) {
+		helper_at(input, 0);
+	}
+
+	#[test]
+	fn main() {
+		helper(" div");
+		helper("\n\t\tdiv");
+		helper(" \t \r\n  ");
+		helper("   ");
+	}
+
+	#[test]
+	fn span_over_lanes() {
+		let fill = " \t".repeat(MAX_LANE_WIDTH * 2);
+
+		helper(format!("{fill}div"));
+		helper(format!("{fill}\ndiv"));
+		helper(format!("\n{fill}div"));
+		helper(&fill);
+	}
+
+	#[test]
+	fn newline_after_the_end() {
+		for offset in 0..MAX_LANE_WIDTH {
+			let fill = " ".repeat(offset);
+
+			helper(format!("{fill}a\n{}", "b".repeat(MAX_LANE_WIDTH)));
+		}
+	}
+
+	#[test]
+	fn starts_in_the_middle_of_lane() {
+		let input = format!("a\nb{}c", " ".repeat(MAX_LANE_WIDTH));
+
+		for start in [1, 3] {
+			helper_at(&input, start);
+		}
+	}
+}
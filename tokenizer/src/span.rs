@@ -1,4 +1,51 @@
-pub(crate) struct Span {
-	start: *const u8,
-	end: *const u8,
+use std::ops::Range;
+
+/// Byte offsets of a token or an error in the input, `end` is exclusive
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Span {
+	pub start: usize,
+	pub end: usize,
+}
+
+/// Value along with its location in the input
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Spanned<T> {
+	pub value: T,
+	pub span: Span,
+}
+
+impl Span {
+	#[inline(always)]
+	pub const fn new(start: usize, end: usize) -> Self {
+		Self { start, end }
+	}
+
+	#[inline(always)]
+	pub const fn is_empty(&self) -> bool {
+		self.start == self.end
+	}
+
+	#[inline(always)]
+	pub const fn len(&self) -> usize {
+		self.end - self.start
+	}
+
+	#[inline(always)]
+	pub fn range(&self) -> Range<usize> {
+		self.start..self.end
+	}
+}
+
+impl From<Span> for Range<usize> {
+	#[inline(always)]
+	fn from(span: Span) -> Self {
+		span.range()
+	}
+}
+
+impl std::fmt::Display for Span {
+	#[inline]
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}..{}", self.start, self.end)
+	}
 }
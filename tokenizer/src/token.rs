@@ -1,25 +1,37 @@
+use consts::ASCII;
+
+use crate::utils::ByteHelper;
+
 // WARNING: KEEP IT COPYABLE
 // Actually do not touch it at all
 #[non_exhaustive]
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub enum Token {
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum RawToken {
 	/// Comment token - will not include starting `/*` and ending `*/`
 	Comment(*const u8, u32), // Formatter should preserve comments
 
 	Ident(*const u8, u32),
 	Function(*const u8, u32),
-	// URL(*const u8, u32),
-	// TODO: ^
-	// BadURL, // Not supported
+	/// Unquoted url token - will not include surrounding `url(` and `)`
+	URL(*const u8, u32),
+	BadURL,
 	AtRule(*const u8, u32),
 	Hash(*const u8, u32),
 	/// String token - will not include surrounding quotes
 	String(*const u8, u32),
 	Number(*const u8, u32),
+	/// Percentage token - will not include trailing `%`
+	Percentage(*const u8, u32),
+	/// Dimension token - both value and unit
+	Dimension(*const u8, u32),
+	UnicodeRange(*const u8, u32),
+	/// `<!--`
+	CDO,
+	/// `-->`
+	CDC,
 
 	Delim(u8),
-	// Percentage, // Number
-	// Dimension, // Number
 	/// Whitespace token - any amount of whitespace that matches: [\s*]
 	Whitespace(bool),
 	Colon,
@@ -33,23 +45,69 @@ pub enum Token {
 	BracketCurlyClose,
 }
 
+/// Same as [`RawToken`], but borrows from the input
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Token<'a> {
+	/// Comment token - will not include starting `/*` and ending `*/`
+	Comment(&'a [u8]),
+
+	Ident(&'a [u8]),
+	Function(&'a [u8]),
+	/// Unquoted url token - will not include surrounding `url(` and `)`
+	URL(&'a [u8]),
+	/// Malformed unquoted url, e.g. `url(a b)`
+	BadURL,
+	AtRule(&'a [u8]),
+	Hash(&'a [u8]),
+	/// String token - will not include surrounding quotes
+	String(&'a [u8]),
+	Number(&'a [u8]),
+	/// Percentage token - will not include trailing `%`
+	Percentage(&'a [u8]),
+	Dimension {
+		value: &'a [u8],
+		unit: &'a [u8],
+	},
+	/// Unicode range token, e.g. `U+0025-00FF` or `u+4??`
+	UnicodeRange(&'a [u8]),
+	/// `<!--`
+	CDO,
+	/// `-->`
+	CDC,
+
+	Delim(u8),
+	/// Whitespace token - `true` if it contains a linebreak
+	Whitespace(bool),
+	Colon,
+	Semicolon,
+	Comma,
+	BracketRoundOpen,
+	BracketRoundClose,
+	BracketSquareOpen,
+	BracketSquareClose,
+	BracketCurlyOpen,
+	BracketCurlyClose,
+}
+
 const _ASSERT_SIZE: () = {
 	const SIZE_LIMIT: usize = 16;
 	macro_rules! m {
 		() => {
-			"Token struct size exceeds 16 bytes"
+			"RawToken struct size exceeds 16 bytes"
 		};
 	}
 	use std::mem::size_of;
-	assert!(size_of::<Token>() == SIZE_LIMIT, m!());
-	assert!(size_of::<Option<Token>>() == SIZE_LIMIT, m!());
-	assert!(size_of::<Result<Option<Token>, ()>>() == SIZE_LIMIT, m!());
-	assert!(size_of::<crate::Result>() == SIZE_LIMIT, m!());
+	assert!(size_of::<RawToken>() == SIZE_LIMIT, m!());
+	assert!(size_of::<Option<RawToken>>() == SIZE_LIMIT, m!());
+	assert!(size_of::<Result<Option<RawToken>, ()>>() == SIZE_LIMIT, m!());
+	assert!(size_of::<crate::RawResult>() == SIZE_LIMIT, m!());
 };
 
 const _ASSERT_TRANSMUTE: () = {
 	const LENGTH: usize = 10;
-	let token = Token::Ident(std::ptr::null(), LENGTH as u32);
+	let token = RawToken::Ident(std::ptr::null(), LENGTH as u32);
 	let bytes = token.bytes();
 	assert!((bytes as *const u8).is_null());
 	assert!(bytes.len() == LENGTH);
@@ -57,23 +115,27 @@ const _ASSERT_TRANSMUTE: () = {
 
 macro_rules! bytes_pat {
 	($($des: tt)*) => {
-		| Token::Comment($($des)*)
-		| Token::Ident($($des)*)
-		| Token::Function($($des)*)
-		| Token::AtRule($($des)*)
-		| Token::Hash($($des)*)
-		| Token::String($($des)*)
-		| Token::Number($($des)*)
+		| RawToken::Comment($($des)*)
+		| RawToken::Ident($($des)*)
+		| RawToken::Function($($des)*)
+		| RawToken::AtRule($($des)*)
+		| RawToken::Hash($($des)*)
+		| RawToken::String($($des)*)
+		| RawToken::Number($($des)*)
+		| RawToken::Percentage($($des)*)
+		| RawToken::Dimension($($des)*)
+		| RawToken::UnicodeRange($($des)*)
+		| RawToken::URL($($des)*)
 	};
 }
 
-impl Token {
+impl RawToken {
 	#[inline]
 	pub const fn bytes(self) -> *const [u8] {
 		debug_assert!(matches!(self, bytes_pat!(..)));
 
 		// HACK: Dangerous AF - tests should cover it
-		let (_, len, ptr) = unsafe { std::mem::transmute::<Token, (u32, u32, *const u8)>(self) };
+		let (_, len, ptr) = unsafe { std::mem::transmute::<RawToken, (u32, u32, *const u8)>(self) };
 		std::ptr::slice_from_raw_parts(ptr, len as usize)
 	}
 
@@ -86,7 +148,142 @@ impl Token {
 	}
 }
 
-impl std::fmt::Display for Token {
+/// Length of the number at the start of a dimension, e.g. `1.5e3` of `1.5e3px`
+#[inline]
+fn number_len(bytes: &[u8]) -> usize {
+	let digits = |from: usize| from + bytes[from..].iter().take_while(|b| b.is_digit()).count();
+	let is_digit = |i: usize| bytes.get(i).copied().is_digit();
+
+	let mut len = digits(matches!(bytes[0], ASCII::PLUS | ASCII::DASH) as usize);
+
+	if bytes.get(len) == Some(&ASCII::FULL_STOP) && is_digit(len + 1) {
+		len = digits(len + 1);
+	}
+
+	if matches!(bytes.get(len), Some(b'e' | b'E')) {
+		let sign = matches!(bytes.get(len + 1), Some(&(ASCII::PLUS | ASCII::DASH))) as usize;
+
+		if is_digit(len + 1 + sign) {
+			len = digits(len + 1 + sign);
+		}
+	}
+
+	len
+}
+
+impl<'a> Token<'a> {
+	/// Referenced input, [`None`] for tokens without any.
+	/// Dimension references only its value
+	#[inline]
+	pub fn bytes(self) -> Option<&'a [u8]> {
+		match self {
+			Token::Comment(bytes)
+			| Token::Ident(bytes)
+			| Token::Function(bytes)
+			| Token::URL(bytes)
+			| Token::AtRule(bytes)
+			| Token::Hash(bytes)
+			| Token::String(bytes)
+			| Token::Number(bytes)
+			| Token::Percentage(bytes)
+			| Token::Dimension { value: bytes, .. }
+			| Token::UnicodeRange(bytes) => Some(bytes),
+			_ => None,
+		}
+	}
+
+	/// Rebuilds raw token over `buf`, `raw` has to point into `buf`, starting at `base`
+	#[inline]
+	pub(crate) fn from_raw(raw: RawToken, buf: &'a [u8], base: *const u8) -> Self {
+		let bytes = || {
+			let bytes = raw.bytes();
+			let start = bytes.cast::<u8>().addr() - base.addr();
+
+			&buf[start..start + bytes.len()]
+		};
+
+		match raw {
+			RawToken::Comment(..) => Token::Comment(bytes()),
+			RawToken::Ident(..) => Token::Ident(bytes()),
+			RawToken::Function(..) => Token::Function(bytes()),
+			RawToken::AtRule(..) => Token::AtRule(bytes()),
+			RawToken::Hash(..) => Token::Hash(bytes()),
+			RawToken::String(..) => Token::String(bytes()),
+			RawToken::Number(..) => Token::Number(bytes()),
+			RawToken::Percentage(..) => Token::Percentage(bytes()),
+			RawToken::Dimension(..) => {
+				let (value, unit) = bytes().split_at(number_len(bytes()));
+				Token::Dimension { value, unit }
+			}
+			RawToken::UnicodeRange(..) => Token::UnicodeRange(bytes()),
+			RawToken::URL(..) => Token::URL(bytes()),
+			RawToken::BadURL => Token::BadURL,
+			RawToken::CDO => Token::CDO,
+			RawToken::CDC => Token::CDC,
+			RawToken::Delim(delim) => Token::Delim(delim),
+			RawToken::Whitespace(newline) => Token::Whitespace(newline),
+			RawToken::Colon => Token::Colon,
+			RawToken::Semicolon => Token::Semicolon,
+			RawToken::Comma => Token::Comma,
+			RawToken::BracketRoundOpen => Token::BracketRoundOpen,
+			RawToken::BracketRoundClose => Token::BracketRoundClose,
+			RawToken::BracketSquareOpen => Token::BracketSquareOpen,
+			RawToken::BracketSquareClose => Token::BracketSquareClose,
+			RawToken::BracketCurlyOpen => Token::BracketCurlyOpen,
+			RawToken::BracketCurlyClose => Token::BracketCurlyClose,
+		}
+	}
+}
+
+impl std::fmt::Display for Token<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let name = match self {
+			Token::Comment(_) => "Comment",
+			Token::Ident(_) => "Ident",
+			Token::Function(_) => "Function",
+			Token::AtRule(_) => "AtRule",
+			Token::Hash(_) => "Hash",
+			Token::String(_) => "String",
+			Token::Number(_) => "Number",
+			Token::Percentage(_) => "Percentage",
+			Token::UnicodeRange(_) => "UnicodeRange",
+			Token::URL(_) => "URL",
+			Token::BadURL => "BadURL",
+			Token::CDO => "CDO",
+			Token::CDC => "CDC",
+
+			Token::Dimension { value, unit } => {
+				return write!(
+					f,
+					"Dimension(\"{}\", \"{}\")",
+					String::from_utf8_lossy(value),
+					String::from_utf8_lossy(unit)
+				);
+			}
+
+			Token::Delim(d) => return write!(f, "Delim({})", *d as char),
+
+			Token::Whitespace(true) => "Whitespace with linebreak",
+			Token::Whitespace(false) => "Whitespace",
+			Token::Colon => "Colon",
+			Token::Semicolon => "Semicolon",
+			Token::Comma => "Comma",
+			Token::BracketRoundOpen => "BracketRoundOpen",
+			Token::BracketRoundClose => "BracketRoundClose",
+			Token::BracketSquareOpen => "BracketSquareOpen",
+			Token::BracketSquareClose => "BracketSquareClose",
+			Token::BracketCurlyOpen => "BracketCurlyOpen",
+			Token::BracketCurlyClose => "BracketCurlyClose",
+		};
+
+		match self.bytes() {
+			Some(bytes) => write!(f, "{name}(\"{}\")", String::from_utf8_lossy(bytes)),
+			None => f.write_str(name),
+		}
+	}
+}
+
+impl std::fmt::Display for RawToken {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		use std::str::from_utf8_unchecked as str;
 
@@ -102,26 +299,35 @@ impl std::fmt::Display for Token {
 		unsafe {
 			#[allow(unreachable_patterns)]
 			match self {
-				Token::Comment(..) => write!(f, "Comment(\"{}\")", str(&*self.bytes())),
-				Token::Ident(..) => write!(f, "Ident(\"{}\")", str(&*self.bytes())),
-				Token::Function(..) => write!(f, "Function(\"{}\")", str(&*self.bytes())),
-				Token::AtRule(..) => write!(f, "AtRule(\"{}\")", str(&*self.bytes())),
-				Token::Hash(..) => write!(f, "Hash(\"{}\")", str(&*self.bytes())),
-				Token::String(..) => write!(f, "String(\"{}\")", str(&*self.bytes())),
-				Token::Number(..) => write!(f, "Number(\"{}\")", str(&*self.bytes())),
-
-				Token::Delim(d) => write!(f, "Delim({})", *d as char),
-
-				Token::Whitespace(newline) => f.write_str(fmt_newline(*newline)),
-				Token::Colon => f.write_str("Colon"),
-				Token::Semicolon => f.write_str("Semicolon"),
-				Token::Comma => f.write_str("Comma"),
-				Token::BracketRoundOpen => f.write_str("BracketRoundOpen"),
-				Token::BracketRoundClose => f.write_str("BracketRoundClose"),
-				Token::BracketSquareOpen => f.write_str("BracketSquareOpen"),
-				Token::BracketSquareClose => f.write_str("BracketSquareClose"),
-				Token::BracketCurlyOpen => f.write_str("BracketCurlyOpen"),
-				Token::BracketCurlyClose => f.write_str("BracketCurlyClose"),
+				RawToken::Comment(..) => write!(f, "Comment(\"{}\")", str(&*self.bytes())),
+				RawToken::Ident(..) => write!(f, "Ident(\"{}\")", str(&*self.bytes())),
+				RawToken::Function(..) => write!(f, "Function(\"{}\")", str(&*self.bytes())),
+				RawToken::AtRule(..) => write!(f, "AtRule(\"{}\")", str(&*self.bytes())),
+				RawToken::Hash(..) => write!(f, "Hash(\"{}\")", str(&*self.bytes())),
+				RawToken::String(..) => write!(f, "String(\"{}\")", str(&*self.bytes())),
+				RawToken::Number(..) => write!(f, "Number(\"{}\")", str(&*self.bytes())),
+				RawToken::Percentage(..) => write!(f, "Percentage(\"{}\")", str(&*self.bytes())),
+				RawToken::Dimension(..) => write!(f, "Dimension(\"{}\")", str(&*self.bytes())),
+				RawToken::UnicodeRange(..) => {
+					write!(f, "UnicodeRange(\"{}\")", str(&*self.bytes()))
+				}
+				RawToken::URL(..) => write!(f, "URL(\"{}\")", str(&*self.bytes())),
+				RawToken::BadURL => f.write_str("BadURL"),
+				RawToken::CDO => f.write_str("CDO"),
+				RawToken::CDC => f.write_str("CDC"),
+
+				RawToken::Delim(d) => write!(f, "Delim({})", *d as char),
+
+				RawToken::Whitespace(newline) => f.write_str(fmt_newline(*newline)),
+				RawToken::Colon => f.write_str("Colon"),
+				RawToken::Semicolon => f.write_str("Semicolon"),
+				RawToken::Comma => f.write_str("Comma"),
+				RawToken::BracketRoundOpen => f.write_str("BracketRoundOpen"),
+				RawToken::BracketRoundClose => f.write_str("BracketRoundClose"),
+				RawToken::BracketSquareOpen => f.write_str("BracketSquareOpen"),
+				RawToken::BracketSquareClose => f.write_str("BracketSquareClose"),
+				RawToken::BracketCurlyOpen => f.write_str("BracketCurlyOpen"),
+				RawToken::BracketCurlyClose => f.write_str("BracketCurlyClose"),
 
 				_ => todo!(),
 			}
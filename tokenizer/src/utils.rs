@@ -1,6 +1,9 @@
 pub trait ByteHelper {
 	fn is_digit(&self) -> bool;
+	fn is_hex_digit(&self) -> bool;
+	fn is_ident_char(&self) -> bool;
 	fn is_ident_start(&self) -> bool;
+	fn is_whitespace(&self) -> bool;
 }
 
 pub trait ByteArrayHelper {
@@ -13,9 +16,25 @@ impl ByteHelper for u8 {
 		self.is_ascii_digit()
 	}
 
+	#[inline(always)]
+	fn is_hex_digit(&self) -> bool {
+		self.is_ascii_hexdigit()
+	}
+
+	#[inline(always)]
+	fn is_ident_char(&self) -> bool {
+		self.is_ident_start() || matches!(self, b'0'..=b'9' | b'-')
+	}
+
+	/// Any byte of a non-ASCII code point starts an ident
 	#[inline(always)]
 	fn is_ident_start(&self) -> bool {
-		matches!(self, b'a'..=b'z' | b'A'..=b'Z' | b'_')
+		matches!(self, b'a'..=b'z' | b'A'..=b'Z' | b'_' | 0x80..)
+	}
+
+	#[inline(always)]
+	fn is_whitespace(&self) -> bool {
+		matches!(self, b' ' | b'\t' | b'\n' | b'\r')
 	}
 }
 
@@ -25,8 +44,23 @@ impl ByteHelper for Option<u8> {
 		matches!(self, Some(x) if x.is_digit())
 	}
 
+	#[inline(always)]
+	fn is_hex_digit(&self) -> bool {
+		matches!(self, Some(x) if x.is_hex_digit())
+	}
+
+	#[inline(always)]
+	fn is_ident_char(&self) -> bool {
+		matches!(self, Some(x) if x.is_ident_char())
+	}
+
 	#[inline(always)]
 	fn is_ident_start(&self) -> bool {
 		matches!(self, Some(x) if x.is_ident_start())
 	}
+
+	#[inline(always)]
+	fn is_whitespace(&self) -> bool {
+		matches!(self, Some(x) if x.is_whitespace())
+	}
 }
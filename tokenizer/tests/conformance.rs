@@ -0,0 +1,41 @@
+//! Runs conformance vectors from `tests/conformance/*.json` against [`Tokenizer`],
+//! see `tests/conformance/support.rs` for the format
+
+use serde_json::{json, Value};
+use tokenizer::{Error, Token, Tokenizer};
+
+use support::to_json;
+
+fn tokenize(input: &str) -> Value {
+	let mut tokens = Vec::new();
+
+	for res in Tokenizer::new(input.as_bytes()) {
+		let token = match res {
+			Ok(token) => token,
+			Err(err) => {
+				tokens.push(match err {
+					Error::BadComment => json!(["error", "bad-comment"]),
+					Error::BadString => json!(["error", "bad-string"]),
+					Error::EOF => unreachable!("iterator stops on EOF"),
+				});
+
+				break;
+			}
+		};
+
+		tokens.push(to_json!(
+			token,
+			token => unreachable!("{token} is not covered by conformance vectors"),
+		));
+	}
+
+	Value::Array(tokens)
+}
+
+#[test]
+fn conformance() {
+	support::run(tokenize);
+}
+
+#[path = "conformance/support.rs"]
+mod support;
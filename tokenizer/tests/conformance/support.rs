@@ -0,0 +1,92 @@
+//! Shared by `tokenizer` and `hel-css-formatter` conformance tests.
+//!
+//! Every file is an array of `{ "name", "input", "tokens" }` cases, where token is
+//! `[kind, ...payload]` and payload is the raw source slice, escapes are never decoded.
+//! Parse errors the spec recovers from end the case with `["error", kind]`
+
+use std::path::Path;
+
+use serde_json::Value;
+
+const DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../tokenizer/tests/conformance");
+
+/// Maps `Token` of either tokenizer to its JSON form, extra arms go last
+macro_rules! to_json {
+	($token:expr $(, $pat:pat => $arm:expr)* $(,)?) => {{
+		use serde_json::json;
+
+		fn str(bytes: &[u8]) -> &str {
+			std::str::from_utf8(bytes).expect("token is split on ASCII bytes")
+		}
+
+		match $token {
+			Token::Comment(bytes) => json!(["comment", str(bytes)]),
+			Token::Ident(bytes) => json!(["ident", str(bytes)]),
+			Token::Function(bytes) => json!(["function", str(bytes)]),
+			Token::URL(bytes) => json!(["url", str(bytes)]),
+			Token::BadURL => json!(["bad-url"]),
+			Token::AtRule(bytes) => json!(["at-keyword", str(bytes)]),
+			Token::Hash(bytes) => json!(["hash", str(bytes)]),
+			Token::String(bytes) => json!(["string", str(bytes)]),
+			Token::Number(bytes) => json!(["number", str(bytes)]),
+			Token::Percentage(bytes) => json!(["percentage", str(bytes)]),
+			Token::Dimension { value, unit } => json!(["dimension", str(value), str(unit)]),
+			Token::UnicodeRange(bytes) => json!(["unicode-range", str(bytes)]),
+			Token::CDO => json!(["cdo"]),
+			Token::CDC => json!(["cdc"]),
+			Token::Delim(delim) => json!(["delim", (delim as char).to_string()]),
+			Token::Whitespace { .. } => json!(["whitespace"]),
+			Token::Colon => json!([":"]),
+			Token::Semicolon => json!([";"]),
+			Token::Comma => json!([","]),
+			Token::BracketRoundOpen => json!(["("]),
+			Token::BracketRoundClose => json!([")"]),
+			Token::BracketSquareOpen => json!(["["]),
+			Token::BracketSquareClose => json!(["]"]),
+			Token::BracketCurlyOpen => json!(["{"]),
+			Token::BracketCurlyClose => json!(["}"]),
+			$($pat => $arm,)*
+		}
+	}};
+}
+
+pub(crate) use to_json;
+
+/// Runs every vector through `tokenize`, reporting each mismatch before failing
+pub fn run(tokenize: impl Fn(&str) -> Value) {
+	let mut files: Vec<_> = std::fs::read_dir(DIR)
+		.expect("conformance directory")
+		.map(|entry| entry.expect("readable entry").path())
+		.filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+		.collect();
+	files.sort();
+
+	let mut total = 0;
+	let mut failed = 0;
+
+	for path in files {
+		let file = path.file_stem().unwrap().to_string_lossy();
+		let cases: Vec<Value> =
+			serde_json::from_slice(&std::fs::read(&path).unwrap()).expect("valid JSON array");
+
+		for case in cases {
+			let name = case["name"].as_str().expect("case name");
+			let input = case["input"].as_str().expect("case input");
+			let actual = tokenize(input);
+
+			total += 1;
+
+			if actual != case["tokens"] {
+				failed += 1;
+
+				eprintln!("FAIL {file}/{name}");
+				eprintln!("  input:    {input:?}");
+				eprintln!("  expected: {}", case["tokens"]);
+				eprintln!("  actual:   {actual}");
+			}
+		}
+	}
+
+	assert!(total > 0, "no conformance vectors found in {}", Path::new(DIR).display());
+	assert_eq!(failed, 0, "{failed} of {total} conformance vectors failed");
+}
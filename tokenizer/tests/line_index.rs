@@ -0,0 +1,46 @@
+use tokenizer::{LineColumn, LineIndex};
+
+const INPUT: &[u8] = "a {\r\n\tcontent: \"日本\";\n}\n".as_bytes();
+
+fn at(line: usize, column: usize) -> LineColumn {
+	LineColumn { line, column }
+}
+
+#[test]
+fn line_column() {
+	let index = LineIndex::new(INPUT);
+
+	assert_eq!(index.line_count(), 4);
+
+	assert_eq!(index.line_column(0), at(1, 1));
+	// `\r` belongs to the line
+	assert_eq!(index.line_column(3), at(1, 4));
+	assert_eq!(index.line_column(5), at(2, 1));
+	// Multibyte characters take a single column
+	assert_eq!(index.line_column(19), at(2, 13));
+	assert_eq!(index.line_column(20), at(2, 14));
+	assert_eq!(index.line_column(INPUT.len()), at(4, 1));
+	assert_eq!(index.line_column(usize::MAX), at(4, 1));
+}
+
+#[test]
+fn offset() {
+	let index = LineIndex::new(INPUT);
+
+	for offset in 0..=INPUT.len() {
+		// Only offsets at character boundaries survive a round trip
+		if INPUT.get(offset).is_some_and(|&byte| (byte as i8) < -0x40) {
+			continue;
+		}
+
+		assert_eq!(index.offset(index.line_column(offset)), Some(offset));
+	}
+
+	// Right after the last character of a line
+	assert_eq!(index.offset(at(3, 2)), Some(INPUT.len() - 1));
+
+	assert_eq!(index.offset(at(3, 3)), None);
+	assert_eq!(index.offset(at(5, 1)), None);
+	assert_eq!(index.offset(at(0, 1)), None);
+	assert_eq!(index.offset(at(1, 0)), None);
+}
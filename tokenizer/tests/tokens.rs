@@ -1 +1,235 @@
+use tokenizer::{Error, LaneWidth, Span, Spanned, Token, Tokenizer, MAX_LANE_WIDTH};
 
+const INPUT: &[u8] = b"a { /* long enough to span over a lane */ content: \"a\"; width: 1px }";
+
+fn collect(input: &[u8]) -> Vec<Token<'_>> {
+	collect_with(input, LaneWidth::detect())
+}
+
+fn collect_with(input: &[u8], lanes: LaneWidth) -> Vec<Token<'_>> {
+	Tokenizer::with_lane_width(input, lanes)
+		.filter(|token| !matches!(token, Ok(Token::Whitespace(_))))
+		.collect::<Result<_, _>>()
+		.expect("valid input")
+}
+
+fn supported_lanes() -> impl Iterator<Item = LaneWidth> {
+	LaneWidth::ALL.into_iter().filter(|lanes| lanes.is_supported())
+}
+
+#[test]
+fn any_alignment() {
+	let mut buf = Vec::with_capacity(INPUT.len() + MAX_LANE_WIDTH);
+
+	// At least one of the offsets is not aligned, and one is
+	let cases =
+		(0..MAX_LANE_WIDTH).flat_map(|offset| supported_lanes().map(move |lanes| (offset, lanes)));
+
+	for (offset, lanes) in cases {
+		buf.clear();
+		buf.resize(offset, b' ');
+		buf.extend_from_slice(INPUT);
+
+		let input = &buf[offset..];
+		let tokens = collect_with(input, lanes);
+
+		assert_eq!(
+			tokens,
+			[
+				Token::Ident(b"a"),
+				Token::BracketCurlyOpen,
+				Token::Comment(b" long enough to span over a lane "),
+				Token::Ident(b"content"),
+				Token::Colon,
+				Token::String(b"a"),
+				Token::Semicolon,
+				Token::Ident(b"width"),
+				Token::Colon,
+				Token::Dimension {
+					value: b"1",
+					unit: b"px"
+				},
+				Token::BracketCurlyClose,
+			]
+		);
+
+		// Borrowed straight from the input, not from an aligned copy
+		let Token::Comment(comment) = tokens[2] else {
+			unreachable!()
+		};
+
+		assert!(input.as_ptr_range().contains(&comment.as_ptr()));
+	}
+}
+
+#[test]
+fn lane_widths() {
+	assert!(LaneWidth::detect().is_supported());
+	assert!(LaneWidth::W16.is_supported());
+
+	// Every scanner, with runs longer than the widest lanes
+	let fill = "x".repeat(MAX_LANE_WIDTH * 3);
+	let input = format!(
+		"/* {fill} */ .{fill} {{ content: \"{fill}\\\" {fill}\";{}}}\n\t\t#{fill} '{fill}",
+		" ".repeat(MAX_LANE_WIDTH * 2)
+	);
+
+	let expected: Vec<_> = Tokenizer::with_lane_width(input.as_bytes(), LaneWidth::W16).collect();
+
+	for lanes in supported_lanes() {
+		let tokenizer = Tokenizer::with_lane_width(input.as_bytes(), lanes);
+		assert_eq!(tokenizer.lane_width(), lanes);
+
+		assert_eq!(tokenizer.collect::<Vec<_>>(), expected, "{lanes}");
+	}
+
+	assert_eq!(expected.last(), Some(&Err(Error::BadString)));
+}
+
+#[test]
+fn seek() {
+	let mut tokenizer = Tokenizer::new(INPUT);
+
+	tokenizer.seek(INPUT.len() - 1);
+	assert_eq!(tokenizer.next_token(), Ok(Token::BracketCurlyClose));
+	assert_eq!(tokenizer.next_token(), Err(Error::EOF));
+
+	// Never past the end
+	tokenizer.seek(usize::MAX);
+	assert_eq!(tokenizer.pos(), INPUT.len());
+
+	tokenizer.seek(2);
+	assert_eq!(tokenizer.next_token(), Ok(Token::BracketCurlyOpen));
+	assert_eq!(tokenizer.pos(), 3);
+}
+
+#[test]
+fn errors() {
+	assert_eq!(Tokenizer::new(b"/* a").last(), Some(Err(Error::BadComment)));
+	assert_eq!(Tokenizer::new(b"\"a\nb").next(), Some(Err(Error::BadString)));
+	assert_eq!(Tokenizer::new(b"").next(), None);
+}
+
+#[test]
+fn numeric() {
+	assert_eq!(
+		collect(b"1 -.5% +1.5e-10px 1e 1.x 2n+1"),
+		[
+			Token::Number(b"1"),
+			Token::Percentage(b"-.5"),
+			Token::Dimension {
+				value: b"+1.5e-10",
+				unit: b"px"
+			},
+			Token::Dimension {
+				value: b"1",
+				unit: b"e"
+			},
+			Token::Number(b"1"),
+			Token::Delim(b'.'),
+			Token::Ident(b"x"),
+			Token::Dimension {
+				value: b"2",
+				unit: b"n"
+			},
+			Token::Number(b"+1"),
+		]
+	);
+}
+
+#[test]
+fn url() {
+	assert_eq!(
+		collect(b"url(data:image/png;base64,AA==) URL(  a.png  ) url( \"a.png\") url(a b) url(a"),
+		[
+			Token::URL(b"data:image/png;base64,AA=="),
+			Token::URL(b"a.png"),
+			Token::Function(b"url("),
+			Token::String(b"a.png"),
+			Token::BracketRoundClose,
+			Token::BadURL,
+			Token::URL(b"a"),
+		]
+	);
+}
+
+#[test]
+fn unicode_range_and_cdo_cdc() {
+	assert_eq!(
+		collect(b"<!-- U+0025-00FF u+4?? U+ --> <!"),
+		[
+			Token::CDO,
+			Token::UnicodeRange(b"U+0025-00FF"),
+			Token::UnicodeRange(b"u+4??"),
+			Token::Ident(b"U"),
+			Token::Delim(b'+'),
+			Token::CDC,
+			Token::Delim(b'<'),
+			Token::Delim(b'!'),
+		]
+	);
+}
+
+#[test]
+fn escapes() {
+	assert_eq!(
+		collect(b".\\31 0col .sm\\:flex #\\#id -\\-x 1\\70x \"a\\\"b\\\r\nc\" \\\n"),
+		[
+			Token::Delim(b'.'),
+			Token::Ident(b"\\31 0col"),
+			Token::Delim(b'.'),
+			Token::Ident(b"sm\\:flex"),
+			Token::Hash(b"#\\#id"),
+			Token::Ident(b"-\\-x"),
+			Token::Dimension {
+				value: b"1",
+				unit: b"\\70x"
+			},
+			Token::String(b"a\\\"b\\\r\nc"),
+			// Escaped newline is not an escape outside of strings
+			Token::Delim(b'\\'),
+		]
+	);
+}
+
+#[test]
+fn spans() {
+	let input = b"a{\0b: 1px}/* c";
+	let spans: Vec<_> = Tokenizer::new(input)
+		.spanned()
+		.map(|res| res.map_or_else(|err| err.span, |token| token.span))
+		.collect();
+
+	assert_eq!(
+		spans,
+		[
+			Span::new(0, 1),
+			Span::new(1, 2),
+			// Null byte is skipped
+			Span::new(3, 4),
+			Span::new(4, 5),
+			Span::new(5, 6),
+			Span::new(6, 9),
+			Span::new(9, 10),
+			Span::new(10, 14),
+		]
+	);
+
+	let mut tokenizer = Tokenizer::new(input);
+	tokenizer.seek(10);
+
+	assert_eq!(
+		tokenizer.next_spanned(),
+		Err(Spanned {
+			value: Error::BadComment,
+			span: Span::new(10, 14)
+		})
+	);
+	assert_eq!(
+		tokenizer.next_spanned(),
+		Err(Spanned {
+			value: Error::EOF,
+			span: Span::new(14, 14)
+		})
+	);
+}